@@ -0,0 +1,93 @@
+/*!
+ * Bytes per second through `Translator::write` into a fresh grid, for the
+ * kinds of output terminals spend their time on. Run with
+ * `cargo bench -p firn-core`; criterion reports changes against the last run.
+ */
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use firn_core::data::DataComponent;
+use firn_core::translator::Translator;
+
+/** Lines of output in each workload, about what a build log scrolls past */
+const LINES: usize = 2_000;
+
+fn plain_text() -> Vec<u8> {
+    (0..LINES)
+        .map(|i| format!("{i:>6} the quick brown fox jumps over the lazy dog, again and again\r\n"))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/** Every word in its own colors, as `ls --color` or a syntax highlighter writes */
+fn heavy_sgr() -> Vec<u8> {
+    (0..LINES)
+        .map(|i| {
+            (0..10)
+                .map(|word| {
+                    let color = (i + word) % 256;
+                    format!("\x1b[1;38;5;{color}m\x1b[48;2;{word};0;{color}mword\x1b[0m ")
+                })
+                .collect::<String>()
+                + "\r\n"
+        })
+        .collect::<String>()
+        .into_bytes()
+}
+
+/** Lines that fit the screen, one after another, as `cat` of a big file scrolls */
+fn scrolling() -> Vec<u8> {
+    (0..LINES * 4)
+        .map(|i| format!("{i}\r\n"))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/**
+ * A full-screen program scrolling within a region and inserting and
+ * deleting lines, as a pager or an editor does
+ */
+fn scroll_region() -> Vec<u8> {
+    let mut bytes = String::from("\x1b[2;23r");
+    for i in 0..LINES {
+        bytes +=
+            &format!("\x1b[23;1H\r\n{i} scrolled into the region\x1b[5;1H\x1b[L\x1b[10;1H\x1b[M");
+    }
+    bytes.into_bytes()
+}
+
+/** Insertions and erasures within a line, as readline redraws a prompt */
+fn line_editing() -> Vec<u8> {
+    (0..LINES)
+        .map(|i| format!("\r$ git commit -m \"message {i}\"\x1b[4@\x1b[K"))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/** Generates the bytes a workload writes */
+type Workload = fn() -> Vec<u8>;
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("throughput");
+    let workloads: [(&str, Workload); 5] = [
+        ("plain_text", plain_text),
+        ("heavy_sgr", heavy_sgr),
+        ("scrolling", scrolling),
+        ("scroll_region", scroll_region),
+        ("line_editing", line_editing),
+    ];
+    for (name, workload) in workloads {
+        let input = workload();
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || (DataComponent::new(), Translator::new().unwrap()),
+                |(data, translator)| translator.write(&input, data),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+use crate::data::{Cell, CellWidth};
+use unicode_bidi::BidiInfo;
+
+/**
+ * Where each cell of a line is shown under the Unicode Bidirectional
+ * Algorithm, as `visual[logical]`, so that Arabic and Hebrew read right to
+ * left. Each line is a paragraph of its own, whose direction is that of its
+ * first strong character. `None` if the line holds no right-to-left text
+ * and so is shown as it is.
+ */
+pub fn visual_columns(line: &[Cell]) -> Option<Vec<usize>> {
+    let mut text = String::new();
+    // The byte each cell's text starts at, for mapping runs back to cells
+    let mut starts = Vec::with_capacity(line.len());
+    for cell in line {
+        starts.push(text.len());
+        text += cell.text();
+    }
+    let info = BidiInfo::new(&text, None);
+    if !info.has_rtl() {
+        return None;
+    }
+    let mut visual = vec![0; line.len()];
+    let mut next = 0;
+    for paragraph in &info.paragraphs {
+        let (levels, runs) = info.visual_runs(paragraph, paragraph.range.clone());
+        for run in runs {
+            let first = starts.partition_point(|&start| start < run.start);
+            let last = starts.partition_point(|&start| start < run.end);
+            let cells: Box<dyn Iterator<Item = usize>> = if levels[run.start].is_rtl() {
+                Box::new((first..last).rev())
+            } else {
+                Box::new(first..last)
+            };
+            for cell in cells {
+                visual[cell] = next;
+                next += 1;
+            }
+        }
+    }
+    // A wide character reversed with its spacer would be drawn over the cell after it
+    for col in 1..line.len() {
+        if line[col].width == CellWidth::Spacer && visual[col] < visual[col - 1] {
+            visual.swap(col - 1, col);
+        }
+    }
+    Some(visual)
+}
+
+/** The cell shown at column `visual`, given what `visual_columns` returned for its line */
+pub fn logical_column(visual_columns: &[usize], visual: usize) -> usize {
+    visual_columns
+        .iter()
+        .position(|&col| col == visual)
+        .unwrap_or(visual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> Vec<Cell> {
+        text.chars()
+            .map(|ch| Cell {
+                grapheme: Some(ch.into()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_visual_columns() {
+        assert_eq!(visual_columns(&line("plain ascii")), None);
+
+        // "shalom" in Hebrew, after an English word: the left-to-right paragraph keeps its order
+        let mixed = line("hi שלום!");
+        let visual = visual_columns(&mixed).unwrap();
+        assert_eq!(visual, [0, 1, 2, 6, 5, 4, 3, 7]);
+        assert_eq!(logical_column(&visual, 3), 6);
+        assert_eq!(logical_column(&visual, 20), 20);
+
+        // A right-to-left paragraph runs from the right, with the number kept left to right
+        let visual = visual_columns(&line("שלום 42")).unwrap();
+        assert_eq!(visual, [6, 5, 4, 3, 2, 0, 1]);
+    }
+}
@@ -0,0 +1,826 @@
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tracing::{debug, debug_span, error, info};
+
+use crate::handler::TerminalHandler;
+use crate::pen::Attributes;
+use crate::rgb::Rgb;
+use crate::search::SearchMatch;
+use crate::selection::Selection;
+use crate::stats::SequenceStats;
+use crate::width::{self, WidthPolicy};
+
+/**
+ * A safe way to interact with a ragged array of cells, indexed
+ * by an 'active position' (cursor)
+ */
+pub struct DataComponent {
+    lines: Vec<Line>,
+    active_position: Position,
+    attributes: Attributes,
+    responses: Vec<u8>,
+    stats: SequenceStats,
+    working_directory: Option<PathBuf>,
+    /** Set by the shell via OSC 0 or 2 */
+    title: Option<String>,
+    /** What OSC 10 and 11 queries are answered with */
+    default_foreground: Rgb,
+    default_background: Rgb,
+    /** BEL arrived since the window last looked */
+    bell: bool,
+    /** Rows changed since the renderer last looked */
+    damage: BTreeSet<usize>,
+    modes: Modes,
+    width_policy: WidthPolicy,
+    /** Where the shell marked prompts, commands and their output, oldest first */
+    prompt_marks: Vec<(Position, PromptMark)>,
+}
+
+struct Line {
+    cells: Vec<Cell>,
+}
+
+#[derive(Clone, Default)]
+pub struct Cell {
+    pub grapheme: Option<String>,
+    pub attributes: Attributes,
+    pub width: CellWidth,
+}
+
+/** Wide characters, like CJK and most emoji, take up a cell and the spacer after it */
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum CellWidth {
+    #[default]
+    Narrow,
+    Wide,
+    /** The right half of a wide character, which holds no text of its own */
+    Spacer,
+}
+
+impl Cell {
+    /** The cell's text as copied: a space if it is blank, and nothing if it is a spacer */
+    pub fn text(&self) -> &str {
+        match (&self.grapheme, self.width) {
+            (_, CellWidth::Spacer) => "",
+            (Some(grapheme), _) => grapheme,
+            (None, _) => " ",
+        }
+    }
+}
+
+/** Terminal modes that change how firn encodes input */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Modes {
+    /** DECSET 2004: wrap pasted text in `CSI 200~` ... `CSI 201~` */
+    pub bracketed_paste: bool,
+    /** DECCKM (DECSET 1): cursor keys send `SS3` rather than `CSI` sequences */
+    pub application_cursor: bool,
+    /** DECKPAM (`ESC =`): the keypad sends `SS3` sequences rather than digits */
+    pub application_keypad: bool,
+    /** DECSET 2031: report switches between light and dark colors with `CSI ? 997 ; n n` */
+    pub color_scheme_updates: bool,
+    /**
+     * BDSM (`CSI 8 l`): the program lays out right-to-left text itself, so
+     * lines are shown in the order they were written
+     */
+    pub explicit_bidi: bool,
+    /**
+     * DECSET 47, 1047 or 1049: a full-screen program like less or vim is
+     * running. firn has only the one screen to draw on, but knows to send
+     * it the mouse wheel as keys.
+     */
+    pub alternate_screen: bool,
+    /** DECSET 1000, 1002 or 1003: the program asked for mouse events, which firn does not report */
+    pub mouse_reporting: bool,
+}
+
+/**
+ * What starts where a shell puts an OSC 133 mark, in the shell integration
+ * FinalTerm introduced
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PromptMark {
+    /** `A`: the prompt */
+    Prompt,
+    /** `B`: the command the user types */
+    Command,
+    /** `C`: the command's output */
+    Output,
+    /** `D`: the command finished, so its output has ended */
+    Finished,
+}
+
+/** Bi-directional support mode, which says whether firn or the program orders right-to-left text */
+const BDSM: &str = "8";
+
+/** Unlike the standard, is 0-indexed */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Position {
+    pub row: usize,
+    pub col: usize,
+}
+
+/** Whether `ch` belongs in a word: letters and digits do, and so do `word_characters` */
+pub fn is_word_character(ch: char, word_characters: &str) -> bool {
+    ch.is_alphanumeric() || word_characters.contains(ch)
+}
+
+impl Default for DataComponent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataComponent {
+    pub fn new() -> Self {
+        Self {
+            lines: vec![Line {
+                cells: vec![Cell::default()],
+            }],
+            active_position: Position { row: 0, col: 0 },
+            attributes: Attributes::default(),
+            responses: Vec::new(),
+            stats: SequenceStats::new(),
+            working_directory: None,
+            title: None,
+            default_foreground: Rgb(0xE5, 0xE5, 0xE5),
+            default_background: Rgb(0x20, 0x22, 0x25),
+            bell: false,
+            damage: BTreeSet::from([0]),
+            modes: Modes::default(),
+            width_policy: WidthPolicy::default(),
+            prompt_marks: vec![],
+        }
+    }
+
+    /** Where the shell marked prompts, commands and their output with OSC 133, oldest first */
+    pub fn prompt_marks(&self) -> &[(Position, PromptMark)] {
+        &self.prompt_marks
+    }
+
+    /** Handle OSC 133, whose first argument says what starts at the active position */
+    fn mark_prompt(&mut self, arguments: &str) {
+        let mark = match arguments.split(';').next() {
+            Some("A") => PromptMark::Prompt,
+            Some("B") => PromptMark::Command,
+            Some("C") => PromptMark::Output,
+            Some("D") => PromptMark::Finished,
+            _ => return error!("Unexpected OSC 133 argument {arguments:?}"),
+        };
+        self.prompt_marks.push((self.active_position.clone(), mark));
+    }
+
+    /** Forget the marks on the first `dropped` lines, and renumber the rest */
+    fn drop_prompt_marks(&mut self, dropped: usize) {
+        self.prompt_marks
+            .retain(|(position, _)| position.row >= dropped);
+        for (position, _) in &mut self.prompt_marks {
+            position.row -= dropped;
+        }
+    }
+
+    /** How wide characters written from now on are; the ones already on screen stay as they are */
+    pub fn set_width_policy(&mut self, policy: WidthPolicy) {
+        self.width_policy = policy;
+    }
+
+    /** Take the set of rows changed since the last call, so the renderer can skip redraws */
+    pub fn take_damage(&mut self) -> BTreeSet<usize> {
+        std::mem::take(&mut self.damage)
+    }
+
+    /** The working directory most recently reported by the shell via OSC 7 */
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.working_directory.as_deref()
+    }
+
+    /** Handle OSC 7, which reports the working directory as a `file://host/path` URI */
+    fn set_working_directory(&mut self, uri: &str) {
+        let path = uri
+            .strip_prefix("file://")
+            .and_then(|host_and_path| host_and_path.find('/').map(|i| &host_and_path[i..]));
+        match path {
+            Some(path) => self.working_directory = Some(percent_decode(path).into()),
+            None => error!("Unexpected OSC 7 argument {uri:?}"),
+        }
+    }
+
+    /** The window title most recently set by the shell */
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn stats(&self) -> &SequenceStats {
+        &self.stats
+    }
+
+    fn ignore(&mut self, sequence: String) {
+        info!("Ignoring {sequence}");
+        self.stats.record_ignored(sequence);
+    }
+
+    /** Whether the bell rang since this was last called */
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell)
+    }
+
+    /** Take any reports queued in reply to queries, to be written back to the pty */
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    fn respond(&mut self, response: &str) {
+        debug!("Respond with {response:?}");
+        self.responses.extend_from_slice(response.as_bytes());
+    }
+
+    pub fn device_status_report(&mut self, n: &str) {
+        match n {
+            "5" => self.respond("\x1B[0n"),
+            "6" => {
+                let Position { row, col } = self.get_active_position();
+                self.respond(&format!("\x1B[{};{}R", row + 1, col + 1));
+            }
+            "?996" => self.report_color_scheme(),
+            _ => error!("Unexpected DSR argument {n:?}"),
+        }
+    }
+
+    /** Change the colors the shell is told about, e.g. after switching color schemes */
+    pub fn set_default_colors(&mut self, foreground: Rgb, background: Rgb) {
+        let changed = background.is_dark() != self.default_background.is_dark();
+        self.default_foreground = foreground;
+        self.default_background = background;
+        if changed && self.modes.color_scheme_updates {
+            self.report_color_scheme();
+        }
+    }
+
+    fn report_color_scheme(&mut self) {
+        let scheme = if self.default_background.is_dark() {
+            1
+        } else {
+            2
+        };
+        self.respond(&format!("\x1B[?997;{scheme}n"));
+    }
+
+    /** Answer OSC 10 (foreground) or 11 (background) queries; setting them is not supported */
+    fn report_default_color(&mut self, command: &str, value: &str) {
+        if value != "?" {
+            return self.ignore(format!("OSC {command}"));
+        }
+        let Rgb(r, g, b) = match command {
+            "10" => self.default_foreground,
+            _ => self.default_background,
+        };
+        self.respond(&format!(
+            "\x1B]{command};rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}\x1B\\"
+        ));
+    }
+
+    pub fn device_attributes(&mut self, n: Option<&str>) {
+        match n {
+            None | Some("0") => self.respond("\x1B[?6c"),
+            Some(">") | Some(">0") => self.respond("\x1B[>0;0;0c"),
+            _ => error!("Unexpected DA argument {n:?}"),
+        }
+    }
+
+    pub fn modes(&self) -> Modes {
+        self.modes
+    }
+
+    /** A DEC private mode's flag, if it is one firn implements */
+    fn private_mode_mut(&mut self, mode: &str) -> Option<&mut bool> {
+        match mode {
+            "1" => Some(&mut self.modes.application_cursor),
+            "2004" => Some(&mut self.modes.bracketed_paste),
+            "2031" => Some(&mut self.modes.color_scheme_updates),
+            _ => None,
+        }
+    }
+
+    /** Set (SM/DECSET) or reset (RM/DECRST) modes */
+    pub fn set_modes(&mut self, n: &str, enabled: bool) {
+        let Some(modes) = n.strip_prefix('?') else {
+            if n == BDSM {
+                // Set is the implicit mode, where the terminal reorders right-to-left text
+                self.modes.explicit_bidi = !enabled;
+                return;
+            }
+            let final_byte = if enabled { 'h' } else { 'l' };
+            return self.ignore(format!("CSI {n}{final_byte}"));
+        };
+        for mode in modes.split(';') {
+            match mode {
+                "47" | "1047" | "1049" => self.modes.alternate_screen = enabled,
+                "1000" | "1002" | "1003" => self.modes.mouse_reporting = enabled,
+                _ => {}
+            }
+            match self.private_mode_mut(mode) {
+                Some(flag) => *flag = enabled,
+                None => {
+                    let final_byte = if enabled { 'h' } else { 'l' };
+                    self.ignore(format!("CSI ?{mode}{final_byte}"));
+                }
+            }
+        }
+    }
+
+    /** DECRQM: report a mode as set (1), reset (2) or not recognized (0) */
+    pub fn request_mode(&mut self, n: &str) {
+        match n.strip_prefix('?') {
+            Some(mode) => {
+                let state = match self.private_mode_mut(mode) {
+                    Some(true) => 1,
+                    Some(false) => 2,
+                    None => 0,
+                };
+                self.respond(&format!("\x1B[?{mode};{state}$y"))
+            }
+            None if n == BDSM => {
+                let state = if self.modes.explicit_bidi { 2 } else { 1 };
+                self.respond(&format!("\x1B[{n};{state}$y"))
+            }
+            None => self.respond(&format!("\x1B[{n};0$y")),
+        }
+    }
+
+    pub fn get_active_position(&self) -> Position {
+        self.active_position.clone()
+    }
+
+    fn get_active_line(&self) -> &Line {
+        &self.lines[self.active_position.row]
+    }
+
+    fn get_active_line_mut(&mut self) -> &mut Line {
+        self.damage.insert(self.active_position.row);
+        &mut self.lines[self.active_position.row]
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn line(&self, row: usize) -> &[Cell] {
+        &self.lines[row].cells
+    }
+
+    #[cfg(test)]
+    pub fn get_line_cells(&self, row: usize) -> impl Iterator<Item = &Cell> {
+        self.lines[row].cells.iter()
+    }
+
+    pub fn get_active_cell(&self) -> &Cell {
+        &self.get_active_line().cells[self.active_position.col]
+    }
+
+    pub fn get_active_cell_mut(&mut self) -> &mut Cell {
+        self.damage.insert(self.active_position.row);
+        &mut self.lines[self.active_position.row].cells[self.active_position.col]
+    }
+
+    /** Move the active cell to the right, adding a new empty cell if one does not already exist. */
+    pub fn activate_next_cell(&mut self) {
+        self.active_position.col += 1;
+        assert!(self.active_position.col <= self.get_active_line().cells.len());
+        if self.active_position.col == self.get_active_line().cells.len() {
+            self.get_active_line_mut().cells.push(Cell::default());
+        }
+    }
+
+    /** Move the active cell to the left, unless already at the left-most cell on a line */
+    pub fn activate_prev_cell(&mut self) {
+        self.active_position.col = if self.active_position.col > 0 {
+            self.active_position.col - 1
+        } else {
+            0
+        };
+    }
+
+    /* Move the active cell to the beginning of the next line, making a new line if necessary */
+    pub fn activate_next_line(&mut self) {
+        self.active_position.row += 1;
+        self.active_position.col = 0;
+        assert!(self.active_position.row <= self.lines.len());
+        if self.active_position.row == self.lines.len() {
+            self.damage.insert(self.active_position.row);
+            self.lines.push(Line {
+                cells: vec![Cell::default()],
+            })
+        }
+    }
+
+    /* Move the active cell to the beginning of the previous line, or to the beginning of the current line if already at the first line */
+    pub fn activate_prev_line(&mut self) {
+        self.active_position.col = 0;
+        self.active_position.row = if self.active_position.row > 0 {
+            self.active_position.row - 1
+        } else {
+            0
+        };
+    }
+
+    pub fn activate_first_cell(&mut self) {
+        self.active_position.col = 0;
+    }
+
+    pub fn erase_in_line(&mut self, n: Option<&str>) {
+        match n {
+            Some("0") | None => {
+                let current_length = self.active_position.col + 1;
+                self.get_active_line_mut().cells.truncate(current_length);
+            }
+            Some("1") => {
+                for cell in self.get_active_line_mut().cells.iter_mut() {
+                    cell.grapheme = None;
+                    cell.width = CellWidth::Narrow;
+                }
+            }
+            Some("2") => {
+                self.get_active_line_mut().cells.clear();
+            }
+            _ => {
+                error!("Unexpected EL argument {n:?}")
+            }
+        }
+    }
+
+    pub fn delete_character(&mut self, n: &str) {
+        let n: Result<usize, _> = n.parse();
+        if let Ok(n) = n {
+            let i = self.get_active_position().col + 1;
+            self.get_active_line_mut().cells.splice(i..(i + n), vec![]);
+        } else {
+            error!("Unable to parse {n:?}");
+        }
+    }
+
+    pub fn insert_character(&mut self, n: &str) {
+        let n: Result<usize, _> = n.parse();
+        if let Ok(n) = n {
+            let i = self.get_active_position().col;
+            self.get_active_line_mut()
+                .cells
+                .splice(i..i, vec![Cell::default(); n]);
+        }
+    }
+
+    /** The selected text, with each row's trailing blanks trimmed if `trim` */
+    pub fn selected_text(&self, selection: &Selection, trim: bool) -> String {
+        let (start, end) = selection.ordered();
+        let end_row = end.row.min(self.lines.len().saturating_sub(1));
+        (start.row..=end_row)
+            .map(|row| {
+                let columns = selection.columns(row, self.lines[row].cells.len());
+                let text = self.text(row, columns);
+                if trim {
+                    text.trim_end().to_string()
+                } else {
+                    text
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /**
+     * First and last cell of the word at `position`: a run of letters, digits
+     * and `word_characters`. Any other cell is a word of its own.
+     */
+    pub fn word_at(&self, position: &Position, word_characters: &str) -> (Position, Position) {
+        let cells = &self.lines[position.row].cells;
+        let is_word = |col: usize| {
+            // The right half of a wide character is part of the same word
+            let col = match cells.get(col) {
+                Some(cell) if cell.width == CellWidth::Spacer && col > 0 => col - 1,
+                _ => col,
+            };
+            let grapheme = cells.get(col).and_then(|cell| cell.grapheme.as_deref());
+            grapheme
+                .and_then(|grapheme| grapheme.chars().next())
+                .is_some_and(|ch| is_word_character(ch, word_characters))
+        };
+        let (mut start, mut end) = (position.col, position.col);
+        if is_word(position.col) {
+            while start > 0 && is_word(start - 1) {
+                start -= 1;
+            }
+            while is_word(end + 1) {
+                end += 1;
+            }
+        }
+        let row = position.row;
+        (Position { row, col: start }, Position { row, col: end })
+    }
+
+    /** Forget the lines scrolled off the top of a screen of `rows` lines */
+    pub fn clear_scrollback(&mut self, rows: usize) {
+        let dropped = (self.lines.len().saturating_sub(rows)).min(self.active_position.row);
+        self.lines.drain(..dropped);
+        self.active_position.row -= dropped;
+        self.drop_prompt_marks(dropped);
+        self.damage.extend(0..self.lines.len());
+    }
+
+    /**
+     * Drop the oldest lines of history beyond the `max` kept above the
+     * `rows` of the screen, returning how many were dropped. Rows are
+     * renumbered from the first line kept.
+     */
+    pub fn limit_scrollback(&mut self, rows: usize, max: usize) -> usize {
+        let excess = self.lines.len().saturating_sub(rows + max);
+        let dropped = excess.min(self.active_position.row);
+        if dropped > 0 {
+            self.lines.drain(..dropped);
+            self.active_position.row -= dropped;
+            self.drop_prompt_marks(dropped);
+            self.damage.extend(0..self.lines.len());
+        }
+        dropped
+    }
+
+    /** The text of some of a row's cells */
+    pub fn text(&self, row: usize, columns: Range<usize>) -> String {
+        self.lines[row].cells[columns]
+            .iter()
+            .map(Cell::text)
+            .collect()
+    }
+
+    /** Every match of `pattern` within a line among `rows`, as cell ranges */
+    pub fn search(&self, pattern: &Regex, rows: Range<usize>) -> Vec<SearchMatch> {
+        let mut matches = vec![];
+        let rows = rows.start.min(self.lines.len())..rows.end.min(self.lines.len());
+        let _span = debug_span!("search", rows = rows.len()).entered();
+        for (row, line) in self.lines[rows.clone()].iter().enumerate() {
+            let row = rows.start + row;
+            // Remember which cell each byte of the line's text came from
+            let mut text = String::new();
+            let mut cell_at_byte = vec![];
+            for (col, cell) in line.cells.iter().enumerate() {
+                let grapheme = cell.text();
+                text += grapheme;
+                cell_at_byte.extend(std::iter::repeat_n(col, grapheme.len()));
+            }
+            cell_at_byte.push(line.cells.len());
+            matches.extend(
+                pattern
+                    .find_iter(&text)
+                    .filter(|found| !found.is_empty())
+                    .map(|found| SearchMatch {
+                        row,
+                        columns: cell_at_byte[found.start()]..cell_at_byte[found.end()],
+                    }),
+            );
+        }
+        matches
+    }
+
+    // XXX replace with real formatting
+    pub fn render(&self, max_lines: usize) -> String {
+        let mut result = String::new();
+        result.clear();
+        for line in self
+            .lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(max_lines))
+        {
+            for cell in line.cells.iter() {
+                result += cell.text();
+            }
+            result = result.trim_end().to_string() + "\n";
+        }
+        result.pop();
+        result
+    }
+
+    /** Like `render`, but with SGR sequences for the cells' colors and styles */
+    pub fn render_ansi(&self, max_lines: usize) -> String {
+        let mut result = String::new();
+        for line in self
+            .lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(max_lines))
+        {
+            // Blank cells at the end of the line would only be trimmed away by `render`
+            let end = line
+                .cells
+                .iter()
+                .rposition(|cell| {
+                    cell.grapheme.as_ref().is_some_and(|g| !g.trim().is_empty())
+                        || cell.attributes != Attributes::default()
+                })
+                .map_or(0, |last| last + 1);
+            let mut attributes = Attributes::default();
+            for cell in &line.cells[..end] {
+                if cell.attributes != attributes {
+                    attributes = cell.attributes;
+                    result += &attributes.sgr();
+                }
+                result += cell.text();
+            }
+            if attributes != Attributes::default() {
+                result += "\x1b[0m";
+            }
+            result += "\n";
+        }
+        result.pop();
+        result
+    }
+
+    fn write_text(&mut self, text: &str) {
+        // After a wide character the active cell is its spacer, but the character may go on
+        if self.get_active_cell().width == CellWidth::Spacer && self.active_position.col > 0 {
+            self.active_position.col -= 1;
+        }
+        let combined_text = self
+            .get_active_cell()
+            .grapheme
+            .to_owned()
+            .unwrap_or_default()
+            + text;
+        let mut graphemes = width::clusters(&combined_text, self.width_policy).into_iter();
+
+        let attributes = self.attributes;
+        if let Some(grapheme) = graphemes.next() {
+            // A cell that already holds a grapheme is only being extended (e.g. by a combining mark)
+            let attributes = match self.get_active_cell().grapheme {
+                Some(_) => self.get_active_cell().attributes,
+                None => attributes,
+            };
+            self.put_grapheme(grapheme, attributes);
+        }
+        for grapheme in graphemes {
+            self.activate_next_cell();
+            self.put_grapheme(grapheme, attributes);
+        }
+    }
+
+    /**
+     * Write a grapheme into the active cell, and if it is wide, its spacer
+     * into the next, which then becomes the active cell. A wide character
+     * only partly overwritten is blanked.
+     */
+    fn put_grapheme(&mut self, grapheme: &str, attributes: Attributes) {
+        let width = width::grapheme_width(grapheme, self.width_policy);
+        let Position { row, col } = self.active_position;
+        let cells = &mut self.lines[row].cells;
+        if cells[col].width == CellWidth::Spacer && col > 0 {
+            cells[col - 1] = Cell {
+                attributes: cells[col - 1].attributes,
+                ..Cell::default()
+            };
+        }
+        if cells[col].width == CellWidth::Wide && width == 1 {
+            if let Some(spacer) = cells.get_mut(col + 1) {
+                *spacer = Cell {
+                    attributes: spacer.attributes,
+                    ..Cell::default()
+                };
+            }
+        }
+        *self.get_active_cell_mut() = Cell {
+            grapheme: Some(grapheme.to_string()),
+            attributes,
+            width: if width == 2 {
+                CellWidth::Wide
+            } else {
+                CellWidth::Narrow
+            },
+        };
+        if width == 2 {
+            self.activate_next_cell();
+            // Whatever was under the spacer is overwritten, the left half of a wide character too
+            if self.get_active_cell().width == CellWidth::Wide {
+                let next = self.active_position.col + 1;
+                if let Some(spacer) = self.get_active_line_mut().cells.get_mut(next) {
+                    spacer.width = CellWidth::Narrow;
+                }
+            }
+            *self.get_active_cell_mut() = Cell {
+                grapheme: None,
+                attributes,
+                width: CellWidth::Spacer,
+            };
+        }
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, escaped) {
+            (b'%', Some(escaped)) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl TerminalHandler for DataComponent {
+    fn print(&mut self, text: &str) {
+        self.write_text(text);
+    }
+
+    fn execute(&mut self, control: char) {
+        match control {
+            '\x07' => self.bell = true,
+            '\x08' => self.activate_prev_cell(),
+            '\x0A' => self.activate_next_line(),
+            '\x0D' => self.activate_first_cell(),
+            control => self.ignore(format!("C0 {:#04x}", control as u32)),
+        }
+    }
+
+    fn esc_dispatch(&mut self, byte: char) {
+        match byte {
+            '\x45' => self.activate_first_cell(),
+            '\x4D' => self.activate_prev_line(),
+            '=' => self.modes.application_keypad = true,
+            '>' => self.modes.application_keypad = false,
+            byte => self.ignore(format!("ESC {byte}")),
+        }
+    }
+
+    fn csi_dispatch(
+        &mut self,
+        parameters: Option<&str>,
+        intermediates: Option<&str>,
+        final_byte: char,
+    ) {
+        match (parameters, intermediates, final_byte) {
+            (Some(n), None, '@') => self.insert_character(n),
+            (None, None, 'C') => self.activate_next_cell(),
+            (n, _, 'K') => self.erase_in_line(n),
+            (Some(n), None, 'P') => self.delete_character(n),
+            (n, None, 'c') => self.device_attributes(n),
+            (Some(n), None, 'n') => self.device_status_report(n),
+            (Some(n), Some("$"), 'p') => self.request_mode(n),
+            (Some(n), None, 'h') => self.set_modes(n, true),
+            (Some(n), None, 'l') => self.set_modes(n, false),
+            _ => {
+                // Mode numbers are what matter for SM/RM; elsewhere only the private marker is kept
+                let parameters = match (final_byte, parameters) {
+                    ('h' | 'l', Some(parameters)) => parameters,
+                    (_, Some(parameters)) => parameters
+                        .trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == ';' || ch == ':'),
+                    (_, None) => "",
+                };
+                let intermediates = intermediates.unwrap_or_default();
+                self.ignore(format!("CSI {parameters}{intermediates}{final_byte}"))
+            }
+        }
+    }
+
+    fn set_attributes(&mut self, attributes: Attributes) {
+        self.attributes = attributes;
+    }
+
+    fn osc_dispatch(&mut self, data: &str) {
+        match data.split_once(';') {
+            Some(("0" | "2", title)) => self.title = Some(title.to_owned()),
+            Some(("7", uri)) => self.set_working_directory(uri),
+            Some(("133", arguments)) => self.mark_prompt(arguments),
+            Some((command @ ("10" | "11"), value)) => self.report_default_color(command, value),
+            _ => {
+                let command = data.split(';').next().unwrap_or_default();
+                self.ignore(format!("OSC {command}"));
+            }
+        }
+    }
+
+    fn string_dispatch(&mut self, opening: char, data: &str) {
+        debug!("Control string {opening:?} {data:?}");
+        let name = match opening {
+            '\x5F' => "APC",
+            '\x50' => "DCS",
+            '\x5E' => "PM",
+            _ => "SOS",
+        };
+        self.ignore(name.to_string());
+    }
+
+    fn unknown(&mut self, ch: char) {
+        self.ignore(format!("unknown U+{:04X}", ch as u32));
+    }
+
+    fn ignored_count(&self) -> usize {
+        self.stats.total()
+    }
+}
@@ -0,0 +1,58 @@
+use crate::pen::Attributes;
+
+/**
+ * Receives the actions decoded from the pty stream by the `Translator`.
+ *
+ * This is modelled on vte's `Perform`: the translator is responsible for
+ * decoding and splitting the stream into control functions, and each
+ * consumer only decides what those functions mean to it. Every method other
+ * than `print` has an empty default, so consumers only implement what they
+ * care about.
+ */
+pub trait TerminalHandler {
+    /** Write graphic characters at the active position */
+    fn print(&mut self, text: &str);
+
+    /** Execute a C0 control function, e.g. LF or BS */
+    fn execute(&mut self, _control: char) {}
+
+    /**
+     * Dispatch an escape sequence consisting of ESC followed by a single
+     * byte, i.e. a 7-bit C1 control function or an independent control
+     * function
+     */
+    fn esc_dispatch(&mut self, _byte: char) {}
+
+    /** Dispatch a control sequence introduced by CSI */
+    fn csi_dispatch(
+        &mut self,
+        _parameters: Option<&str>,
+        _intermediates: Option<&str>,
+        _final_byte: char,
+    ) {
+    }
+
+    /**
+     * The graphic rendition to use for subsequently printed text changed. SGR
+     * sequences are interpreted by the translator rather than being passed to
+     * `csi_dispatch`.
+     */
+    fn set_attributes(&mut self, _attributes: Attributes) {}
+
+    /** Dispatch an operating system command, without its delimiters */
+    fn osc_dispatch(&mut self, _data: &str) {}
+
+    /** Dispatch a control string other than OSC (APC, DCS, PM or SOS) */
+    fn string_dispatch(&mut self, _opening: char, _data: &str) {}
+
+    /** Called for any character the translator could not make sense of */
+    fn unknown(&mut self, _ch: char) {}
+
+    /**
+     * How many control functions this handler has ignored so far, which
+     * tells the translator's event log what became of each one
+     */
+    fn ignored_count(&self) -> usize {
+        0
+    }
+}
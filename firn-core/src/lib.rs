@@ -0,0 +1,79 @@
+#![feature(assert_matches)]
+#![feature(try_trait_v2)]
+
+/*!
+ * firn's terminal emulation, with no GUI attached: bytes written by a
+ * program go in, and a grid of cells comes out.
+ *
+ * ```
+ * let mut terminal = firn_core::Terminal::new().unwrap();
+ * terminal.advance(b"hello\r\n\x1b[1mworld");
+ * let mut screen = vec![];
+ * terminal.render_to(&mut screen, usize::MAX).unwrap();
+ * assert_eq!(screen, b"hello\nworld");
+ * ```
+ */
+
+pub mod bidi;
+pub mod data;
+pub mod handler;
+pub mod parser;
+pub mod pen;
+pub mod recording;
+pub mod rgb;
+pub mod search;
+pub mod selection;
+pub mod stats;
+pub mod translator;
+pub mod width;
+
+use anyhow::Result;
+use data::DataComponent;
+use std::io::Write;
+use translator::Translator;
+
+/** The grid and scrollback of a terminal, along with the parser state of its input */
+pub struct Terminal {
+    data: DataComponent,
+    translator: Translator,
+}
+
+impl Terminal {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            data: DataComponent::new(),
+            translator: Translator::new()?,
+        })
+    }
+
+    /**
+     * Interpret bytes written by the program in the terminal. A sequence cut
+     * off at the end is kept until the rest of it arrives.
+     */
+    pub fn advance(&mut self, bytes: &[u8]) {
+        self.translator.write(bytes, &mut self.data);
+    }
+
+    /** Write the text of the last `max_lines` lines, trailing whitespace trimmed, without attributes */
+    pub fn render_to(&self, writer: &mut impl Write, max_lines: usize) -> std::io::Result<()> {
+        writer.write_all(self.data.render(max_lines).as_bytes())
+    }
+
+    /** Like `render_to`, with SGR sequences for colors and styles */
+    pub fn render_ansi_to(&self, writer: &mut impl Write, max_lines: usize) -> std::io::Result<()> {
+        writer.write_all(self.data.render_ansi(max_lines).as_bytes())
+    }
+
+    /** Replies owed to the program, e.g. to a cursor position report, to be written back to it */
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        self.data.take_responses()
+    }
+
+    pub fn data(&self) -> &DataComponent {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut DataComponent {
+        &mut self.data
+    }
+}
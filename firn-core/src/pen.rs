@@ -0,0 +1,243 @@
+use tracing::error;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Color {
+    #[default]
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/** The graphic rendition of a single cell */
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Attributes {
+    pub foreground: Color,
+    pub background: Color,
+    pub bold: bool,
+    pub faint: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+impl Attributes {
+    /**
+     * The foreground color, with the 8 standard colors swapped for their
+     * bright variants if the text is bold, as xterm's `boldColors` does
+     */
+    pub fn bright_foreground(&self) -> Color {
+        match self.foreground {
+            Color::Indexed(n @ 0..=7) if self.bold => Color::Indexed(n + 8),
+            color => color,
+        }
+    }
+}
+
+impl Attributes {
+    /** The SGR sequence that switches to exactly these attributes, whatever came before */
+    pub fn sgr(&self) -> String {
+        let mut parameters = vec!["0".to_string()];
+        let flags = [
+            (self.bold, 1),
+            (self.faint, 2),
+            (self.italic, 3),
+            (self.underline, 4),
+            (self.blink, 5),
+            (self.inverse, 7),
+            (self.hidden, 8),
+            (self.strikethrough, 9),
+        ];
+        for (_, n) in flags.into_iter().filter(|(set, _)| *set) {
+            parameters.push(n.to_string());
+        }
+        for (color, base) in [(self.foreground, 38), (self.background, 48)] {
+            match color {
+                Color::Default => {}
+                Color::Indexed(n) => parameters.push(format!("{base};5;{n}")),
+                Color::Rgb(r, g, b) => parameters.push(format!("{base};2;{r};{g};{b}")),
+            }
+        }
+        format!("\x1b[{}m", parameters.join(";"))
+    }
+}
+
+/**
+ * Accumulates SGR (select graphic rendition) changes, so that the attributes
+ * to stamp onto newly written cells are always available in one place
+ */
+#[derive(Default)]
+pub struct Pen {
+    attributes: Attributes,
+}
+
+impl Pen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attributes(&self) -> Attributes {
+        self.attributes
+    }
+
+    /** Apply the parameters of an SGR control sequence (CSI ... m) */
+    pub fn apply_sgr(&mut self, parameters: Option<&str>) {
+        let parameters = parameters.unwrap_or_default();
+        let mut parameters = parameters.split(';');
+        while let Some(parameter) = parameters.next() {
+            // Colon-separated sub-parameters (e.g. 38:2::r:g:b) travel in one parameter
+            let mut sub_parameters = parameter.split(':');
+            let code = sub_parameters.next().unwrap_or_default();
+            let a = &mut self.attributes;
+            match code {
+                "" | "0" => *a = Attributes::default(),
+                "1" => a.bold = true,
+                "2" => a.faint = true,
+                "3" => a.italic = true,
+                "4" => a.underline = !matches!(sub_parameters.next(), Some("0")),
+                "5" | "6" => a.blink = true,
+                "7" => a.inverse = true,
+                "8" => a.hidden = true,
+                "9" => a.strikethrough = true,
+                "21" => a.underline = true,
+                "22" => {
+                    a.bold = false;
+                    a.faint = false;
+                }
+                "23" => a.italic = false,
+                "24" => a.underline = false,
+                "25" => a.blink = false,
+                "27" => a.inverse = false,
+                "28" => a.hidden = false,
+                "29" => a.strikethrough = false,
+                "39" => a.foreground = Color::Default,
+                "49" => a.background = Color::Default,
+                "38" | "48" => {
+                    let color = if parameter.contains(':') {
+                        Self::parse_extended_color(sub_parameters, true)
+                    } else {
+                        Self::parse_extended_color(&mut parameters, false)
+                    };
+                    match (code, color) {
+                        ("38", Some(color)) => a.foreground = color,
+                        (_, Some(color)) => a.background = color,
+                        (_, None) => error!("Unable to parse extended color {parameter:?}"),
+                    }
+                }
+                code => match code.parse::<u8>() {
+                    Ok(n @ 30..=37) => a.foreground = Color::Indexed(n - 30),
+                    Ok(n @ 40..=47) => a.background = Color::Indexed(n - 40),
+                    Ok(n @ 90..=97) => a.foreground = Color::Indexed(n - 90 + 8),
+                    Ok(n @ 100..=107) => a.background = Color::Indexed(n - 100 + 8),
+                    _ => error!("Unexpected SGR argument {code:?}"),
+                },
+            }
+        }
+    }
+
+    /**
+     * Parse the remainder of an extended color (38 or 48): either `5;n` or
+     * `2;r;g;b`. The colon form may carry an extra colour space identifier
+     * before the components, i.e. `2::r:g:b`.
+     */
+    fn parse_extended_color<'a>(
+        mut parameters: impl Iterator<Item = &'a str>,
+        has_color_space: bool,
+    ) -> Option<Color> {
+        match parameters.next()? {
+            "5" => Some(Color::Indexed(parameters.next()?.parse().ok()?)),
+            "2" => {
+                let mut components: Vec<&str> = parameters.by_ref().take(3).collect();
+                if has_color_space && components.len() == 3 {
+                    if let Some(component) = parameters.next() {
+                        components.remove(0);
+                        components.push(component);
+                    }
+                }
+                match components[..] {
+                    [r, g, b] => Some(Color::Rgb(
+                        r.parse().ok()?,
+                        g.parse().ok()?,
+                        b.parse().ok()?,
+                    )),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sgr() {
+        assert_eq!(Attributes::default().sgr(), "\x1b[0m");
+        let mut pen = Pen::new();
+        pen.apply_sgr(Some("1;4;38;5;196;48;2;1;2;3"));
+        assert_eq!(pen.attributes().sgr(), "\x1b[0;1;4;38;5;196;48;2;1;2;3m");
+    }
+
+    #[test]
+    fn test_sgr_reset() {
+        let mut pen = Pen::new();
+        pen.apply_sgr(Some("1;3;31"));
+        assert!(pen.attributes().bold);
+        pen.apply_sgr(None);
+        assert_eq!(pen.attributes(), Attributes::default());
+    }
+
+    #[test]
+    fn test_sgr_22_clears_bold_and_faint() {
+        let mut pen = Pen::new();
+        pen.apply_sgr(Some("1;2;4"));
+        pen.apply_sgr(Some("22"));
+        assert!(!pen.attributes().bold);
+        assert!(!pen.attributes().faint);
+        assert!(pen.attributes().underline);
+    }
+
+    #[test]
+    fn test_sgr_colors() {
+        let mut pen = Pen::new();
+        pen.apply_sgr(Some("31;102"));
+        assert_eq!(pen.attributes().foreground, Color::Indexed(1));
+        assert_eq!(pen.attributes().background, Color::Indexed(10));
+        pen.apply_sgr(Some("39;49"));
+        assert_eq!(pen.attributes().foreground, Color::Default);
+        assert_eq!(pen.attributes().background, Color::Default);
+    }
+
+    #[test]
+    fn test_sgr_extended_colors() {
+        let mut pen = Pen::new();
+        pen.apply_sgr(Some("38;5;208;48;2;1;2;3;1"));
+        assert_eq!(pen.attributes().foreground, Color::Indexed(208));
+        assert_eq!(pen.attributes().background, Color::Rgb(1, 2, 3));
+        assert!(pen.attributes().bold);
+
+        pen.apply_sgr(Some("38:2::10:20:30"));
+        assert_eq!(pen.attributes().foreground, Color::Rgb(10, 20, 30));
+        pen.apply_sgr(Some("48:2:40:50:60"));
+        assert_eq!(pen.attributes().background, Color::Rgb(40, 50, 60));
+    }
+
+    #[test]
+    fn test_bright_foreground() {
+        let mut pen = Pen::new();
+        pen.apply_sgr(Some("1;31"));
+        assert_eq!(pen.attributes().bright_foreground(), Color::Indexed(9));
+        pen.apply_sgr(Some("91"));
+        assert_eq!(pen.attributes().bright_foreground(), Color::Indexed(9));
+        pen.apply_sgr(Some("22;31"));
+        assert_eq!(pen.attributes().bright_foreground(), Color::Indexed(1));
+        pen.apply_sgr(Some("1;38;5;3"));
+        assert_eq!(pen.attributes().bright_foreground(), Color::Indexed(11));
+        pen.apply_sgr(Some("39"));
+        assert_eq!(pen.attributes().bright_foreground(), Color::Default);
+    }
+}
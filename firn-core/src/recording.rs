@@ -0,0 +1,99 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io::BufRead;
+
+/** One read from the pty, stored as a line of JSON by `firn --record` */
+#[derive(Serialize, Deserialize)]
+pub struct Chunk {
+    /** Seconds since the recording started */
+    pub time: f64,
+    /**
+     * What was read, as text like an asciicast's, except that a byte which is
+     * not part of valid utf-8 is written `\xNN` and a backslash `\\`, so that
+     * nothing is lost. Older recordings hold an array of the bytes instead.
+     */
+    #[serde(
+        serialize_with = "serialize_data",
+        deserialize_with = "deserialize_data"
+    )]
+    pub data: Vec<u8>,
+}
+
+fn serialize_data<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&escape(data))
+}
+
+fn deserialize_data<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Data {
+        Escaped(String),
+        Bytes(Vec<u8>),
+    }
+
+    match Data::deserialize(deserializer)? {
+        Data::Escaped(text) => unescape(&text).map_err(serde::de::Error::custom),
+        Data::Bytes(bytes) => Ok(bytes),
+    }
+}
+
+fn escape(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.utf8_chunks() {
+        text += &chunk.valid().replace('\\', "\\\\");
+        for byte in chunk.invalid() {
+            text += &format!("\\x{byte:02x}");
+        }
+    }
+    text
+}
+
+fn unescape(text: &str) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut utf8 = [0; 4];
+            data.extend_from_slice(ch.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => data.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if hex.len() == 2 => data.push(byte),
+                    _ => bail!("Expected two hex digits after \\x, got {hex:?}"),
+                }
+            }
+            other => bail!("Unknown escape {other:?} in recorded data"),
+        }
+    }
+    Ok(data)
+}
+
+/** The chunks of a recording, in order */
+pub fn chunks(reader: impl BufRead) -> impl Iterator<Item = Result<Chunk>> {
+    reader.lines().map(|line| Ok(serde_json::from_str(&line?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_data() {
+        let chunk = Chunk {
+            time: 0.5,
+            data: b"\x1b[1mcaf\xc3\xa9\\\xd0".to_vec(),
+        };
+        let line = serde_json::to_string(&chunk).unwrap();
+        assert_eq!(line, r#"{"time":0.5,"data":"\u001b[1mcafé\\\\\\xd0"}"#);
+        let read: Chunk = serde_json::from_str(&line).unwrap();
+        assert_eq!(read.data, chunk.data);
+
+        let legacy: Chunk = serde_json::from_str(r#"{"time":0.0,"data":[208,163]}"#).unwrap();
+        assert_eq!(legacy.data, "У".as_bytes());
+        assert!(serde_json::from_str::<Chunk>(r#"{"time":0.0,"data":"\\q"}"#).is_err());
+    }
+}
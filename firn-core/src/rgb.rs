@@ -0,0 +1,53 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/** A color written as `#rrggbb` */
+#[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for Rgb {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        let Some(hex) = text.strip_prefix('#').filter(|hex| hex.len() == 6) else {
+            bail!("Expected a color like #rrggbb, got {text:?}");
+        };
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+        Ok(Self(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(Rgb(r, g, b): Rgb) -> Self {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+impl Rgb {
+    /** Whether this would count as a dark background, going by its perceived lightness */
+    pub fn is_dark(self) -> bool {
+        let Rgb(r, g, b) = self;
+        let lightness = 0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+        lightness < 128.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+
+    #[test]
+    fn test_rgb() {
+        assert_eq!(
+            Rgb::try_from("#FF8000".to_string()).unwrap(),
+            Rgb(255, 128, 0)
+        );
+        assert_matches!(Rgb::try_from("FF8000".to_string()), Err(_));
+        assert_matches!(Rgb::try_from("#FF80".to_string()), Err(_));
+        assert_matches!(Rgb::try_from("#GG8000".to_string()), Err(_));
+        assert!(Rgb(0x20, 0x22, 0x25).is_dark());
+        assert!(!Rgb(0xFF, 0xFF, 0xFE).is_dark());
+    }
+}
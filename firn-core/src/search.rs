@@ -0,0 +1,8 @@
+use std::ops::Range;
+
+/** A run of cells that matched a search */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchMatch {
+    pub row: usize,
+    pub columns: Range<usize>,
+}
@@ -0,0 +1,156 @@
+use crate::data::Position;
+
+/** What a selection grows by while dragging */
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub enum SelectionUnit {
+    /** Started by a single click */
+    #[default]
+    Cell,
+    /** Started by a double click */
+    Word,
+    /** Started by a triple click */
+    Line,
+}
+
+/**
+ * A run of cells in reading order between where a drag started and where it
+ * currently is, both inclusive
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selection {
+    pub anchor: Position,
+    pub head: Position,
+    pub unit: SelectionUnit,
+    /** First and last cell of the unit first clicked, which stays selected whichever way the drag goes */
+    origin: (Position, Position),
+}
+
+impl Selection {
+    pub fn new(position: Position) -> Self {
+        Self::spanning(position.clone(), position, SelectionUnit::Cell)
+    }
+
+    /** Select a whole unit, from its first to its last cell */
+    pub fn spanning(start: Position, end: Position, unit: SelectionUnit) -> Self {
+        Self {
+            anchor: start.clone(),
+            head: end.clone(),
+            unit,
+            origin: (start, end),
+        }
+    }
+
+    /** Extend the selection to the unit from `start` to `end`, e.g. the word under the pointer */
+    pub fn extend(&mut self, start: Position, end: Position) {
+        let (first, last) = &self.origin;
+        if (start.row, start.col) < (first.row, first.col) {
+            self.anchor = last.clone();
+            self.head = start;
+        } else {
+            self.anchor = first.clone();
+            self.head = end;
+        }
+    }
+
+    /** The first and last selected cells */
+    pub fn ordered(&self) -> (&Position, &Position) {
+        let key = |position: &Position| (position.row, position.col);
+        if key(&self.anchor) <= key(&self.head) {
+            (&self.anchor, &self.head)
+        } else {
+            (&self.head, &self.anchor)
+        }
+    }
+
+    /**
+     * Renumber the rows after `rows` lines above were dropped, or `None` if
+     * the selection started among them
+     */
+    pub fn shifted_up(mut self, rows: usize) -> Option<Self> {
+        for position in [
+            &mut self.anchor,
+            &mut self.head,
+            &mut self.origin.0,
+            &mut self.origin.1,
+        ] {
+            position.row = position.row.checked_sub(rows)?;
+        }
+        Some(self)
+    }
+
+    /** The selected columns of `row`, as a half-open range clamped to `len` */
+    pub fn columns(&self, row: usize, len: usize) -> std::ops::Range<usize> {
+        let (start, end) = self.ordered();
+        if row < start.row || row > end.row {
+            return 0..0;
+        }
+        let first = if row == start.row { start.col } else { 0 };
+        let last = if row == end.row { end.col + 1 } else { len };
+        first.min(len)..last.min(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataComponent;
+    use crate::translator::Translator;
+
+    fn position(row: usize, col: usize) -> Position {
+        Position { row, col }
+    }
+
+    #[test]
+    fn test_backwards_selection() {
+        let mut selection = Selection::new(position(2, 3));
+        selection.head = position(1, 5);
+        assert_eq!(selection.ordered(), (&position(1, 5), &position(2, 3)));
+        assert_eq!(selection.columns(0, 10), 0..0);
+        assert_eq!(selection.columns(1, 10), 5..10);
+        assert_eq!(selection.columns(2, 10), 0..4);
+        assert_eq!(selection.columns(2, 2), 0..2);
+    }
+
+    #[test]
+    fn test_shifted_up() {
+        let selection = Selection::spanning(position(3, 1), position(5, 2), SelectionUnit::Line);
+        let shifted = selection.clone().shifted_up(2).unwrap();
+        assert_eq!(shifted.ordered(), (&position(1, 1), &position(3, 2)));
+        assert_eq!(selection.shifted_up(4), None);
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut selection =
+            Selection::spanning(position(1, 4), position(1, 7), SelectionUnit::Word);
+        selection.extend(position(2, 0), position(2, 3));
+        assert_eq!(selection.ordered(), (&position(1, 4), &position(2, 3)));
+        selection.extend(position(0, 2), position(0, 5));
+        assert_eq!(selection.ordered(), (&position(0, 2), &position(1, 7)));
+        selection.extend(position(1, 4), position(1, 7));
+        assert_eq!(selection.ordered(), (&position(1, 4), &position(1, 7)));
+    }
+
+    #[test]
+    fn test_word_at() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write("cd ~/src/my-repo; ls".as_bytes(), &mut data);
+        assert_eq!(
+            data.word_at(&position(0, 6), "-_./~"),
+            (position(0, 3), position(0, 15))
+        );
+        assert_eq!(
+            data.word_at(&position(0, 6), ""),
+            (position(0, 5), position(0, 7))
+        );
+        assert_eq!(
+            data.word_at(&position(0, 16), "-_./~"),
+            (position(0, 16), position(0, 16))
+        );
+        assert_eq!(
+            data.word_at(&position(0, 40), ""),
+            (position(0, 40), position(0, 40))
+        );
+    }
+}
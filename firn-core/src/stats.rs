@@ -0,0 +1,85 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+/**
+ * Counts of the control functions that were received but not acted on, so
+ * that users can report exactly which sequences their applications need
+ */
+#[derive(Default)]
+pub struct SequenceStats {
+    ignored: HashMap<String, usize>,
+    /** The last `RECENT` sequences ignored, oldest first */
+    recent: VecDeque<String>,
+    total: usize,
+}
+
+/** How many of the most recently ignored sequences are remembered in order */
+const RECENT: usize = 8;
+
+impl SequenceStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_ignored(&mut self, sequence: String) {
+        if self.recent.len() == RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(sequence.clone());
+        *self.ignored.entry(sequence).or_default() += 1;
+        self.total += 1;
+    }
+
+    /** How many sequences have been ignored, counting repeats */
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /** The most recently ignored sequences, newest first */
+    pub fn recent(&self) -> impl Iterator<Item = &str> {
+        self.recent.iter().rev().map(String::as_str)
+    }
+
+    /** Ignored sequences, most frequent first */
+    pub fn ignored(&self) -> Vec<(&str, usize)> {
+        let mut ignored: Vec<_> = self
+            .ignored
+            .iter()
+            .map(|(sequence, count)| (sequence.as_str(), *count))
+            .collect();
+        ignored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        ignored
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ignored.is_empty()
+    }
+}
+
+impl Display for SequenceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (sequence, count) in self.ignored() {
+            writeln!(f, "{count:>8} {sequence}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignored_sorted_by_count() {
+        let mut stats = SequenceStats::new();
+        stats.record_ignored("CSI ?1049h".into());
+        stats.record_ignored("OSC 7".into());
+        stats.record_ignored("OSC 7".into());
+        assert_eq!(stats.ignored(), [("OSC 7", 2), ("CSI ?1049h", 1)]);
+        assert_eq!(stats.to_string(), "       2 OSC 7\n       1 CSI ?1049h\n");
+        assert_eq!(
+            stats.recent().collect::<Vec<_>>(),
+            ["OSC 7", "OSC 7", "CSI ?1049h"]
+        );
+    }
+}
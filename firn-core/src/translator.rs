@@ -0,0 +1,567 @@
+use crate::{
+    handler::TerminalHandler,
+    parser::{Node, NodeParseResult},
+    pen::Pen,
+};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, trace, trace_span, Level};
+use utf8::{DecodeError, Incomplete};
+
+/**
+ * How long a lone ESC may sit in the buffer, with nothing after it, before it
+ * is given up on and emitted on its own
+ */
+pub const ESC_TIMEOUT: Duration = Duration::from_millis(50);
+
+/** The target every parsed node is logged under at trace level, with what came of it */
+pub const EVENTS: &str = "firn_core::events";
+
+pub struct Translator {
+    text_buffer: String,
+    incomplete: Incomplete,
+    pending_since: Option<Instant>,
+    pen: Pen,
+}
+
+impl Translator {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            text_buffer: String::new(),
+            incomplete: Incomplete::empty(),
+            pending_since: None,
+            pen: Pen::new(),
+        })
+    }
+
+    /** Whether there is buffered text or a partial character that could not yet be parsed */
+    pub fn is_pending(&self) -> bool {
+        !self.text_buffer.is_empty() || !self.incomplete.is_empty()
+    }
+
+    /** Whether all that is buffered is an ESC, which `flush_stale` may emit on its own */
+    pub fn is_lone_esc(&self) -> bool {
+        self.text_buffer == "\x1B" && self.incomplete.is_empty()
+    }
+
+    /** How many bytes of text are buffered, waiting for the rest of a sequence */
+    pub fn pending_len(&self) -> usize {
+        self.text_buffer.len()
+    }
+
+    pub fn write(&mut self, input: &[u8], handler: &mut impl TerminalHandler) {
+        let _span = trace_span!("write", bytes = input.len()).entered();
+        self.read_bytes_to_buffer(input);
+        self.write_buffer_to_handler(handler);
+    }
+
+    pub fn read_bytes_to_buffer(&mut self, mut input: &[u8]) {
+        if !self.incomplete.is_empty() {
+            match self.incomplete.try_complete(input) {
+                Some((Ok(text), remaining_input)) => {
+                    self.text_buffer += text;
+                    input = remaining_input;
+                }
+                Some((Err(invalid_sequence), remaining_input)) => {
+                    error!("Could not decode to valid utf-8 {invalid_sequence:?}");
+                    self.text_buffer += &char::REPLACEMENT_CHARACTER.to_string();
+                    input = remaining_input;
+                }
+                None => return,
+            }
+        }
+
+        loop {
+            match utf8::decode(input) {
+                Ok(text) => {
+                    self.text_buffer += text;
+                    return;
+                }
+                Err(DecodeError::Incomplete {
+                    valid_prefix,
+                    incomplete_suffix,
+                }) => {
+                    self.text_buffer += valid_prefix;
+                    self.incomplete = incomplete_suffix;
+                    return;
+                }
+                Err(DecodeError::Invalid {
+                    valid_prefix,
+                    invalid_sequence,
+                    remaining_input,
+                }) => {
+                    self.text_buffer += valid_prefix;
+                    error!("Could not decode to valid utf-8 {invalid_sequence:?}");
+                    self.text_buffer += &char::REPLACEMENT_CHARACTER.to_string();
+                    input = remaining_input;
+                }
+            }
+        }
+    }
+
+    pub fn write_buffer_to_handler(&mut self, handler: &mut impl TerminalHandler) {
+        let text_buffer = std::mem::take(&mut self.text_buffer);
+        let mut chars = text_buffer.chars();
+        while let NodeParseResult::Match(remaining_chars, node) = Node::parse(chars.clone()) {
+            chars = remaining_chars;
+            self.dispatch(&node, handler);
+        }
+        self.text_buffer = chars.collect();
+        // The deadline starts over whenever more of a sequence arrives
+        self.pending_since = self.is_pending().then(Instant::now);
+    }
+
+    /**
+     * If a lone ESC has been waiting for a continuation for longer than
+     * `ESC_TIMEOUT`, emit it as a plain control character rather than holding
+     * the stream forever. Longer sequences, like an OSC 52 payload or a CSI
+     * split across packets, are left to complete however long that takes.
+     */
+    pub fn flush_stale(&mut self, handler: &mut impl TerminalHandler) {
+        self.flush_stale_at(Instant::now(), handler);
+    }
+
+    fn flush_stale_at(&mut self, now: Instant, handler: &mut impl TerminalHandler) {
+        let Some(pending_since) = self.pending_since else {
+            return;
+        };
+        if now.duration_since(pending_since) < ESC_TIMEOUT || !self.is_lone_esc() {
+            return;
+        }
+        debug!("Timed out waiting for an escape sequence");
+        handler.execute('\x1B');
+        self.text_buffer.clear();
+        self.pending_since = None;
+    }
+
+    fn dispatch(&mut self, node: &Node, handler: &mut impl TerminalHandler) {
+        let ignored =
+            tracing::enabled!(target: EVENTS, Level::TRACE).then(|| handler.ignored_count());
+        self.dispatch_to(node, handler);
+        if let Some(ignored) = ignored {
+            let outcome = if handler.ignored_count() > ignored {
+                "ignored"
+            } else {
+                "handled"
+            };
+            trace!(target: EVENTS, ?node, outcome);
+        }
+    }
+
+    fn dispatch_to(&mut self, node: &Node, handler: &mut impl TerminalHandler) {
+        const OSC: char = '\x5D';
+        match node {
+            Node::Text(text) => handler.print(text),
+            Node::C0Control(control) => handler.execute(*control),
+            Node::C1Control(byte) | Node::IndependentControlFunction(byte) => {
+                handler.esc_dispatch(*byte)
+            }
+            Node::ControlSequence {
+                parameter_bytes,
+                intermediate_bytes: None,
+                final_byte: 'm',
+            } if !parameter_bytes
+                .as_deref()
+                .is_some_and(|bytes| bytes.starts_with(['<', '=', '>', '?'])) =>
+            {
+                self.pen.apply_sgr(parameter_bytes.as_deref());
+                handler.set_attributes(self.pen.attributes());
+            }
+            Node::ControlSequence {
+                parameter_bytes,
+                intermediate_bytes,
+                final_byte,
+            } => handler.csi_dispatch(
+                parameter_bytes.as_deref(),
+                intermediate_bytes.as_deref(),
+                *final_byte,
+            ),
+            Node::ControlString {
+                opening: OSC,
+                character_string,
+            } => handler.osc_dispatch(character_string),
+            Node::ControlString {
+                opening,
+                character_string,
+            } => handler.string_dispatch(*opening, character_string),
+            Node::Unknown(ch) => handler.unknown(*ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches::assert_matches;
+
+    use crate::data::{CellWidth, DataComponent, Position, PromptMark};
+    use crate::pen::{Attributes, Color};
+    use crate::rgb::Rgb;
+    use crate::selection::Selection;
+
+    use super::*;
+
+    #[test]
+    fn test_write_text() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"hello world", &mut data);
+        assert_eq!(data.render(10), "hello world");
+        assert_eq!(data.get_active_position(), Position { row: 0, col: 10 });
+    }
+
+    #[test]
+    fn test_write_wide_text() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write("a中b".as_bytes(), &mut data);
+        assert_eq!(data.render(10), "a中b");
+        assert_eq!(data.get_active_position(), Position { row: 0, col: 3 });
+        let widths: Vec<_> = data.get_line_cells(0).map(|cell| cell.width).collect();
+        assert_eq!(
+            widths,
+            [
+                CellWidth::Narrow,
+                CellWidth::Wide,
+                CellWidth::Spacer,
+                CellWidth::Narrow
+            ]
+        );
+
+        // VS16 turns a text heart into a wide emoji, and ZWJ sequences arrive piecemeal
+        translator.write("\r\n\u{2764}".as_bytes(), &mut data);
+        assert_eq!(data.get_active_position(), Position { row: 1, col: 0 });
+        translator.write("\u{FE0F}".as_bytes(), &mut data);
+        assert_eq!(data.get_active_position(), Position { row: 1, col: 1 });
+        translator.write("👨\u{200D}".as_bytes(), &mut data);
+        translator.write("👩\u{1F3FD}x".as_bytes(), &mut data);
+        assert_eq!(data.render(1), "\u{2764}\u{FE0F}👨\u{200D}👩\u{1F3FD}x");
+        assert_eq!(data.get_active_position(), Position { row: 1, col: 4 });
+        translator.write(b"\x1B[6n", &mut data);
+        assert_eq!(data.take_responses(), b"\x1B[2;5R");
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(
+            b"plain \x1b[1;31mbold red\x1b[m \r\n\x1b[7m \x1b[m",
+            &mut data,
+        );
+        assert_eq!(
+            data.render_ansi(10),
+            "plain \x1b[0;1;38;5;1mbold red\x1b[0m\n\x1b[0;7m \x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_write_text_incomplete_utf8() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+
+        let bytes = b"\xd0";
+        // `bytes` is not valid utf8 (at least on its own...)
+        assert_matches!(String::from_utf8(bytes.into()), Err(_));
+
+        translator.write(b"\xd0", &mut data);
+        assert_eq!(data.render(10), "");
+        assert_eq!(data.get_active_position(), Position { row: 0, col: 0 });
+    }
+
+    #[test]
+    fn test_split_utf8() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+
+        let first_byte = b"\xd0";
+        assert_matches!(String::from_utf8(first_byte.into()), Err(_));
+
+        let second_byte = b"\xa3";
+        assert_matches!(String::from_utf8(second_byte.into()), Err(_));
+
+        assert_eq!(b"\xd0\xa3", "У".as_bytes());
+
+        translator.write(first_byte, &mut data);
+        translator.write(second_byte, &mut data);
+        assert_eq!(data.render(10), "У");
+        assert_eq!(data.get_active_position(), Position { row: 0, col: 0 });
+    }
+
+    #[test]
+    fn test_selected_text() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"one two   \r\nthree four", &mut data);
+
+        let mut selection = Selection::new(Position { row: 0, col: 4 });
+        selection.head = Position { row: 1, col: 4 };
+        assert_eq!(data.selected_text(&selection, true), "two\nthree");
+
+        selection.anchor = Position { row: 0, col: 0 };
+        assert_eq!(data.selected_text(&selection, false), "one two   \nthree");
+    }
+
+    #[test]
+    fn test_clear_scrollback() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"one\r\ntwo\r\nthree\r\nfour", &mut data);
+
+        data.clear_scrollback(2);
+        assert_eq!(data.render(10), "three\nfour");
+        assert_eq!(data.get_active_position().row, 1);
+        data.clear_scrollback(5);
+        assert_eq!(data.line_count(), 2);
+    }
+
+    #[test]
+    fn test_limit_scrollback() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"one\r\ntwo\r\nthree\r\nfour", &mut data);
+
+        assert_eq!(data.limit_scrollback(2, 2), 0);
+        assert_eq!(data.limit_scrollback(2, 1), 1);
+        assert_eq!(data.render(10), "two\nthree\nfour");
+        assert_eq!(data.get_active_position().row, 2);
+    }
+
+    #[test]
+    fn test_bracketed_paste_mode() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+
+        translator.write(b"\x1B[?2004h\x1B[?2004$p", &mut data);
+        assert!(data.modes().bracketed_paste);
+        assert_eq!(data.take_responses(), b"\x1B[?2004;1$y");
+
+        translator.write(b"\x1B[?2004l\x1B[?2004$p", &mut data);
+        assert!(!data.modes().bracketed_paste);
+        assert_eq!(data.take_responses(), b"\x1B[?2004;2$y");
+    }
+
+    #[test]
+    fn test_bidi_mode() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+
+        translator.write(b"\x1B[8l\x1B[8$p", &mut data);
+        assert!(data.modes().explicit_bidi);
+        assert_eq!(data.take_responses(), b"\x1B[8;2$y");
+
+        translator.write(b"\x1B[8h\x1B[8$p", &mut data);
+        assert!(!data.modes().explicit_bidi);
+        assert_eq!(data.take_responses(), b"\x1B[8;1$y");
+    }
+
+    #[test]
+    fn test_alternate_screen_mode() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+
+        translator.write(b"\x1B[?1049h", &mut data);
+        assert!(data.modes().alternate_screen && !data.modes().mouse_reporting);
+        translator.write(b"\x1B[?1000;1006h", &mut data);
+        assert!(data.modes().mouse_reporting);
+        translator.write(b"\x1B[?1000l\x1B[?1049l", &mut data);
+        assert!(!data.modes().alternate_screen && !data.modes().mouse_reporting);
+        // Neither is implemented, so DECRQM does not claim them
+        translator.write(b"\x1B[?1049$p", &mut data);
+        assert_eq!(data.take_responses(), b"\x1B[?1049;0$y");
+    }
+
+    #[test]
+    fn test_damage() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        data.take_damage();
+
+        translator.write(b"\x1B[5n", &mut data);
+        assert!(data.take_damage().is_empty());
+
+        translator.write(b"a\r\nb", &mut data);
+        assert_eq!(data.take_damage().into_iter().collect::<Vec<_>>(), [0, 1]);
+        assert!(data.take_damage().is_empty());
+    }
+
+    #[test]
+    fn test_lone_esc_times_out() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+
+        translator.write(b"\x1B", &mut data);
+        assert!(translator.is_pending());
+
+        translator.flush_stale_at(Instant::now(), &mut data);
+        assert!(translator.is_pending());
+
+        translator.flush_stale_at(Instant::now() + ESC_TIMEOUT, &mut data);
+        assert!(!translator.is_pending());
+    }
+
+    #[test]
+    fn test_split_sequences_outlast_timeout() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+
+        translator.write(b"\x1B[3", &mut data);
+        translator.flush_stale_at(Instant::now() + 2 * ESC_TIMEOUT, &mut data);
+        assert!(translator.is_pending());
+        translator.write(b"1mx", &mut data);
+        assert!(!translator.is_pending());
+        assert_eq!(data.render(10), "x");
+        let attributes = data.get_line_cells(0).next().unwrap().attributes;
+        assert_eq!(attributes.foreground, Color::Indexed(1));
+
+        translator.write(b"\x1B]7;file://host/h", &mut data);
+        translator.flush_stale_at(Instant::now() + 2 * ESC_TIMEOUT, &mut data);
+        translator.write(b"ome\x1B\\", &mut data);
+        assert!(!translator.is_pending());
+        assert_eq!(data.render(10), "x");
+        assert_eq!(
+            data.working_directory(),
+            Some(std::path::Path::new("/home"))
+        );
+    }
+
+    #[test]
+    fn test_cursor_position_report() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"ab\r\ncd\x1B[6n", &mut data);
+        assert_eq!(data.take_responses(), b"\x1B[2;2R");
+        assert_eq!(data.take_responses(), b"");
+    }
+
+    #[test]
+    fn test_sgr_stamps_written_cells() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"a\x1B[1;32mb\x1B[0mc", &mut data);
+        let attributes: Vec<_> = data.get_line_cells(0).map(|cell| cell.attributes).collect();
+        assert_eq!(attributes[0], Attributes::default());
+        assert!(attributes[1].bold);
+        assert_eq!(attributes[1].foreground, Color::Indexed(2));
+        assert_eq!(attributes[2], Attributes::default());
+    }
+
+    #[test]
+    fn test_private_marker_is_not_sgr() {
+        // XTMODKEYS, as sent by vim and neovim, must not be read as faint
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"\x1B[>4;2mx", &mut data);
+        let attributes: Vec<_> = data.get_line_cells(0).map(|cell| cell.attributes).collect();
+        assert_eq!(attributes[0], Attributes::default());
+    }
+
+    #[test]
+    fn test_osc_7_working_directory() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"\x1B]7;file://host/home/me/my%20dir\x1B\\", &mut data);
+        assert_eq!(
+            data.working_directory(),
+            Some(std::path::Path::new("/home/me/my dir"))
+        );
+    }
+
+    #[test]
+    fn test_osc_133_prompt_marks() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(
+            b"\x1B]133;A\x07$ \x1B]133;B\x07ls\r\n\x1B]133;C\x07a b\r\n\x1B]133;D;0\x07",
+            &mut data,
+        );
+        let marks: Vec<_> = data
+            .prompt_marks()
+            .iter()
+            .map(|(position, mark)| (position.row, *mark))
+            .collect();
+        assert_eq!(
+            marks,
+            [
+                (0, PromptMark::Prompt),
+                (0, PromptMark::Command),
+                (1, PromptMark::Output),
+                (2, PromptMark::Finished)
+            ]
+        );
+
+        data.clear_scrollback(2);
+        assert_eq!(data.prompt_marks().len(), 2);
+        assert_eq!(data.prompt_marks()[0].0.row, 0);
+    }
+
+    #[test]
+    fn test_osc_title() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        assert_eq!(data.title(), None);
+        translator.write(b"\x1B]0;vim; notes\x07", &mut data);
+        assert_eq!(data.title(), Some("vim; notes"));
+        translator.write(b"\x1B]2;htop\x1B\\", &mut data);
+        assert_eq!(data.title(), Some("htop"));
+    }
+
+    #[test]
+    fn test_default_color_queries() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        data.set_default_colors(Rgb(0, 0, 0), Rgb(0xFF, 0xFF, 0xFE));
+        translator.write(b"\x1B]11;?\x07", &mut data);
+        assert_eq!(data.take_responses(), b"\x1B]11;rgb:ffff/ffff/fefe\x1B\\");
+
+        translator.write(b"\x1B[?2031h\x1B[?996n", &mut data);
+        assert_eq!(data.take_responses(), b"\x1B[?997;2n");
+        data.set_default_colors(Rgb(0xFF, 0xFF, 0xFF), Rgb(0x10, 0x10, 0x10));
+        assert_eq!(data.take_responses(), b"\x1B[?997;1n");
+    }
+
+    #[derive(Default)]
+    struct RecordingHandler {
+        actions: Vec<String>,
+    }
+
+    impl TerminalHandler for RecordingHandler {
+        fn print(&mut self, text: &str) {
+            self.actions.push(format!("print {text}"));
+        }
+
+        fn execute(&mut self, control: char) {
+            self.actions.push(format!("execute {control:?}"));
+        }
+
+        fn csi_dispatch(
+            &mut self,
+            parameters: Option<&str>,
+            intermediates: Option<&str>,
+            final_byte: char,
+        ) {
+            self.actions
+                .push(format!("csi {parameters:?} {intermediates:?} {final_byte}"));
+        }
+
+        fn osc_dispatch(&mut self, data: &str) {
+            self.actions.push(format!("osc {data}"));
+        }
+    }
+
+    #[test]
+    fn test_dispatch_to_handler() {
+        let mut handler = RecordingHandler::default();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"hi\r\n\x1B[2K\x1B]0;title\x07", &mut handler);
+        assert_eq!(
+            handler.actions,
+            [
+                "print hi",
+                "execute '\\r'",
+                "execute '\\n'",
+                "csi Some(\"2\") None K",
+                "osc 0;title",
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+/*!
+ * Scripted conformance checks in the spirit of vttest's screens and
+ * esctest's test classes: each feeds a control sequence to a fresh
+ * `Terminal` and looks at the screen, the replies or the title that result.
+ *
+ * Unlike the other tests, these do not all have to pass. The number passing
+ * in each group is compared with `tests/conformance/baseline.txt`, so a
+ * regression fails while the overall score is tracked as it improves.
+ * Run with
+ *
+ *     cargo test -p firn-core --features conformance --test conformance -- --nocapture
+ *
+ * and add `FIRN_UPDATE_CONFORMANCE=1` to record a new baseline.
+ */
+#![cfg(feature = "conformance")]
+
+use firn_core::Terminal;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+enum Expect {
+    /** The text of the screen, trailing whitespace trimmed */
+    Screen(&'static str),
+    /** The screen with SGR sequences for its attributes */
+    Ansi(&'static str),
+    /** What the terminal writes back to the program */
+    Response(&'static str),
+    Title(&'static str),
+}
+
+use Expect::*;
+
+struct Check {
+    group: &'static str,
+    name: &'static str,
+    input: &'static str,
+    expect: Expect,
+}
+
+const fn check(
+    group: &'static str,
+    name: &'static str,
+    input: &'static str,
+    expect: Expect,
+) -> Check {
+    Check {
+        group,
+        name,
+        input,
+        expect,
+    }
+}
+
+const CHECKS: &[Check] = &[
+    check("cursor", "CR", "abc\rX", Screen("Xbc")),
+    check("cursor", "BS", "abc\x08X", Screen("abX")),
+    check("cursor", "LF keeps the column", "ab\nX", Screen("ab\n  X")),
+    check("cursor", "HT", "a\tb", Screen("a       b")),
+    check("cursor", "CUF", "a\x1b[3CX", Screen("a   X")),
+    check("cursor", "CUB", "abcd\x1b[2DX", Screen("abXd")),
+    check("cursor", "CUU", "a\r\nb\x1b[AX", Screen("aX\nb")),
+    check("cursor", "CUD", "a\x1b[BX", Screen("a\n X")),
+    check("cursor", "CUP", "\x1b[2;3HX", Screen("\n  X")),
+    check("cursor", "HVP", "\x1b[2;3fX", Screen("\n  X")),
+    check("cursor", "CHA", "abcd\x1b[2GX", Screen("aXcd")),
+    check("cursor", "DECSC/DECRC", "ab\x1b7cd\x1b8X", Screen("abXd")),
+    check("erase", "EL 0", "abcd\x1b[2D\x1b[K", Screen("ab")),
+    check("erase", "EL 1", "abcd\x1b[2D\x1b[1K", Screen("   d")),
+    check("erase", "EL 2", "abcd\x1b[2K", Screen("")),
+    check("erase", "ED 0", "ab\r\ncd\x1b[A\x1b[J", Screen("ab")),
+    check("erase", "ED 2", "ab\r\ncd\x1b[2J", Screen("")),
+    check("erase", "ECH", "abcd\x1b[3D\x1b[2X", Screen("a  d")),
+    check("editing", "ICH", "abcd\x1b[3D\x1b[2@", Screen("a  bcd")),
+    check("editing", "DCH", "abcd\x1b[3D\x1b[2P", Screen("ad")),
+    check("editing", "IL", "a\r\nb\x1b[A\x1b[L", Screen("\na\nb")),
+    check("editing", "DL", "a\r\nb\x1b[A\x1b[M", Screen("b")),
+    check("editing", "REP", "a\x1b[3b", Screen("aaaa")),
+    check("scrolling", "IND", "a\x1bDb", Screen("a\n b")),
+    check("scrolling", "NEL", "ab\x1bEc", Screen("ab\nc")),
+    check("scrolling", "RI at the top", "a\x1bMb", Screen(" b\na")),
+    check("sgr", "bold", "\x1b[1mx", Ansi("\x1b[0;1mx\x1b[0m")),
+    check("sgr", "reset", "\x1b[1m\x1b[0mx", Ansi("x")),
+    check(
+        "sgr",
+        "256 colors",
+        "\x1b[38;5;100mx",
+        Ansi("\x1b[0;38;5;100mx\x1b[0m"),
+    ),
+    check(
+        "sgr",
+        "direct color",
+        "\x1b[48;2;1;2;3mx",
+        Ansi("\x1b[0;48;2;1;2;3mx\x1b[0m"),
+    ),
+    check(
+        "sgr",
+        "colon separators",
+        "\x1b[38:2::1:2:3mx",
+        Ansi("\x1b[0;38;2;1;2;3mx\x1b[0m"),
+    ),
+    check("reports", "DA1", "\x1b[c", Response("\x1b[?6c")),
+    check("reports", "DSR 5", "\x1b[5n", Response("\x1b[0n")),
+    check("reports", "CPR", "abc\x1b[6n", Response("\x1b[1;4R")),
+    check(
+        "reports",
+        "DECRQM",
+        "\x1b[?2004h\x1b[?2004$p",
+        Response("\x1b[?2004;1$y"),
+    ),
+    check("osc", "title with BEL", "\x1b]2;hello\x07", Title("hello")),
+    check("osc", "title with ST", "\x1b]0;hi\x1b\\", Title("hi")),
+];
+
+/** Why `check` failed, if it did */
+fn run(check: &Check) -> Option<String> {
+    let mut terminal = Terminal::new().unwrap();
+    terminal.advance(check.input.as_bytes());
+    let (expected, got) = match check.expect {
+        Screen(expected) => {
+            let mut screen = vec![];
+            terminal.render_to(&mut screen, usize::MAX).unwrap();
+            (expected, String::from_utf8(screen).unwrap())
+        }
+        Ansi(expected) => {
+            let mut screen = vec![];
+            terminal.render_ansi_to(&mut screen, usize::MAX).unwrap();
+            (expected, String::from_utf8(screen).unwrap())
+        }
+        Response(expected) => (
+            expected,
+            String::from_utf8_lossy(&terminal.take_responses()).into_owned(),
+        ),
+        Title(expected) => (expected, terminal.data().title().unwrap_or_default().into()),
+    };
+    let got = got.trim_end();
+    (got != expected).then(|| format!("expected {expected:?}, got {got:?}"))
+}
+
+#[test]
+fn test_conformance() {
+    let mut groups: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    let mut report = String::new();
+    std::panic::set_hook(Box::new(|_| {}));
+    for check in CHECKS {
+        let (passed, total) = groups.entry(check.group).or_default();
+        *total += 1;
+        // A sequence the emulator chokes on is one more failure, not the end of the run
+        let result = std::panic::catch_unwind(|| run(check))
+            .unwrap_or_else(|_| Some("panicked".to_string()));
+        match result {
+            None => *passed += 1,
+            Some(failure) => {
+                let _ = writeln!(report, "FAIL {}: {}: {failure}", check.group, check.name);
+            }
+        }
+    }
+    drop(std::panic::take_hook());
+    let mut scores = String::new();
+    for (group, (passed, total)) in &groups {
+        let _ = writeln!(scores, "{group} {passed}/{total}");
+    }
+    println!("{report}\n{scores}");
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/baseline.txt");
+    if std::env::var_os("FIRN_UPDATE_CONFORMANCE").is_some() {
+        std::fs::write(&path, &scores).unwrap();
+        return;
+    }
+    let baseline = std::fs::read_to_string(&path).unwrap_or_default();
+    for line in baseline.lines() {
+        let Some((group, score)) = line.split_once(' ') else {
+            continue;
+        };
+        let before: usize = score.split('/').next().unwrap().parse().unwrap();
+        let now = groups.get(group).map_or(0, |(passed, _)| *passed);
+        assert!(
+            now >= before,
+            "{group}: {now} checks pass, down from {before} in the baseline"
+        );
+    }
+}
@@ -0,0 +1,60 @@
+/*!
+ * Replays each recording in `tests/golden` through the emulator and compares
+ * the screen with the `.txt` file of the same name. Recordings are made with
+ * `firn --record`, e.g. `firn --headless --record name.rec -e ...`.
+ *
+ * After an intentional change, rewrite the expected screens with
+ * `FIRN_UPDATE_GOLDEN=1 cargo test -p firn-core --test golden`.
+ */
+
+use firn_core::recording;
+use firn_core::Terminal;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+fn replay(path: &Path) -> String {
+    let mut terminal = Terminal::new().unwrap();
+    let file = File::open(path).unwrap();
+    for chunk in recording::chunks(BufReader::new(file)) {
+        terminal.advance(&chunk.unwrap().data);
+    }
+    let mut screen = vec![];
+    terminal.render_to(&mut screen, usize::MAX).unwrap();
+    String::from_utf8(screen).unwrap() + "\n"
+}
+
+#[test]
+fn test_golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("FIRN_UPDATE_GOLDEN").is_some();
+    let mut recordings: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "rec"))
+        .collect();
+    recordings.sort();
+    assert!(!recordings.is_empty(), "No recordings in {dir:?}");
+
+    let mut failures = vec![];
+    for recording in recordings {
+        let golden = recording.with_extension("txt");
+        let screen = replay(&recording);
+        if update {
+            std::fs::write(&golden, &screen).unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&golden).unwrap_or_default();
+        if screen != expected {
+            failures.push(format!(
+                "{recording:?}\n--- expected\n{expected}--- got\n{screen}"
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "Screens differ from the golden files; rerun with FIRN_UPDATE_GOLDEN=1 if that is \
+         intended\n\n{}",
+        failures.join("\n")
+    );
+}
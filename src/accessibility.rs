@@ -0,0 +1,258 @@
+use accesskit::{Live, NodeBuilder, NodeId, Role, TextPosition, TextSelection, Tree, TreeUpdate};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/** The least time between announcements, so that a flood of output is not read out line by line */
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_millis(500);
+/** How many lines of output wait to be announced; older ones are skipped */
+const MAX_QUEUED_LINES: usize = 4;
+
+const WINDOW: NodeId = NodeId(0);
+const TERMINAL: NodeId = NodeId(1);
+const ANNOUNCEMENT: NodeId = NodeId(2);
+/** Rows of the grid are numbered from here */
+const FIRST_ROW: u64 = 3;
+
+/** What a screen reader is told about: the rows in view and where the cursor is among them */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Screen {
+    pub title: String,
+    pub rows: Vec<String>,
+    /** Row and column, unless the cursor is scrolled out of view */
+    pub cursor: Option<(usize, usize)>,
+}
+
+/** Lines of output waiting to be read out, at most one announcement per `ANNOUNCE_INTERVAL` */
+#[derive(Default)]
+struct Announcer {
+    queued: VecDeque<String>,
+    last: Option<Instant>,
+}
+
+impl Announcer {
+    fn push(&mut self, line: &str) {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.queued.len() == MAX_QUEUED_LINES {
+            self.queued.pop_front();
+        }
+        self.queued.push_back(line.to_owned());
+    }
+
+    fn take(&mut self, now: Instant) -> Option<String> {
+        if self.queued.is_empty()
+            || self
+                .last
+                .is_some_and(|last| now.duration_since(last) < ANNOUNCE_INTERVAL)
+        {
+            return None;
+        }
+        self.last = Some(now);
+        Some(Vec::from(std::mem::take(&mut self.queued)).join("\n"))
+    }
+}
+
+/**
+ * Shows the grid to screen readers through AccessKit, as a terminal whose
+ * rows are lines of text with the cursor as the caret, and reads out new
+ * output through a live region. Only AT-SPI, i.e. Linux and the BSDs, is
+ * supported, since iced does not hand out the native window the other
+ * platforms' adapters need.
+ */
+pub struct Accessibility {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    adapter: accesskit_unix::Adapter,
+    /** The latest tree in full, for whenever a screen reader connects */
+    latest: Arc<Mutex<TreeUpdate>>,
+    screen: Screen,
+    announcer: Announcer,
+    announcement: String,
+}
+
+impl Accessibility {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn new() -> Option<Self> {
+        let latest = Arc::new(Mutex::new(tree(&Screen::default(), "")));
+        let adapter =
+            accesskit_unix::Adapter::new(Activation(latest.clone()), NoActions, NoDeactivation);
+        Some(Self {
+            adapter,
+            latest,
+            screen: Screen::default(),
+            announcer: Announcer::default(),
+            announcement: String::new(),
+        })
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub fn new() -> Option<Self> {
+        tracing::error!("Screen readers are only supported through AT-SPI, on Linux and the BSDs");
+        None
+    }
+
+    /** A line of output to read out */
+    pub fn announce(&mut self, line: &str) {
+        self.announcer.push(line);
+    }
+
+    /** Whether lines are waiting for `ANNOUNCE_INTERVAL` to pass */
+    pub fn is_waiting(&self) -> bool {
+        !self.announcer.queued.is_empty()
+    }
+
+    /** Tell the screen reader about the screen, if it or the announcement changed */
+    pub fn update(&mut self, screen: Screen, now: Instant) {
+        let announcement = self.announcer.take(now);
+        if screen == self.screen && announcement.is_none() {
+            return;
+        }
+        self.screen = screen;
+        if let Some(announcement) = announcement {
+            self.announcement = announcement;
+        }
+        let update = tree(&self.screen, &self.announcement);
+        *self.latest.lock().unwrap() = update.clone();
+        #[cfg(all(unix, not(target_os = "macos")))]
+        self.adapter.update_if_active(|| update);
+    }
+
+    pub fn set_focused(&mut self, _focused: bool) {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        self.adapter.update_window_focus_state(_focused);
+    }
+
+    /** Where the window is on screen, in logical pixels, for screen readers that highlight it */
+    pub fn set_bounds(&mut self, _position: (i32, i32), _size: (u32, u32)) {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let (x, y) = (f64::from(_position.0), f64::from(_position.1));
+            let bounds = accesskit::Rect::new(x, y, x + f64::from(_size.0), y + f64::from(_size.1));
+            self.adapter.set_root_window_bounds(bounds, bounds);
+        }
+    }
+}
+
+/** The whole tree for a screen, which is small enough to send in full on every change */
+fn tree(screen: &Screen, announcement: &str) -> TreeUpdate {
+    let row_id = |row: usize| NodeId(FIRST_ROW + row as u64);
+    let mut nodes = vec![];
+
+    let mut window = NodeBuilder::new(Role::Window);
+    window.set_name(screen.title.as_str());
+    window.set_children(vec![TERMINAL, ANNOUNCEMENT]);
+    nodes.push((WINDOW, window.build()));
+
+    let mut terminal = NodeBuilder::new(Role::Terminal);
+    terminal.set_name(screen.title.as_str());
+    terminal.set_read_only();
+    terminal.set_children((0..screen.rows.len()).map(row_id).collect::<Vec<_>>());
+    if let Some((row, col)) = screen.cursor {
+        let position = TextPosition {
+            node: row_id(row),
+            character_index: col.min(screen.rows[row].chars().count()),
+        };
+        terminal.set_text_selection(TextSelection {
+            anchor: position,
+            focus: position,
+        });
+    }
+    nodes.push((TERMINAL, terminal.build()));
+
+    for (index, text) in screen.rows.iter().enumerate() {
+        let mut text = text.clone();
+        if index + 1 < screen.rows.len() {
+            text.push('\n');
+        }
+        let mut row = NodeBuilder::new(Role::InlineTextBox);
+        let lengths: Vec<u8> = text.chars().map(|ch| ch.len_utf8() as u8).collect();
+        row.set_character_lengths(lengths);
+        row.set_value(text);
+        nodes.push((row_id(index), row.build()));
+    }
+
+    let mut live = NodeBuilder::new(Role::Status);
+    live.set_live(Live::Polite);
+    live.set_value(announcement);
+    nodes.push((ANNOUNCEMENT, live.build()));
+
+    let mut tree = Tree::new(WINDOW);
+    tree.app_name = Some("firn".into());
+    TreeUpdate {
+        nodes,
+        tree: Some(tree),
+        focus: TERMINAL,
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+struct Activation(Arc<Mutex<TreeUpdate>>);
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl accesskit::ActivationHandler for Activation {
+    fn request_initial_tree(&mut self) -> Option<TreeUpdate> {
+        Some(self.0.lock().unwrap().clone())
+    }
+}
+
+/** A terminal's only action is typing, which goes through the keyboard anyway */
+#[cfg(all(unix, not(target_os = "macos")))]
+struct NoActions;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl accesskit::ActionHandler for NoActions {
+    fn do_action(&mut self, _request: accesskit::ActionRequest) {}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+struct NoDeactivation;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl accesskit::DeactivationHandler for NoDeactivation {
+    fn deactivate_accessibility(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcer() {
+        let start = Instant::now();
+        let mut announcer = Announcer::default();
+        assert_eq!(announcer.take(start), None);
+        announcer.push("$ make  ");
+        announcer.push("   ");
+        assert_eq!(announcer.take(start).as_deref(), Some("$ make"));
+        for line in ["1", "2", "3", "4", "5"] {
+            announcer.push(line);
+        }
+        assert_eq!(announcer.take(start + ANNOUNCE_INTERVAL / 2), None);
+        assert_eq!(
+            announcer.take(start + ANNOUNCE_INTERVAL).as_deref(),
+            Some("2\n3\n4\n5")
+        );
+    }
+
+    #[test]
+    fn test_tree() {
+        let screen = Screen {
+            title: "vim".into(),
+            rows: vec!["ab".into(), "ü".into()],
+            cursor: Some((1, 5)),
+        };
+        let update = tree(&screen, "done");
+        assert_eq!(update.nodes.len(), 5);
+        let (_, terminal) = &update.nodes[1];
+        let caret = terminal.text_selection().unwrap().focus;
+        assert_eq!(caret.node, NodeId(FIRST_ROW + 1));
+        assert_eq!(caret.character_index, 1);
+        let (_, row) = &update.nodes[2];
+        assert_eq!(row.value(), Some("ab\n"));
+        assert_eq!(row.character_lengths(), [1, 1, 1]);
+        let (_, row) = &update.nodes[3];
+        assert_eq!(row.character_lengths(), [2]);
+    }
+}
@@ -0,0 +1,222 @@
+use iced::futures::channel::mpsc::Sender;
+use iced::futures::SinkExt;
+use iced::{subscription, Subscription};
+use std::future::pending;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tracing::debug;
+
+/**
+ * How often to ask macOS and Windows whether they switched between light and
+ * dark, for want of a way to be told
+ */
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/** Whether the desktop prefers light or dark colors */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Appearance {
+    Light,
+    Dark,
+}
+
+async fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    if !output.status.success() {
+        debug!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/**
+ * Ask the OS which appearance it prefers, or `None` if it cannot tell.
+ * There is no portable API for this, so it runs whatever each platform's
+ * desktop provides for querying it.
+ */
+pub async fn detect() -> Option<Appearance> {
+    if cfg!(target_os = "macos") {
+        // Only set at all while dark mode is on
+        let style = output("defaults", &["read", "-g", "AppleInterfaceStyle"]).await;
+        Some(match style {
+            Some(style) if style.trim() == "Dark" => Appearance::Dark,
+            _ => Appearance::Light,
+        })
+    } else if cfg!(windows) {
+        let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+        let value = output("reg", &["query", key, "/v", "AppsUseLightTheme"]).await?;
+        parse_registry(&value)
+    } else {
+        let portal = output(
+            "gdbus",
+            &[
+                "call",
+                "--session",
+                "--dest=org.freedesktop.portal.Desktop",
+                "--object-path=/org/freedesktop/portal/desktop",
+                "--method=org.freedesktop.portal.Settings.Read",
+                "org.freedesktop.appearance",
+                "color-scheme",
+            ],
+        )
+        .await;
+        match portal.as_deref().and_then(parse_portal) {
+            Some(appearance) => Some(appearance),
+            None => {
+                let scheme = output(
+                    "gsettings",
+                    &["get", "org.gnome.desktop.interface", "color-scheme"],
+                )
+                .await?;
+                Some(parse_gsettings(&scheme))
+            }
+        }
+    }
+}
+
+/**
+ * The appearance each time the desktop switches it. On Linux a single
+ * `gdbus monitor` listens for the portal's `SettingChanged` signal; macOS and
+ * Windows are asked every `POLL_INTERVAL`.
+ */
+pub fn subscription() -> Subscription<Option<Appearance>> {
+    struct Watch;
+
+    subscription::channel(
+        std::any::TypeId::of::<Watch>(),
+        4,
+        async move |mut output: Sender<Option<Appearance>>| {
+            if cfg!(any(target_os = "macos", windows)) {
+                loop {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    let _ = output.send(detect().await).await;
+                }
+            }
+            if let Err(err) = watch_portal(&mut output).await {
+                debug!("Unable to watch the desktop portal's appearance: {err}");
+            }
+            pending::<()>().await;
+            unreachable!();
+        },
+    )
+}
+
+async fn watch_portal(output: &mut Sender<Option<Appearance>>) -> std::io::Result<()> {
+    let gdbus = [
+        "gdbus",
+        "monitor",
+        "--session",
+        "--dest=org.freedesktop.portal.Desktop",
+        "--object-path=/org/freedesktop/portal/desktop",
+    ];
+    let spawn = |program: &str, args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+    };
+    // Into a pipe, gdbus buffers what it prints, which would hold a change back for
+    // kilobytes of other signals, so it is told to print by the line where it can be
+    let mut monitor = match spawn("stdbuf", &[&["-oL"], &gdbus[..]].concat()) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => spawn(gdbus[0], &gdbus[1..]),
+        monitor => monitor,
+    }?;
+    let mut lines = BufReader::new(monitor.stdout.take().unwrap()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if let Some(appearance) = parse_setting_changed(&line) {
+            if output.send(appearance).await.is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/**
+ * `gdbus monitor` prints the signal as e.g. `/org/freedesktop/portal/desktop:
+ * org.freedesktop.portal.Settings.SettingChanged ('org.freedesktop.appearance',
+ * 'color-scheme', <uint32 1>)`, among other signals and settings
+ */
+fn parse_setting_changed(line: &str) -> Option<Option<Appearance>> {
+    let (_, arguments) = line.split_once(".SettingChanged ")?;
+    arguments
+        .starts_with("('org.freedesktop.appearance', 'color-scheme',")
+        .then(|| parse_portal(arguments))
+}
+
+/**
+ * The desktop portal answers with e.g. `(<<uint32 1>>,)`, where 1 means dark,
+ * 2 light and 0 that there is no preference
+ */
+fn parse_portal(reply: &str) -> Option<Appearance> {
+    let value = reply.split("uint32").nth(1)?;
+    match value.trim_start().chars().next()? {
+        '1' => Some(Appearance::Dark),
+        '2' => Some(Appearance::Light),
+        _ => None,
+    }
+}
+
+/** GNOME's setting is e.g. `'prefer-dark'` or `'default'` */
+fn parse_gsettings(scheme: &str) -> Appearance {
+    if scheme.contains("dark") {
+        Appearance::Dark
+    } else {
+        Appearance::Light
+    }
+}
+
+/** `reg query` prints the value as e.g. `AppsUseLightTheme    REG_DWORD    0x0` */
+fn parse_registry(value: &str) -> Option<Appearance> {
+    let data = value
+        .lines()
+        .find(|line| line.contains("AppsUseLightTheme"))?
+        .split_whitespace()
+        .last()?;
+    Some(match data {
+        "0x0" => Appearance::Dark,
+        _ => Appearance::Light,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_portal("(<<uint32 1>>,)\n"), Some(Appearance::Dark));
+        assert_eq!(parse_portal("(<<uint32 2>>,)\n"), Some(Appearance::Light));
+        assert_eq!(parse_portal("(<<uint32 0>>,)\n"), None);
+        let changed =
+            "/org/freedesktop/portal/desktop: org.freedesktop.portal.Settings.SettingChanged";
+        assert_eq!(
+            parse_setting_changed(&format!(
+                "{changed} ('org.freedesktop.appearance', 'color-scheme', <uint32 1>)"
+            )),
+            Some(Some(Appearance::Dark))
+        );
+        assert_eq!(
+            parse_setting_changed(&format!(
+                "{changed} ('org.freedesktop.appearance', 'color-scheme', <uint32 0>)"
+            )),
+            Some(None)
+        );
+        assert_eq!(
+            parse_setting_changed(&format!(
+                "{changed} ('org.gnome.desktop.interface', 'font-name', <'Cantarell 11'>)"
+            )),
+            None
+        );
+        assert_eq!(parse_gsettings("'prefer-dark'\n"), Appearance::Dark);
+        assert_eq!(parse_gsettings("'default'\n"), Appearance::Light);
+        let reg = "\r\nHKEY_CURRENT_USER\\...\\Personalize\r\n    AppsUseLightTheme    REG_DWORD    0x0\r\n";
+        assert_eq!(parse_registry(reg), Some(Appearance::Dark));
+    }
+}
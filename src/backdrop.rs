@@ -0,0 +1,161 @@
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer::{self, Quad};
+use iced::advanced::widget::{Operation, Tree};
+use iced::advanced::{mouse, overlay, Clipboard, Renderer as _, Shell, Widget};
+use iced::event::{self, Event};
+use iced::widget::image;
+use iced::{Background, Color, ContentFit, Element, Length, Rectangle, Renderer, Theme};
+
+/**
+ * Draws an image behind its content, covering it and dimmed by a translucent
+ * wash of the background color so the text on top stays readable. iced has
+ * no widget to stack one thing on another, hence this.
+ */
+pub struct Backdrop<'a, Message> {
+    image: image::Handle,
+    /** Drawn over the image */
+    wash: Color,
+    content: Element<'a, Message>,
+}
+
+impl<'a, Message> Backdrop<'a, Message> {
+    pub fn new(
+        image: image::Handle,
+        wash: Color,
+        content: impl Into<Element<'a, Message>>,
+    ) -> Self {
+        Self {
+            image,
+            wash,
+            content: content.into(),
+        }
+    }
+}
+
+impl<'a, Message> Widget<Message, Renderer> for Backdrop<'a, Message> {
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content))
+    }
+
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.as_widget().layout(renderer, limits);
+        layout::Node::with_children(content.size(), vec![content])
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        self.content.as_widget().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        image::draw(renderer, layout, &self.image, ContentFit::Cover);
+        renderer.fill_quad(
+            Quad {
+                bounds: layout.bounds(),
+                border_radius: 0.0.into(),
+                border_width: 0.0,
+                border_color: Color::TRANSPARENT,
+            },
+            Background::Color(self.wash),
+        );
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+        );
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+    ) -> Option<overlay::Element<'b, Message, Renderer>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+        )
+    }
+}
+
+impl<'a, Message: 'a> From<Backdrop<'a, Message>> for Element<'a, Message> {
+    fn from(backdrop: Backdrop<'a, Message>) -> Self {
+        Element::new(backdrop)
+    }
+}
@@ -0,0 +1,109 @@
+use crate::config::BackendConfig;
+use anyhow::Result;
+use iced::futures::future::BoxFuture;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+#[cfg(windows)]
+mod conpty;
+mod playback;
+#[cfg(unix)]
+mod serial;
+#[cfg(unix)]
+mod unix;
+
+#[cfg(windows)]
+pub use conpty::ConPty as DefaultBackend;
+#[cfg(unix)]
+pub use unix::UnixPty as DefaultBackend;
+
+/** Size of the grid, in cells, as seen by the child */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+/** The program to run on the other side of the pty */
+#[derive(Clone, Debug)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub working_directory: Option<PathBuf>,
+}
+
+/** Keys that ask the line discipline for a signal or end of file */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlKey {
+    Interrupt,
+    EndOfFile,
+    Suspend,
+    Quit,
+}
+
+impl ControlKey {
+    /**
+     * The control character for this key. The line discipline decides what
+     * it does, by the settings the program left it with: a program reading
+     * raw keys gets the character itself.
+     */
+    pub fn character(self) -> u8 {
+        match self {
+            Self::Interrupt => 0x03,
+            Self::EndOfFile => 0x04,
+            Self::Suspend => 0x1A,
+            Self::Quit => 0x1C,
+        }
+    }
+}
+
+/** The write half of a pty, which also controls its size */
+pub trait PtyWriter: AsyncWrite + Send + Unpin {
+    fn resize(&self, size: WindowSize) -> Result<()>;
+}
+
+/** The process attached to a pty */
+pub trait PtyChild: Send {
+    fn id(&self) -> Option<u32>;
+
+    fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>>;
+
+    /** Ask the process to exit, as happens when its terminal goes away */
+    fn hangup(&mut self) -> Result<()>;
+
+    /** End the process forcibly */
+    fn kill(&mut self) -> Result<()>;
+}
+
+pub struct SpawnedPty {
+    pub reader: Box<dyn AsyncRead + Send + Unpin>,
+    pub writer: Box<dyn PtyWriter>,
+    pub child: Box<dyn PtyChild>,
+}
+
+/** A platform mechanism for running a program attached to a pseudo terminal */
+pub trait PtyBackend {
+    fn spawn(&self, command: &CommandSpec, size: WindowSize) -> Result<SpawnedPty>;
+}
+
+/** The backend selected by the configuration */
+pub fn from_config(config: &BackendConfig) -> Result<Box<dyn PtyBackend>> {
+    Ok(match config {
+        BackendConfig::Pty => Box::new(DefaultBackend),
+        #[cfg(unix)]
+        BackendConfig::Serial { device, baud_rate } => Box::new(serial::SerialBackend {
+            device: device.clone(),
+            baud_rate: *baud_rate,
+        }),
+        #[cfg(unix)]
+        BackendConfig::Fd { fd } => Box::new(serial::FdBackend { fd: *fd }),
+        BackendConfig::Playback { path, speed } => Box::new(playback::PlaybackBackend {
+            path: path.clone(),
+            speed: *speed,
+        }),
+        #[cfg(not(unix))]
+        backend => anyhow::bail!("{backend:?} is not supported on this platform"),
+    })
+}
@@ -0,0 +1,297 @@
+use super::{CommandSpec, PtyBackend, PtyChild, PtyWriter, SpawnedPty, WindowSize};
+use anyhow::{bail, Context, Result};
+use iced::futures::future::BoxFuture;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::{FromRawHandle, RawHandle};
+use std::os::windows::process::ExitStatusExt;
+use std::pin::Pin;
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::{mem, ptr};
+use tokio::io::AsyncWrite;
+use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+use windows_sys::Win32::System::Console::{
+    ClosePseudoConsole, CreatePseudoConsole, ResizePseudoConsole, COORD, HPCON,
+};
+use windows_sys::Win32::System::Pipes::CreatePipe;
+use windows_sys::Win32::System::Threading::{
+    CreateProcessW, DeleteProcThreadAttributeList, GetExitCodeProcess,
+    InitializeProcThreadAttributeList, TerminateProcess, UpdateProcThreadAttribute,
+    WaitForSingleObject, CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT, INFINITE,
+    LPPROC_THREAD_ATTRIBUTE_LIST, PROCESS_INFORMATION, PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE,
+    STARTUPINFOEXW,
+};
+
+/** Windows pseudo consoles (ConPTY), available since Windows 10 1809 */
+pub struct ConPty;
+
+impl From<WindowSize> for COORD {
+    fn from(size: WindowSize) -> Self {
+        Self {
+            X: size.cols as i16,
+            Y: size.rows as i16,
+        }
+    }
+}
+
+/** Closes the pseudo console once both the writer and the child are gone */
+struct PseudoConsole(HPCON);
+
+impl Drop for PseudoConsole {
+    fn drop(&mut self) {
+        unsafe { ClosePseudoConsole(self.0) };
+    }
+}
+
+/** An owned handle that is closed on drop */
+struct OwnedHandle(HANDLE);
+
+impl Drop for OwnedHandle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+fn create_pipe() -> Result<(OwnedHandle, OwnedHandle)> {
+    let (mut read, mut write) = (0, 0);
+    if unsafe { CreatePipe(&mut read, &mut write, ptr::null(), 0) } == 0 {
+        return Err(io::Error::last_os_error()).with_context(|| "Unable to create a pipe");
+    }
+    Ok((OwnedHandle(read), OwnedHandle(write)))
+}
+
+fn into_file(handle: OwnedHandle) -> File {
+    let file = unsafe { File::from_raw_handle(handle.0 as RawHandle) };
+    mem::forget(handle);
+    file
+}
+
+fn to_wide(text: impl AsRef<std::ffi::OsStr>) -> Vec<u16> {
+    text.as_ref().encode_wide().chain(Some(0)).collect()
+}
+
+/** Quote a single argument following the rules of `CommandLineToArgvW` */
+fn quote_argument(argument: &str, command_line: &mut String) {
+    if !argument.is_empty() && !argument.contains([' ', '\t', '\n', '"']) {
+        command_line.push_str(argument);
+        return;
+    }
+    command_line.push('"');
+    let mut backslashes = 0;
+    for c in argument.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                command_line.extend(std::iter::repeat('\\').take(backslashes * 2 + 1));
+                command_line.push('"');
+                backslashes = 0;
+            }
+            c => {
+                command_line.extend(std::iter::repeat('\\').take(backslashes));
+                command_line.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    command_line.extend(std::iter::repeat('\\').take(backslashes * 2));
+    command_line.push('"');
+}
+
+fn command_line(command: &CommandSpec) -> Vec<u16> {
+    let mut command_line = String::new();
+    for argument in std::iter::once(&command.program).chain(&command.args) {
+        if !command_line.is_empty() {
+            command_line.push(' ');
+        }
+        quote_argument(argument, &mut command_line);
+    }
+    to_wide(command_line)
+}
+
+/** firn's own environment with the command's variables layered on top */
+fn environment_block(command: &CommandSpec) -> Vec<u16> {
+    let mut environment: BTreeMap<String, String> = std::env::vars().collect();
+    environment.extend(command.env.iter().cloned());
+    let mut block = vec![];
+    for (key, value) in environment {
+        block.extend(format!("{key}={value}").encode_utf16());
+        block.push(0);
+    }
+    block.push(0);
+    block
+}
+
+impl PtyBackend for ConPty {
+    fn spawn(&self, command: &CommandSpec, size: WindowSize) -> Result<SpawnedPty> {
+        let (input_read, input_write) = create_pipe()?;
+        let (output_read, output_write) = create_pipe()?;
+
+        let mut console = 0;
+        let result = unsafe {
+            CreatePseudoConsole(size.into(), input_read.0, output_write.0, 0, &mut console)
+        };
+        if result < 0 {
+            bail!("Unable to create a pseudo console (HRESULT {result:#x})");
+        }
+        let console = Arc::new(PseudoConsole(console));
+        // The pseudo console holds its own references to these
+        drop((input_read, output_write));
+
+        let mut attribute_list_size = 0;
+        unsafe {
+            InitializeProcThreadAttributeList(ptr::null_mut(), 1, 0, &mut attribute_list_size)
+        };
+        let mut attribute_list_buffer = vec![0u8; attribute_list_size];
+        let attribute_list = attribute_list_buffer.as_mut_ptr() as LPPROC_THREAD_ATTRIBUTE_LIST;
+        if unsafe {
+            InitializeProcThreadAttributeList(attribute_list, 1, 0, &mut attribute_list_size)
+        } == 0
+        {
+            return Err(io::Error::last_os_error())
+                .with_context(|| "Unable to initialize the attribute list");
+        }
+        let updated = unsafe {
+            UpdateProcThreadAttribute(
+                attribute_list,
+                0,
+                PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE as usize,
+                console.0 as *const _,
+                mem::size_of::<HPCON>(),
+                ptr::null_mut(),
+                ptr::null(),
+            )
+        };
+        if updated == 0 {
+            let err = io::Error::last_os_error();
+            unsafe { DeleteProcThreadAttributeList(attribute_list) };
+            return Err(err).with_context(|| "Unable to attach the pseudo console");
+        }
+
+        let mut startup_info: STARTUPINFOEXW = unsafe { mem::zeroed() };
+        startup_info.StartupInfo.cb = mem::size_of::<STARTUPINFOEXW>() as u32;
+        startup_info.lpAttributeList = attribute_list;
+
+        let mut command_line = command_line(command);
+        let environment = environment_block(command);
+        let working_directory = command.working_directory.as_ref().map(to_wide);
+        let mut process_info: PROCESS_INFORMATION = unsafe { mem::zeroed() };
+        let created = unsafe {
+            CreateProcessW(
+                ptr::null(),
+                command_line.as_mut_ptr(),
+                ptr::null(),
+                ptr::null(),
+                0,
+                EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT,
+                environment.as_ptr() as *const _,
+                working_directory
+                    .as_ref()
+                    .map_or(ptr::null(), |directory| directory.as_ptr()),
+                &startup_info.StartupInfo,
+                &mut process_info,
+            )
+        };
+        let err = io::Error::last_os_error();
+        unsafe { DeleteProcThreadAttributeList(attribute_list) };
+        if created == 0 {
+            return Err(err).with_context(|| format!("Unable to start {:?}", command.program));
+        }
+        drop(OwnedHandle(process_info.hThread));
+
+        Ok(SpawnedPty {
+            reader: Box::new(tokio::fs::File::from_std(into_file(output_read))),
+            writer: Box::new(ConPtyWriter {
+                file: tokio::fs::File::from_std(into_file(input_write)),
+                console: console.clone(),
+            }),
+            child: Box::new(ConPtyChild {
+                process: Arc::new(OwnedHandle(process_info.hProcess)),
+                pid: process_info.dwProcessId,
+                _console: console,
+            }),
+        })
+    }
+}
+
+struct ConPtyWriter {
+    file: tokio::fs::File,
+    console: Arc<PseudoConsole>,
+}
+
+impl AsyncWrite for ConPtyWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.file).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_shutdown(cx)
+    }
+}
+
+impl PtyWriter for ConPtyWriter {
+    fn resize(&self, size: WindowSize) -> Result<()> {
+        let result = unsafe { ResizePseudoConsole(self.console.0, size.into()) };
+        if result < 0 {
+            bail!("Unable to resize the pseudo console (HRESULT {result:#x})");
+        }
+        Ok(())
+    }
+}
+
+struct ConPtyChild {
+    process: Arc<OwnedHandle>,
+    pid: u32,
+    _console: Arc<PseudoConsole>,
+}
+
+// Process handles may be used from any thread
+unsafe impl Send for OwnedHandle {}
+unsafe impl Sync for OwnedHandle {}
+unsafe impl Send for PseudoConsole {}
+unsafe impl Sync for PseudoConsole {}
+
+impl PtyChild for ConPtyChild {
+    fn id(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+
+    fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>> {
+        let process = self.process.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                unsafe { WaitForSingleObject(process.0, INFINITE) };
+                let mut code = 0;
+                if unsafe { GetExitCodeProcess(process.0, &mut code) } == 0 {
+                    return Err(io::Error::last_os_error())
+                        .with_context(|| "Unable to get the exit code");
+                }
+                Ok(ExitStatus::from_raw(code))
+            })
+            .await?
+        })
+    }
+
+    /** Windows has no hangup signal, so this is the same as `kill` */
+    fn hangup(&mut self) -> Result<()> {
+        self.kill()
+    }
+
+    fn kill(&mut self) -> Result<()> {
+        if unsafe { TerminateProcess(self.process.0, 1) } == 0 {
+            return Err(io::Error::last_os_error()).with_context(|| "Unable to terminate");
+        }
+        Ok(())
+    }
+}
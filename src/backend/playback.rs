@@ -0,0 +1,218 @@
+use super::{CommandSpec, PtyBackend, PtyChild, PtyWriter, SpawnedPty, WindowSize};
+use crate::cast::Header;
+use anyhow::{bail, Context, Result};
+use iced::futures::future::BoxFuture;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::ExitStatus;
+use std::task::{Context as TaskContext, Poll};
+use tokio::io::{AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::select;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+use tracing::{debug, info};
+
+const MIN_SPEED: f64 = 1.0 / 16.0;
+const MAX_SPEED: f64 = 16.0;
+
+/**
+ * Plays an asciinema cast in place of a shell. Typed keys control playback:
+ * space pauses and resumes, `+` and `-` double and halve the speed, and `0`
+ * goes back to the speed playback started at.
+ */
+pub struct PlaybackBackend {
+    pub path: PathBuf,
+    pub speed: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Control {
+    speed: f64,
+    paused: bool,
+}
+
+impl PtyBackend for PlaybackBackend {
+    fn spawn(&self, _command: &CommandSpec, _size: WindowSize) -> Result<SpawnedPty> {
+        let text = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Unable to read {:?}", self.path))?;
+        let output = parse(&text).with_context(|| format!("Invalid cast {:?}", self.path))?;
+        let (reader, sink) = tokio::io::duplex(64 * 1024);
+        let control = Control {
+            speed: self.speed.clamp(MIN_SPEED, MAX_SPEED),
+            paused: false,
+        };
+        let (sender, receiver) = watch::channel(control);
+        Ok(SpawnedPty {
+            reader: Box::new(reader),
+            writer: Box::new(PlaybackWriter {
+                control: sender,
+                initial_speed: control.speed,
+            }),
+            child: Box::new(PlaybackChild {
+                task: tokio::spawn(play(output, sink, receiver)),
+            }),
+        })
+    }
+}
+
+/** The output events of a cast, with their times; resizes and the rest are skipped */
+fn parse(text: &str) -> Result<Vec<(f64, String)>> {
+    let mut lines = text.lines().enumerate();
+    let (_, header) = lines.next().context("The file is empty")?;
+    let header: Header = serde_json::from_str(header).context("Invalid header")?;
+    if header.version != 2 {
+        bail!(
+            "Only version 2 casts are supported, not version {}",
+            header.version
+        );
+    }
+    let mut output = vec![];
+    for (i, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+        let (time, code, data): (f64, String, String) = serde_json::from_str(line)
+            .with_context(|| format!("Invalid event on line {}", i + 1))?;
+        if code == "o" {
+            output.push((time, data));
+        }
+    }
+    Ok(output)
+}
+
+/** Write each event once its time comes, as scaled by the speed, holding still while paused */
+async fn play(
+    output: Vec<(f64, String)>,
+    mut sink: DuplexStream,
+    mut control: watch::Receiver<Control>,
+) {
+    // How far into the recording playback is, in its own seconds
+    let mut position = 0.0;
+    for (time, data) in output {
+        while position < time {
+            let Control { speed, paused } = *control.borrow_and_update();
+            if paused {
+                if control.changed().await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            let started = Instant::now();
+            select! {
+                _ = sleep(Duration::from_secs_f64((time - position) / speed)) => position = time,
+                changed = control.changed() => {
+                    position += started.elapsed().as_secs_f64() * speed;
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        if sink.write_all(data.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+    info!("Playback finished");
+}
+
+/** Takes keys as playback controls rather than passing them on */
+struct PlaybackWriter {
+    control: watch::Sender<Control>,
+    initial_speed: f64,
+}
+
+impl AsyncWrite for PlaybackWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        for byte in buf {
+            self.control.send_modify(|control| match byte {
+                b' ' => control.paused = !control.paused,
+                b'+' | b'=' => control.speed = (control.speed * 2.0).min(MAX_SPEED),
+                b'-' => control.speed = (control.speed / 2.0).max(MIN_SPEED),
+                b'0' => control.speed = self.initial_speed,
+                _ => {}
+            });
+        }
+        debug!("Playback {:?}", *self.control.borrow());
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl PtyWriter for PlaybackWriter {
+    fn resize(&self, size: WindowSize) -> Result<()> {
+        debug!("Ignoring resize to {size:?} during playback");
+        Ok(())
+    }
+}
+
+/** Playback stands in for the process, which "exits" once the cast is over */
+struct PlaybackChild {
+    task: JoinHandle<()>,
+}
+
+impl PtyChild for PlaybackChild {
+    fn id(&self) -> Option<u32> {
+        None
+    }
+
+    fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>> {
+        Box::pin(async move {
+            // An aborted task ends playback all the same
+            let _ = (&mut self.task).await;
+            Ok(success())
+        })
+    }
+
+    fn hangup(&mut self) -> Result<()> {
+        self.task.abort();
+        Ok(())
+    }
+
+    fn kill(&mut self) -> Result<()> {
+        self.hangup()
+    }
+}
+
+#[cfg(unix)]
+fn success() -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(0)
+}
+
+#[cfg(windows)]
+fn success() -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let cast = r#"{"version": 2, "width": 80, "height": 24}
+[0.5, "o", "hello"]
+[1.0, "r", "100x30"]
+
+[1.25, "o", "\r\n"]
+"#;
+        assert_eq!(
+            parse(cast).unwrap(),
+            [(0.5, "hello".to_string()), (1.25, "\r\n".to_string())]
+        );
+        assert!(parse(r#"{"version": 1, "width": 80, "height": 24}"#).is_err());
+        assert!(parse("").is_err());
+        let err =
+            parse("{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.5, \"o\"]").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid event on line 2");
+    }
+}
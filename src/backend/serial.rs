@@ -0,0 +1,193 @@
+use super::{CommandSpec, PtyBackend, PtyChild, PtyWriter, SpawnedPty, WindowSize};
+use anyhow::{bail, Context, Result};
+use iced::futures::future::BoxFuture;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::sys::termios::{self, BaudRate, ControlFlags, SetArg, SpecialCharacterIndices};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, BorrowedFd};
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::task::{ready, Context as TaskContext, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::Notify;
+use tracing::debug;
+
+/** A serial device, put into raw mode at the given baud rate */
+pub struct SerialBackend {
+    pub device: PathBuf,
+    pub baud_rate: u32,
+}
+
+/** An already open file descriptor, used as is */
+pub struct FdBackend {
+    pub fd: i32,
+}
+
+fn baud_rate(rate: u32) -> Result<BaudRate> {
+    Ok(match rate {
+        1200 => BaudRate::B1200,
+        2400 => BaudRate::B2400,
+        4800 => BaudRate::B4800,
+        9600 => BaudRate::B9600,
+        19200 => BaudRate::B19200,
+        38400 => BaudRate::B38400,
+        57600 => BaudRate::B57600,
+        115200 => BaudRate::B115200,
+        230400 => BaudRate::B230400,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        460800 => BaudRate::B460800,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        921600 => BaudRate::B921600,
+        rate => bail!("Unsupported baud rate {rate}"),
+    })
+}
+
+impl PtyBackend for SerialBackend {
+    fn spawn(&self, _command: &CommandSpec, _size: WindowSize) -> Result<SpawnedPty> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags((OFlag::O_NOCTTY | OFlag::O_NONBLOCK).bits())
+            .open(&self.device)
+            .with_context(|| format!("Unable to open {:?}", self.device))?;
+
+        let mut attributes = termios::tcgetattr(file.as_raw_fd())?;
+        termios::cfmakeraw(&mut attributes);
+        termios::cfsetspeed(&mut attributes, baud_rate(self.baud_rate)?)?;
+        attributes.control_flags |= ControlFlags::CLOCAL | ControlFlags::CREAD;
+        attributes.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        attributes.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        termios::tcsetattr(file.as_raw_fd(), SetArg::TCSANOW, &attributes)
+            .with_context(|| format!("Unable to configure {:?}", self.device))?;
+
+        attach(file)
+    }
+}
+
+impl PtyBackend for FdBackend {
+    fn spawn(&self, _command: &CommandSpec, _size: WindowSize) -> Result<SpawnedPty> {
+        let flags = fcntl(self.fd, FcntlArg::F_GETFL)
+            .with_context(|| format!("File descriptor {} is not open", self.fd))?;
+        let flags = OFlag::from_bits_truncate(flags) | OFlag::O_NONBLOCK;
+        fcntl(self.fd, FcntlArg::F_SETFL(flags))?;
+        // Safety: the fd was handed to firn for exclusive use, and fcntl found it open. Each
+        // spawn, a restart included, gets a duplicate to close, so the original stays open.
+        let fd = unsafe { BorrowedFd::borrow_raw(self.fd) }.try_clone_to_owned()?;
+        attach(File::from(fd))
+    }
+}
+
+/** Wrap a non-blocking file in the reader, writer and stand-in child that `make_pty` expects */
+fn attach(file: File) -> Result<SpawnedPty> {
+    let file = Arc::new(AsyncFd::new(file)?);
+    let closed = Arc::new(Notify::new());
+    Ok(SpawnedPty {
+        reader: Box::new(FdReader {
+            file: file.clone(),
+            closed: closed.clone(),
+        }),
+        writer: Box::new(FdWriter { file }),
+        child: Box::new(FdChild { closed }),
+    })
+}
+
+struct FdReader {
+    file: Arc<AsyncFd<File>>,
+    /** Notified once the other end hangs up */
+    closed: Arc<Notify>,
+}
+
+impl AsyncRead for FdReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.file.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|file| file.get_ref().read(unfilled)) {
+                Ok(Ok(nbytes)) => {
+                    if nbytes == 0 {
+                        self.closed.notify_one();
+                    }
+                    buf.advance(nbytes);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) => {
+                    self.closed.notify_one();
+                    return Poll::Ready(Err(err));
+                }
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+struct FdWriter {
+    file: Arc<AsyncFd<File>>,
+}
+
+impl AsyncWrite for FdWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.file.poll_write_ready(cx))?;
+            match guard.try_io(|file| file.get_ref().write(buf)) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl PtyWriter for FdWriter {
+    fn resize(&self, size: WindowSize) -> Result<()> {
+        debug!("Ignoring resize to {size:?}, the other end has no notion of a window");
+        Ok(())
+    }
+}
+
+/** There is no process on the other end, so "exiting" means the device went away */
+struct FdChild {
+    closed: Arc<Notify>,
+}
+
+impl PtyChild for FdChild {
+    fn id(&self) -> Option<u32> {
+        None
+    }
+
+    fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>> {
+        Box::pin(async move {
+            self.closed.notified().await;
+            Ok(ExitStatus::from_raw(0))
+        })
+    }
+
+    fn hangup(&mut self) -> Result<()> {
+        self.closed.notify_one();
+        Ok(())
+    }
+
+    fn kill(&mut self) -> Result<()> {
+        self.hangup()
+    }
+}
@@ -0,0 +1,66 @@
+use super::{CommandSpec, PtyBackend, PtyChild, PtyWriter, SpawnedPty, WindowSize};
+use anyhow::{Context, Result};
+use iced::futures::future::BoxFuture;
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+use std::process::ExitStatus;
+
+/** Unix ptys via `pty-process` */
+pub struct UnixPty;
+
+impl From<WindowSize> for pty_process::Size {
+    fn from(size: WindowSize) -> Self {
+        Self::new(size.rows, size.cols)
+    }
+}
+
+impl PtyBackend for UnixPty {
+    fn spawn(&self, command: &CommandSpec, size: WindowSize) -> Result<SpawnedPty> {
+        let pty = pty_process::Pty::new().with_context(|| "Unable to open a pty")?;
+        pty.resize(size.into())?;
+
+        let mut cmd = pty_process::Command::new(&command.program);
+        cmd.args(&command.args).envs(command.env.iter().cloned());
+        if let Some(working_directory) = &command.working_directory {
+            cmd.current_dir(working_directory);
+        }
+        let child = cmd
+            .spawn(&pty.pts()?)
+            .with_context(|| format!("Unable to start {:?}", command.program))?;
+
+        let (reader, writer) = pty.into_split();
+        Ok(SpawnedPty {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            child: Box::new(child),
+        })
+    }
+}
+
+impl PtyWriter for pty_process::OwnedWritePty {
+    fn resize(&self, size: WindowSize) -> Result<()> {
+        Ok(pty_process::OwnedWritePty::resize(self, size.into())?)
+    }
+}
+
+impl PtyChild for tokio::process::Child {
+    fn id(&self) -> Option<u32> {
+        tokio::process::Child::id(self)
+    }
+
+    fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>> {
+        Box::pin(async move { Ok(tokio::process::Child::wait(self).await?) })
+    }
+
+    /** The shell leads its own session, so this reaches its whole process group */
+    fn hangup(&mut self) -> Result<()> {
+        if let Some(pid) = tokio::process::Child::id(self) {
+            signal::killpg(Pid::from_raw(pid as i32), Signal::SIGHUP)?;
+        }
+        Ok(())
+    }
+
+    fn kill(&mut self) -> Result<()> {
+        Ok(self.start_kill()?)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::backend::WindowSize;
+
+/** Height of the bar saying what is wrong with the config, in rows of the grid it covers */
+pub const CONFIG_ERROR_ROWS: usize = 2;
+/** Height of the bar asking whether to close, in rows of the grid it covers */
+pub const CONFIRM_BAR_ROWS: usize = 2;
+/** Height of the bar asking whether to paste, in rows of the grid it covers */
+pub const PASTE_BAR_ROWS: usize = 6;
+/** Height of the bar offering to reopen the last session, in rows of the grid it covers */
+pub const RESTORE_BAR_ROWS: usize = 2;
+/** Height of the tab bar, in rows of the grid it covers */
+pub const TAB_BAR_ROWS: usize = 2;
+/** Height of the search bar, in rows of the grid it covers */
+pub const SEARCH_BAR_ROWS: usize = 2;
+/** Height of the mark bar, in rows of the grid it covers */
+pub const MARK_BAR_ROWS: usize = 2;
+/** Height of the status bar, in rows of the grid it covers */
+pub const STATUS_BAR_ROWS: usize = 1;
+
+/**
+ * Which bars show around the grid. Every one of them takes rows away from
+ * it, and so from the shells, which are sized to the rows left in view.
+ */
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Bars {
+    pub config_error: bool,
+    pub confirm_close: bool,
+    pub paste: bool,
+    pub restore: bool,
+    pub tabs: bool,
+    pub search: bool,
+    pub mark: bool,
+    /** The only bar below the grid */
+    pub status: bool,
+}
+
+impl Bars {
+    /** How many rows the bars above the grid take up, in the order they are laid out */
+    pub fn above(&self) -> usize {
+        [
+            (self.config_error, CONFIG_ERROR_ROWS),
+            (self.confirm_close, CONFIRM_BAR_ROWS),
+            (self.paste, PASTE_BAR_ROWS),
+            (self.restore, RESTORE_BAR_ROWS),
+            (self.tabs, TAB_BAR_ROWS),
+            (self.search, SEARCH_BAR_ROWS),
+            (self.mark, MARK_BAR_ROWS),
+        ]
+        .into_iter()
+        .filter_map(|(shown, rows)| shown.then_some(rows))
+        .sum()
+    }
+
+    /** How many of the window's `rows` are left for the grid, at least one */
+    pub fn grid_rows(&self, rows: usize) -> usize {
+        let below = if self.status { STATUS_BAR_ROWS } else { 0 };
+        rows.saturating_sub(self.above() + below).max(1)
+    }
+
+    /** The size the shells get when the whole window fits `grid` */
+    pub fn pty_size(&self, grid: WindowSize) -> WindowSize {
+        WindowSize {
+            rows: self.grid_rows(grid.rows.into()) as u16,
+            cols: grid.cols,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_rows() {
+        let grid = WindowSize { rows: 24, cols: 80 };
+        let mut bars = Bars::default();
+        assert_eq!(bars.pty_size(grid), grid);
+        bars.status = true;
+        assert_eq!(bars.pty_size(grid), WindowSize { rows: 23, cols: 80 });
+        assert_eq!(bars.above(), 0);
+        bars.tabs = true;
+        bars.search = true;
+        assert_eq!(bars.grid_rows(24), 19);
+        assert_eq!(bars.above(), 4);
+        // Exported images start below every bar above the grid, the config error's too
+        bars.config_error = true;
+        assert_eq!(bars.above(), 6);
+        bars.paste = true;
+        assert_eq!(bars.grid_rows(8), 1);
+    }
+}
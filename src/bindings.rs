@@ -0,0 +1,444 @@
+use anyhow::{bail, Context, Result};
+use iced::keyboard::{KeyCode, Modifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/** What a key binding does */
+#[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+pub enum Action {
+    Copy,
+    Paste,
+    /** Paste with line breaks turned into spaces, so that a snippet runs as one command, if at all */
+    PasteOneLine,
+    /** Paste with every match of this regex replaced, `$1` and so on standing for its groups */
+    PasteReplacing {
+        pattern: String,
+        replacement: String,
+    },
+    Search,
+    NewTab,
+    CloseTab,
+    NewWindow,
+    PreviousTab,
+    NextTab,
+    Hints,
+    CopyMode,
+    /** Save the rows in view, or the selected ones, as `export_format` */
+    Export,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ScrollPageUp,
+    ScrollPageDown,
+    /** Scroll the prompt above the top of the view up to it */
+    PreviousPrompt,
+    /** Scroll the prompt below the top of the view up to it */
+    NextPrompt,
+    /** Open the bar that names a mark on the line in view or under the cursor, and lists the marks */
+    AddMark,
+    /** Scroll the mark above the top of the view up to it */
+    PreviousMark,
+    /** Scroll the mark below the top of the view up to it */
+    NextMark,
+    /** Select the output of the last command that finished */
+    SelectLastOutput,
+    /** Select the output of the last command that finished, and copy it */
+    CopyLastOutput,
+    ToggleFullscreen,
+    ToggleMaximized,
+    ToggleDecorations,
+    /** Show the emulator's internals over the grid, for debugging firn */
+    ToggleDebugOverlay,
+    /** Start or stop logging the tab's output to a file, as `session_log` says */
+    ToggleSessionLog,
+    /** Type this text into the shell */
+    SendText(String),
+    /** Write these bytes to the shell, e.g. an escape sequence for a key firn does not know */
+    SendBytes(Bytes),
+    /** Run the action a plugin registered under this name with `firn.action` */
+    Plugin(String),
+    /** Nothing, so that a default binding's key reaches the shell instead */
+    None,
+}
+
+/**
+ * Bytes written as text with escapes: `\xNN` for any byte, `\e` for ESC,
+ * `\r`, `\n`, `\t`, `\0` and `\\`
+ */
+#[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct Bytes(pub Vec<u8>);
+
+impl TryFrom<String> for Bytes {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        let mut bytes = vec![];
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                let mut utf8 = [0; 4];
+                bytes.extend_from_slice(ch.encode_utf8(&mut utf8).as_bytes());
+                continue;
+            }
+            bytes.push(match chars.next() {
+                Some('e') => 0x1b,
+                Some('r') => b'\r',
+                Some('n') => b'\n',
+                Some('t') => b'\t',
+                Some('0') => 0,
+                Some('\\') => b'\\',
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    u8::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == 2)
+                        .with_context(|| format!("Expected two hex digits after \\x in {text:?}"))?
+                }
+                Some(other) => bail!("Unknown escape \\{other} in {text:?}"),
+                None => bail!("Nothing to escape at the end of {text:?}"),
+            });
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl From<Bytes> for String {
+    fn from(Bytes(bytes): Bytes) -> Self {
+        let mut text = String::new();
+        for chunk in bytes.utf8_chunks() {
+            for ch in chunk.valid().chars() {
+                match ch {
+                    '\\' => text.push_str("\\\\"),
+                    '\x1b' => text.push_str("\\e"),
+                    ch if ch.is_control() => text += &format!("\\x{:02x}", ch as u32),
+                    ch => text.push(ch),
+                }
+            }
+            for byte in chunk.invalid() {
+                text += &format!("\\x{byte:02x}");
+            }
+        }
+        text
+    }
+}
+
+/** Bindings firn starts out with, which the config can override or unbind */
+pub fn defaults() -> Vec<(&'static str, Action)> {
+    vec![
+        ("ctrl+shift+c", Action::Copy),
+        ("ctrl+shift+v", Action::Paste),
+        ("ctrl+alt+shift+v", Action::PasteOneLine),
+        ("ctrl+shift+f", Action::Search),
+        ("ctrl+shift+t", Action::NewTab),
+        ("ctrl+shift+w", Action::CloseTab),
+        ("ctrl+shift+n", Action::NewWindow),
+        ("ctrl+pageup", Action::PreviousTab),
+        ("ctrl+pagedown", Action::NextTab),
+        ("ctrl+shift+space", Action::Hints),
+        ("ctrl+shift+x", Action::CopyMode),
+        ("ctrl+shift+s", Action::Export),
+        ("ctrl+=", Action::ZoomIn),
+        ("ctrl+shift+=", Action::ZoomIn),
+        ("ctrl+plus", Action::ZoomIn),
+        ("ctrl+numpadadd", Action::ZoomIn),
+        ("ctrl+-", Action::ZoomOut),
+        ("ctrl+numpadsubtract", Action::ZoomOut),
+        ("ctrl+0", Action::ZoomReset),
+        ("ctrl+numpad0", Action::ZoomReset),
+        ("shift+pageup", Action::ScrollPageUp),
+        ("shift+pagedown", Action::ScrollPageDown),
+        ("ctrl+shift+up", Action::PreviousPrompt),
+        ("ctrl+shift+down", Action::NextPrompt),
+        ("ctrl+shift+o", Action::SelectLastOutput),
+        ("ctrl+shift+g", Action::CopyLastOutput),
+        ("ctrl+shift+m", Action::AddMark),
+        ("ctrl+shift+[", Action::PreviousMark),
+        ("ctrl+shift+]", Action::NextMark),
+        ("f11", Action::ToggleFullscreen),
+        ("ctrl+f11", Action::ToggleMaximized),
+        ("shift+f11", Action::ToggleDecorations),
+        ("ctrl+shift+f12", Action::ToggleDebugOverlay),
+        ("ctrl+shift+l", Action::ToggleSessionLog),
+    ]
+}
+
+/** A key together with the modifiers held with it, written like `ctrl+shift+c` */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyCombo {
+    pub key_code: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+impl TryFrom<&str> for KeyCombo {
+    type Error = anyhow::Error;
+
+    fn try_from(text: &str) -> Result<Self> {
+        let lowercase = text.to_lowercase();
+        // The key itself may be `+`, as in `ctrl++`
+        let (modifier_names, key) = match lowercase.strip_suffix("++") {
+            Some(rest) => (rest, "+"),
+            None => lowercase.rsplit_once('+').unwrap_or(("", &lowercase)),
+        };
+        let mut modifiers = Modifiers::empty();
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                "super" | "cmd" | "logo" => Modifiers::LOGO,
+                _ => bail!(
+                    "Unknown modifier {name:?} in {text:?}, expected ctrl, shift, alt or super"
+                ),
+            };
+        }
+        let key_code = key_code(key).with_context(|| format!("Unknown key {key:?} in {text:?}"))?;
+        Ok(Self {
+            key_code,
+            modifiers,
+        })
+    }
+}
+
+fn key_code(name: &str) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::A,
+        KeyCode::B,
+        KeyCode::C,
+        KeyCode::D,
+        KeyCode::E,
+        KeyCode::F,
+        KeyCode::G,
+        KeyCode::H,
+        KeyCode::I,
+        KeyCode::J,
+        KeyCode::K,
+        KeyCode::L,
+        KeyCode::M,
+        KeyCode::N,
+        KeyCode::O,
+        KeyCode::P,
+        KeyCode::Q,
+        KeyCode::R,
+        KeyCode::S,
+        KeyCode::T,
+        KeyCode::U,
+        KeyCode::V,
+        KeyCode::W,
+        KeyCode::X,
+        KeyCode::Y,
+        KeyCode::Z,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Key0,
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+        KeyCode::Key7,
+        KeyCode::Key8,
+        KeyCode::Key9,
+    ];
+    const NUMPAD: [KeyCode; 10] = [
+        KeyCode::Numpad0,
+        KeyCode::Numpad1,
+        KeyCode::Numpad2,
+        KeyCode::Numpad3,
+        KeyCode::Numpad4,
+        KeyCode::Numpad5,
+        KeyCode::Numpad6,
+        KeyCode::Numpad7,
+        KeyCode::Numpad8,
+        KeyCode::Numpad9,
+    ];
+    const FUNCTION: [KeyCode; 12] = [
+        KeyCode::F1,
+        KeyCode::F2,
+        KeyCode::F3,
+        KeyCode::F4,
+        KeyCode::F5,
+        KeyCode::F6,
+        KeyCode::F7,
+        KeyCode::F8,
+        KeyCode::F9,
+        KeyCode::F10,
+        KeyCode::F11,
+        KeyCode::F12,
+    ];
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        if ch.is_ascii_lowercase() {
+            return Some(LETTERS[(ch as u8 - b'a') as usize]);
+        }
+        if let Some(digit) = ch.to_digit(10) {
+            return Some(DIGITS[digit as usize]);
+        }
+    }
+    if let Some(digit) = name
+        .strip_prefix("numpad")
+        .and_then(|n| n.parse::<usize>().ok())
+    {
+        return NUMPAD.get(digit).copied();
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION.get(n.checked_sub(1)?).copied();
+    }
+    Some(match name {
+        "space" => KeyCode::Space,
+        "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "escape" | "esc" => KeyCode::Escape,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "numpadadd" => KeyCode::NumpadAdd,
+        "numpadsubtract" => KeyCode::NumpadSubtract,
+        "numpadmultiply" => KeyCode::NumpadMultiply,
+        "numpaddivide" => KeyCode::NumpadDivide,
+        "numpadenter" => KeyCode::NumpadEnter,
+        "=" | "equals" => KeyCode::Equals,
+        "+" | "plus" => KeyCode::Plus,
+        "-" | "minus" => KeyCode::Minus,
+        "," | "comma" => KeyCode::Comma,
+        "." | "period" => KeyCode::Period,
+        "/" | "slash" => KeyCode::Slash,
+        "\\" | "backslash" => KeyCode::Backslash,
+        ";" | "semicolon" => KeyCode::Semicolon,
+        "'" | "apostrophe" => KeyCode::Apostrophe,
+        "`" | "grave" => KeyCode::Grave,
+        "[" | "bracketleft" => KeyCode::LBracket,
+        "]" | "bracketright" => KeyCode::RBracket,
+        _ => return None,
+    })
+}
+
+/**
+ * Whether pressing the key also types a character, which has to be
+ * swallowed when a binding takes the key instead
+ */
+pub fn types_character(key_code: KeyCode) -> bool {
+    !matches!(
+        key_code,
+        KeyCode::F1
+            | KeyCode::F2
+            | KeyCode::F3
+            | KeyCode::F4
+            | KeyCode::F5
+            | KeyCode::F6
+            | KeyCode::F7
+            | KeyCode::F8
+            | KeyCode::F9
+            | KeyCode::F10
+            | KeyCode::F11
+            | KeyCode::F12
+            | KeyCode::Insert
+            | KeyCode::Home
+            | KeyCode::End
+            | KeyCode::PageUp
+            | KeyCode::PageDown
+            | KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::Left
+            | KeyCode::Right
+    )
+}
+
+/** The action bound to each key combination: the defaults, overridden by the `keybindings` config */
+pub struct Bindings(HashMap<KeyCombo, Action>);
+
+impl Bindings {
+    pub fn new(config: &BTreeMap<String, Action>) -> Result<Self> {
+        let mut bindings: HashMap<_, _> = defaults()
+            .into_iter()
+            .map(|(combo, action)| (KeyCombo::try_from(combo).unwrap(), action))
+            .collect();
+        // Written differently, e.g. `shift+ctrl+c` and `ctrl+shift+c`, two keys may still be one
+        let mut seen: HashMap<KeyCombo, &str> = HashMap::new();
+        for (text, action) in config {
+            let combo = KeyCombo::try_from(text.as_str())?;
+            if let Some(other) = seen.insert(combo, text) {
+                bail!("{other:?} and {text:?} are the same keys");
+            }
+            match action {
+                Action::None => bindings.remove(&combo),
+                action => bindings.insert(combo, action.clone()),
+            };
+        }
+        Ok(Self(bindings))
+    }
+
+    pub fn get(&self, key_code: KeyCode, modifiers: Modifiers) -> Option<&Action> {
+        self.0.get(&KeyCombo {
+            key_code,
+            modifiers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+
+    #[test]
+    fn test_key_combo() {
+        let combo = KeyCombo::try_from("Ctrl+Shift+C").unwrap();
+        assert_eq!(combo.key_code, KeyCode::C);
+        assert_eq!(combo.modifiers, Modifiers::CTRL | Modifiers::SHIFT);
+        assert_eq!(
+            KeyCombo::try_from("ctrl++").unwrap().key_code,
+            KeyCode::Plus
+        );
+        assert_eq!(
+            KeyCombo::try_from("f11").unwrap().modifiers,
+            Modifiers::empty()
+        );
+        assert_matches!(KeyCombo::try_from("hyper+c"), Err(_));
+        assert_matches!(KeyCombo::try_from("ctrl+f13"), Err(_));
+        assert_matches!(KeyCombo::try_from("ctrl+"), Err(_));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = |text: &str| Bytes::try_from(text.to_string()).map(|bytes| bytes.0);
+        assert_eq!(bytes(r"\e[13;5u").unwrap(), b"\x1b[13;5u");
+        assert_eq!(bytes(r"\x00\xff\\é").unwrap(), b"\x00\xff\\\xc3\xa9");
+        assert_matches!(bytes(r"\x1"), Err(_));
+        assert_matches!(bytes(r"\q"), Err(_));
+        assert_matches!(bytes("\\"), Err(_));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let config: BTreeMap<String, Action> = serde_json::from_str(
+            r#"{"ctrl+shift+c": "None", "alt+c": "Copy", "ctrl+shift+enter": {"SendText": "\u001b[13;5u"}}"#,
+        )
+        .unwrap();
+        let bindings = Bindings::new(&config).unwrap();
+        let ctrl_shift = Modifiers::CTRL | Modifiers::SHIFT;
+        assert_eq!(bindings.get(KeyCode::C, ctrl_shift), None);
+        assert_eq!(
+            bindings.get(KeyCode::C, Modifiers::ALT),
+            Some(&Action::Copy)
+        );
+        assert_eq!(bindings.get(KeyCode::V, ctrl_shift), Some(&Action::Paste));
+        assert_eq!(
+            bindings.get(KeyCode::Enter, ctrl_shift),
+            Some(&Action::SendText("\x1b[13;5u".into()))
+        );
+
+        let conflict: BTreeMap<String, Action> =
+            serde_json::from_str(r#"{"ctrl+shift+c": "Copy", "shift+ctrl+c": "Paste"}"#).unwrap();
+        assert!(Bindings::new(&conflict).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::backend::WindowSize;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/** The first line of an asciinema v2 cast file */
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    /** When the recording started, in seconds since the Unix epoch */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/**
+ * Writes a session as an asciinema v2 cast: a header line, then a JSON
+ * `[seconds, code, data]` line for each event, `o` for output and `r` for a
+ * resize to `<cols>x<rows>`
+ */
+pub struct CastWriter {
+    writer: BufWriter<File>,
+    start: Instant,
+    /** The start of a UTF-8 sequence cut off at the end of the last read */
+    incomplete: Vec<u8>,
+}
+
+impl CastWriter {
+    pub fn create(path: &Path, size: WindowSize, env: BTreeMap<String, String>) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = Header {
+            version: 2,
+            width: size.cols,
+            height: size.rows,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|time| time.as_secs()),
+            env,
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+            incomplete: vec![],
+        })
+    }
+
+    pub fn output(&mut self, data: &[u8]) -> Result<()> {
+        self.incomplete.extend_from_slice(data);
+        let text = take_utf8(&mut self.incomplete);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &text)
+    }
+
+    pub fn resize(&mut self, size: WindowSize) -> Result<()> {
+        self.event("r", &format!("{}x{}", size.cols, size.rows))
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        serde_json::to_writer(&mut self.writer, &(time, code, data))?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/**
+ * Decode as much of `bytes` as is complete, leaving a UTF-8 sequence cut
+ * off at the end for next time. Invalid bytes become U+FFFD, since casts
+ * can only hold text.
+ */
+pub fn take_utf8(bytes: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => bytes.len(),
+    };
+    let rest = bytes.split_off(complete);
+    let text = String::from_utf8_lossy(bytes).into_owned();
+    *bytes = rest;
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_utf8() {
+        let mut bytes = b"ab\xd0".to_vec();
+        assert_eq!(take_utf8(&mut bytes), "ab");
+        assert_eq!(bytes, b"\xd0");
+        bytes.extend_from_slice(b"\xa3!");
+        assert_eq!(take_utf8(&mut bytes), "У!");
+        assert!(bytes.is_empty());
+
+        let mut bytes = b"\xff\xd0x".to_vec();
+        assert_eq!(take_utf8(&mut bytes), "\u{FFFD}\u{FFFD}x");
+    }
+
+    #[test]
+    fn test_cast_writer() {
+        let path = std::env::temp_dir().join(format!("firn-test-cast-{}.cast", std::process::id()));
+        let size = WindowSize { rows: 24, cols: 80 };
+        let mut cast = CastWriter::create(&path, size, BTreeMap::new()).unwrap();
+        cast.output(b"hi\xd0").unwrap();
+        cast.resize(WindowSize {
+            rows: 30,
+            cols: 100,
+        })
+        .unwrap();
+        cast.output(b"\xa3").unwrap();
+        drop(cast);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        let header: Header = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!((header.version, header.width, header.height), (2, 80, 24));
+        let events: Vec<(f64, String, String)> = lines[1..]
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let events: Vec<(&str, &str)> = events
+            .iter()
+            .map(|(_, code, data)| (code.as_str(), data.as_str()))
+            .collect();
+        assert_eq!(events, [("o", "hi"), ("r", "100x30"), ("o", "У")]);
+    }
+}
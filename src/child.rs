@@ -1,37 +1,271 @@
+use crate::backend::{self, CommandSpec, ControlKey, WindowSize};
+use crate::cast::CastWriter;
 use crate::config::Config;
+use crate::recorder::Recorder;
+use crate::terminfo;
 use anyhow::Context;
 use anyhow::Result;
 use iced::futures::channel::mpsc::{Receiver, Sender};
 use iced::futures::{SinkExt, StreamExt};
 use iced::{futures::channel::mpsc, subscription, Subscription};
-use log::{debug, error, info};
-use pty_process::Size;
+use std::collections::{BTreeMap, VecDeque};
 use std::future::pending;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::time;
+use tokio::time::{timeout, timeout_at, Duration, Instant};
 use tokio::{join, select};
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, Instrument};
 
-pub fn subscribe_to_pty(config: Config) -> Subscription<OutputEvent> {
+const PASTE_CHUNK_SIZE: usize = 1024;
+
+/** How often input held back by a full channel is offered to the shell again */
+pub const RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+/** A shell exiting sooner than this after starting is not restarted, as it would likely only exit again */
+pub const MIN_LIFETIME: Duration = Duration::from_secs(1);
+
+/** Identifies one of the shells hosted by a `SessionManager` */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SessionId(usize);
+
+/** An `OutputEvent` from a particular session */
+#[derive(Debug, Clone)]
+pub struct SessionEvent {
+    pub id: SessionId,
+    pub event: OutputEvent,
+}
+
+struct Session {
+    config: Config,
+    size: WindowSize,
+    /** Bumped to spawn a fresh shell for the same session */
+    generation: usize,
+    sender: Option<Sender<InputEvent>>,
+    pid: Option<u32>,
+    /** When the current shell was spawned, kept after it exits until the session is restarted */
+    spawned_at: Option<Instant>,
+    /** Input the channel had no room for yet, in the order it was sent */
+    backlog: VecDeque<InputEvent>,
+}
+
+impl Session {
+    /** Send as much of the backlog as the channel has room for */
+    fn flush(&mut self) {
+        let Some(sender) = self.sender.as_mut() else {
+            self.backlog.clear();
+            return;
+        };
+        while let Some(event) = self.backlog.pop_front() {
+            match sender.try_send(event) {
+                Ok(()) => {}
+                Err(err) if err.is_full() => {
+                    self.backlog.push_front(err.into_inner());
+                    return;
+                }
+                Err(err) => {
+                    debug!("Dropping input for a shell that is gone: {err}");
+                    self.backlog.clear();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Hosts any number of shells at once, each with its own pty, input sender,
+ * size and lifecycle events
+ */
+#[derive(Default)]
+pub struct SessionManager {
+    sessions: BTreeMap<SessionId, Session>,
+    next_id: usize,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Register a new session; its shell is spawned once `subscription` is polled */
+    pub fn spawn(&mut self, config: Config, size: WindowSize) -> SessionId {
+        let id = SessionId(self.next_id);
+        self.next_id += 1;
+        self.sessions.insert(
+            id,
+            Session {
+                config,
+                size,
+                generation: 0,
+                sender: None,
+                pid: None,
+                spawned_at: None,
+                backlog: VecDeque::new(),
+            },
+        );
+        id
+    }
+
+    /** Drop a session, which also drops its pty */
+    pub fn remove(&mut self, id: SessionId) {
+        self.sessions.remove(&id);
+    }
+
+    /** Start a fresh shell in an existing session */
+    pub fn restart(&mut self, id: SessionId) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.generation += 1;
+            session.sender = None;
+            session.pid = None;
+            session.spawned_at = None;
+            session.backlog.clear();
+        }
+    }
+
+    /** Whether a session's shell exited, or failed to start, within `MIN_LIFETIME` */
+    pub fn exited_early(&self, id: SessionId) -> bool {
+        self.sessions.get(&id).is_some_and(|session| {
+            session
+                .spawned_at
+                .is_none_or(|spawned_at| spawned_at.elapsed() < MIN_LIFETIME)
+        })
+    }
+
+    /** Set an environment variable for every shell spawned from now on */
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        for session in self.sessions.values_mut() {
+            session.config.env.insert(key.into(), value.into());
+        }
+    }
+
+    /**
+     * Ask every running shell to exit, killing it if it has not done so within
+     * `kill_timeout_ms`. Returns how many were running.
+     */
+    pub fn hangup(&mut self) -> usize {
+        let mut running = 0;
+        for session in self.sessions.values_mut() {
+            if let Some(sender) = session.sender.as_mut() {
+                running += 1;
+                if let Err(err) = sender.try_send(InputEvent::Hangup) {
+                    error!("Unable to hang up: {err}");
+                }
+            }
+        }
+        running
+    }
+
+    pub fn any_running(&self) -> bool {
+        self.sessions
+            .values()
+            .any(|session| session.sender.is_some())
+    }
+
+    pub fn pid(&self, id: SessionId) -> Option<u32> {
+        self.sessions.get(&id)?.pid
+    }
+
+    /** Keep track of a session's lifecycle; should be given every `SessionEvent` */
+    pub fn handle_event(&mut self, event: &SessionEvent) {
+        let Some(session) = self.sessions.get_mut(&event.id) else {
+            return;
+        };
+        match &event.event {
+            OutputEvent::Connected(sender) => session.sender = Some(sender.clone()),
+            OutputEvent::Spawned(pid) => {
+                session.pid = Some(*pid);
+                session.spawned_at = Some(Instant::now());
+            }
+            OutputEvent::Disconnected(_) | OutputEvent::Failed(_) => {
+                session.sender = None;
+                session.pid = None;
+                session.backlog.clear();
+            }
+            OutputEvent::Stdout(_) => {}
+        }
+    }
+
+    /**
+     * Send input to a session's shell. Whatever the channel has no room for,
+     * e.g. while the shell is stopped with Ctrl+S or busy with a large paste,
+     * is held back for `retry` rather than lost or sent out of order.
+     */
+    pub fn send(&mut self, id: SessionId, event: InputEvent) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.backlog.push_back(event);
+            session.flush();
+        }
+    }
+
+    /** Whether any input is held back, which `retry` should be called for every `RETRY_INTERVAL` */
+    pub fn is_backlogged(&self) -> bool {
+        self.sessions
+            .values()
+            .any(|session| !session.backlog.is_empty())
+    }
+
+    /** Offer the input held back to the shells again */
+    pub fn retry(&mut self) {
+        for session in self.sessions.values_mut() {
+            session.flush();
+        }
+    }
+
+    pub fn resize(&mut self, id: SessionId, size: WindowSize) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.size = size;
+        }
+        self.send(id, InputEvent::Resize(size))
+    }
+
+    pub fn subscription(&self) -> Subscription<SessionEvent> {
+        Subscription::batch(self.sessions.iter().map(|(id, session)| {
+            subscribe_to_pty(
+                *id,
+                session.config.clone(),
+                session.size,
+                session.generation,
+            )
+        }))
+    }
+}
+
+fn subscribe_to_pty(
+    id: SessionId,
+    config: Config,
+    size: WindowSize,
+    generation: usize,
+) -> Subscription<SessionEvent> {
     struct Connect;
 
     subscription::channel(
-        std::any::TypeId::of::<Connect>(),
+        (std::any::TypeId::of::<Connect>(), id, generation),
         config.channel_buf_size,
-        async move |mut send_output: Sender<OutputEvent>| {
+        async move |mut send_output: Sender<SessionEvent>| {
             let config = config.clone();
             let (send_input, recv_input) = mpsc::channel(config.channel_buf_size);
             send_output
-                .send(OutputEvent::Connected(send_input))
+                .send(SessionEvent {
+                    id,
+                    event: OutputEvent::Connected(send_input),
+                })
                 .await
                 .unwrap();
 
-            make_pty(config, send_output.clone(), recv_input)
+            let event = match make_pty(id, config, size, send_output.clone(), recv_input)
+                .instrument(info_span!("session", ?id))
                 .await
                 .with_context(|| "make_pty")
-                .unwrap();
-
-            send_output.send(OutputEvent::Disconnected).await.unwrap();
+            {
+                Ok(status) => OutputEvent::Disconnected(status),
+                Err(err) => {
+                    error!("{err:#}");
+                    OutputEvent::Failed(format!("{err:#}"))
+                }
+            };
+            send_output.send(SessionEvent { id, event }).await.unwrap();
 
             pending::<()>().await;
             unreachable!();
@@ -39,20 +273,74 @@ pub fn subscribe_to_pty(config: Config) -> Subscription<OutputEvent> {
     )
 }
 
+/** The environment a cast notes it was recorded in */
+pub fn cast_env(config: &Config) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("SHELL".into(), config.shell.clone()),
+        (
+            "TERM".into(),
+            config
+                .env
+                .get("TERM")
+                .map_or(terminfo::DEFAULT_TERM, String::as_str)
+                .into(),
+        ),
+    ])
+}
+
+/** The configured shell, with the environment firn sets up for it */
+pub fn command(config: &Config) -> CommandSpec {
+    CommandSpec {
+        program: config.shell.clone(),
+        args: config.shell_args.clone(),
+        env: [
+            ("TERM", terminfo::DEFAULT_TERM),
+            ("COLORTERM", "truecolor"),
+            ("TERM_PROGRAM", "firn"),
+            ("TERM_PROGRAM_VERSION", env!("CARGO_PKG_VERSION")),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .chain(config.env.clone())
+        .collect(),
+        working_directory: config.working_directory.clone(),
+    }
+}
+
 async fn make_pty(
+    id: SessionId,
     config: Config,
-    sender: Sender<OutputEvent>,
+    size: WindowSize,
+    sender: Sender<SessionEvent>,
     mut receiver: Receiver<InputEvent>,
-) -> Result<()> {
-    let mut pty = pty_process::Pty::new()?;
-    let mut cmd = pty_process::Command::new(config.shell)
-        .args(config.shell_args)
-        .spawn(&pty.pts()?)?;
+) -> Result<ExitStatus> {
+    let pty = backend::from_config(&config.backend)?.spawn(&command(&config), size)?;
+    let (mut pty_reader, mut pty_writer, mut child) = (pty.reader, pty.writer, pty.child);
+    if let Some(pid) = child.id() {
+        sender
+            .clone()
+            .send(SessionEvent {
+                id,
+                event: OutputEvent::Spawned(pid),
+            })
+            .await?;
+    }
+
+    let cast = match &config.cast_path {
+        Some(path) => Some(Arc::new(Mutex::new(CastWriter::create(
+            path,
+            size,
+            cast_env(&config),
+        )?))),
+        None => None,
+    };
 
-    let (mut pty_reader, mut pty_writer) = pty.split();
     let cancellation_token = CancellationToken::new();
+    let hangup_token = CancellationToken::new();
 
     let cloned_token = cancellation_token.clone();
+    let cloned_hangup_token = hangup_token.clone();
+    let cloned_cast = cast.clone();
     let mut write_to_pty = async move || -> Result<()> {
         loop {
             select! {
@@ -63,10 +351,28 @@ async fn make_pty(
                         pty_writer.write_all(&text).await?;
                         debug!("Sent to pty");
                     }
+                    Some(InputEvent::Paste(text)) => {
+                        debug!("Receive paste of {} bytes", text.len());
+                        // Small writes let the reader keep draining the pty while a big paste
+                        // is echoed back, rather than blocking on one huge write
+                        for chunk in text.chunks(PASTE_CHUNK_SIZE) {
+                            pty_writer.write_all(chunk).await?;
+                            tokio::task::yield_now().await;
+                        }
+                    }
+                    Some(InputEvent::Control(key)) => {
+                        let character = key.character();
+                        debug!("Receive {key:?} -> {character:#04x}");
+                        pty_writer.write_all(&[character]).await?;
+                    }
                     Some(InputEvent::Resize(size)) => {
                         debug!("Receive resize -> {size:?}");
                         pty_writer.resize(size)?;
+                        if let Some(cast) = &cloned_cast {
+                            cast.lock().unwrap().resize(size)?;
+                        }
                     }
+                    Some(InputEvent::Hangup) => cloned_hangup_token.cancel(),
                     None => break
                 }
             }
@@ -76,10 +382,17 @@ async fn make_pty(
         Ok(())
     };
 
+    let mut recorder = config
+        .record_path
+        .as_deref()
+        .map(Recorder::create)
+        .transpose()?;
+
     let mut cloned_sender = sender.clone();
     let cloned_token = cancellation_token.clone();
     let mut read_from_pty = async move || -> Result<()> {
         let mut readbuf = vec![0u8; config.read_buf_size];
+        let coalesce_duration = Duration::from_millis(config.coalesce_ms);
 
         loop {
             select! {
@@ -91,9 +404,36 @@ async fn make_pty(
                         }
                         Ok(nbytes) => {
                             debug!("Read {nbytes} bytes from pty");
-                            cloned_sender.send(OutputEvent::Stdout(readbuf[..nbytes].into())).await?;
-                            // HACK: throttle pty output messages to avoid overwhelming iced
-                            time::sleep(time::Duration::from_millis(10)).await;
+                            if let Some(recorder) = recorder.as_mut() {
+                                recorder.record(&readbuf[..nbytes])?;
+                            }
+                            if let Some(cast) = &cast {
+                                cast.lock().unwrap().output(&readbuf[..nbytes])?;
+                            }
+                            let mut chunk = readbuf[..nbytes].to_vec();
+                            // Coalesce whatever else arrives within the budget, so that heavy
+                            // output turns into fewer, larger messages and redraws
+                            let deadline = Instant::now() + coalesce_duration;
+                            while chunk.len() < config.max_chunk_size {
+                                match timeout_at(deadline, pty_reader.read(&mut readbuf)).await {
+                                    Ok(Ok(nbytes)) if nbytes > 0 => {
+                                        if let Some(recorder) = recorder.as_mut() {
+                                            recorder.record(&readbuf[..nbytes])?;
+                                        }
+                                        if let Some(cast) = &cast {
+                                            cast.lock().unwrap().output(&readbuf[..nbytes])?;
+                                        }
+                                        chunk.extend_from_slice(&readbuf[..nbytes]);
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            // The channel is bounded, so this waits while iced catches up. In the
+                            // meantime nothing reads the pty, which in turn blocks the child.
+                            cloned_sender.send(SessionEvent {
+                                id,
+                                event: OutputEvent::Stdout(chunk),
+                            }).await?;
                         }
                         Err(err) => {
                             error!("pty read error: {err}");
@@ -106,11 +446,26 @@ async fn make_pty(
         Ok(())
     };
 
-    let mut cleanup = async move || -> Result<()> {
-        let status = cmd.wait().await?;
+    let mut cleanup = async move || -> Result<ExitStatus> {
+        let kill_timeout = Duration::from_millis(config.kill_timeout_ms);
+        let status = select! {
+            status = child.wait() => status?,
+            _ = hangup_token.cancelled() => {
+                debug!("Hanging up");
+                child.hangup()?;
+                match timeout(kill_timeout, child.wait()).await {
+                    Ok(status) => status?,
+                    Err(_) => {
+                        info!("Shell did not exit within {kill_timeout:?} of hanging up, killing it");
+                        child.kill()?;
+                        child.wait().await?
+                    }
+                }
+            }
+        };
         info!("Shell finished with status {status}");
         cancellation_token.cancel();
-        Ok(())
+        Ok(status)
     };
 
     let result = join!(write_to_pty(), read_from_pty(), cleanup());
@@ -118,19 +473,78 @@ async fn make_pty(
         .0
         .with_context(|| "write_to_pty")
         .and(result.1.with_context(|| "read_from_pty"))
-        .and(result.2.with_context(|| "cleanup"))?;
-    Ok(())
+        .and(result.2.with_context(|| "cleanup"))
 }
 
 #[derive(Debug, Clone)]
 pub enum InputEvent {
     Stdin(Vec<u8>),
-    Resize(Size),
+    /** Pasted text, already prepared; written in chunks */
+    Paste(Vec<u8>),
+    /** A signal or EOF key, sent as its control character */
+    Control(ControlKey),
+    /** Ask the child to exit, as if its terminal had gone away */
+    Hangup,
+    Resize(WindowSize),
 }
 
 #[derive(Debug, Clone)]
 pub enum OutputEvent {
     Connected(Sender<InputEvent>),
-    Disconnected,
+    Spawned(u32),
+    Disconnected(ExitStatus),
+    /** The pty could not be set up or the shell could not be started */
+    Failed(String),
     Stdout(Vec<u8>),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_channel_holds_input_back_in_order() {
+        let mut sessions = SessionManager::new();
+        let id = sessions.spawn(Config::default(), WindowSize { rows: 24, cols: 80 });
+        let (sender, mut receiver) = mpsc::channel(0);
+        sessions.handle_event(&SessionEvent {
+            id,
+            event: OutputEvent::Connected(sender),
+        });
+        for byte in *b"abc" {
+            sessions.send(id, InputEvent::Stdin(vec![byte]));
+        }
+        assert!(sessions.is_backlogged());
+
+        let mut received = vec![];
+        while sessions.is_backlogged() || received.len() < 3 {
+            while let Ok(Some(InputEvent::Stdin(bytes))) = receiver.try_next() {
+                received.extend(bytes);
+            }
+            sessions.retry();
+        }
+        assert_eq!(received, b"abc");
+    }
+
+    #[test]
+    fn test_exited_early() {
+        let mut sessions = SessionManager::new();
+        let id = sessions.spawn(Config::default(), WindowSize { rows: 24, cols: 80 });
+        let exit = |sessions: &mut SessionManager| {
+            for event in [
+                OutputEvent::Spawned(1),
+                OutputEvent::Disconnected(ExitStatus::default()),
+            ] {
+                sessions.handle_event(&SessionEvent { id, event });
+            }
+        };
+        exit(&mut sessions);
+        assert!(sessions.exited_early(id));
+
+        sessions.restart(id);
+        exit(&mut sessions);
+        let session = sessions.sessions.get_mut(&id).unwrap();
+        session.spawned_at = Some(Instant::now() - MIN_LIFETIME);
+        assert!(!sessions.exited_early(id));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::config::Dimensions;
+use crate::ipc::Request;
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::path::PathBuf;
+
+/** A terminal emulator */
+#[derive(Parser, Default, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /** Read the configuration from this file instead of `$XDG_CONFIG_HOME/firn/config.toml` */
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /** Override a configuration option, e.g. `-o font.size=14`; values are JSON, or else strings */
+    #[arg(short = 'o', long = "option", value_name = "KEY=VALUE", value_parser = key_value)]
+    pub overrides: Vec<(String, String)>,
+    /** Start with the settings of this profile from the config */
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /** Tee every byte read from the pty into this file */
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+    /** Record the session into this file as an asciinema v2 cast */
+    #[arg(long, value_name = "PATH")]
+    pub record_cast: Option<PathBuf>,
+    /** Log every control function parsed from the shell's output, and whether it was handled */
+    #[arg(long)]
+    pub debug_events: bool,
+    /** Compile and install firn's terminfo entry, then exit */
+    #[arg(long)]
+    pub install_terminfo: bool,
+    /** Directory to start the shell in */
+    #[arg(long, value_name = "PATH")]
+    pub working_directory: Option<PathBuf>,
+    /** Window title until the shell sets one */
+    #[arg(long)]
+    pub title: Option<String>,
+    /** Application id, or WM_CLASS, for the window manager to tell firn windows apart by */
+    #[arg(long)]
+    pub class: Option<String>,
+    /** Reopen the tabs saved when the last window closed, whatever `restore_session` says */
+    #[arg(long)]
+    pub restore: bool,
+    /** Keep the window open once the shell exits */
+    #[arg(long)]
+    pub hold: bool,
+    /** Environment variables for the shell */
+    #[arg(long, value_name = "KEY=VALUE", value_parser = key_value)]
+    pub env: Vec<(String, String)>,
+    /** Run this program and its arguments instead of the configured shell */
+    #[arg(
+        short = 'e',
+        long = "command",
+        value_name = "COMMAND",
+        num_args = 1..,
+        allow_hyphen_values = true
+    )]
+    pub execute: Option<Vec<String>>,
+    /** The same as `-e`, after `--` */
+    #[arg(last = true, hide = true)]
+    program: Vec<String>,
+    /** Attach to this serial device instead of spawning a shell */
+    #[arg(long, value_name = "DEVICE")]
+    pub serial: Option<PathBuf>,
+    #[arg(long = "baud", value_name = "RATE", requires = "serial")]
+    pub baud_rate: Option<u32>,
+    /** Attach to this inherited file descriptor instead of spawning a shell */
+    #[arg(long, conflicts_with = "serial")]
+    pub fd: Option<i32>,
+    /** Run the shell without a window and print its final screen as text */
+    #[arg(long)]
+    pub headless: bool,
+    /** Size of the headless terminal [default: 80x24] */
+    #[arg(long, value_name = "COLSxROWS", requires = "headless", value_parser = dimensions)]
+    pub size: Option<Dimensions>,
+    /** Print the headless screen with its colors and styles, as ANSI escape sequences */
+    #[arg(long, requires = "headless")]
+    pub ansi: bool,
+    /** Print the headless screen every time the shell writes to it */
+    #[arg(long, requires = "headless")]
+    pub stream: bool,
+    /** Hang up on the headless shell after this many seconds */
+    #[arg(long, value_name = "SECONDS", requires = "headless")]
+    pub timeout: Option<f64>,
+    #[command(subcommand)]
+    pub command: Option<Subcommand>,
+}
+
+#[derive(clap::Subcommand, Debug, PartialEq)]
+pub enum Subcommand {
+    /** Feed a recording through the translator headlessly and print the screen */
+    Replay { path: PathBuf },
+    /**
+     * Play an asciinema cast in the window. Space pauses, `+` and `-` change
+     * the speed and `0` resets it.
+     */
+    Play {
+        path: PathBuf,
+        /** Play this many times faster than recorded */
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
+    /** Send a command to a running firn window, the one this shell runs in unless `--socket` says */
+    Msg {
+        /** The socket of the window to send to, in place of `$FIRN_SOCKET` */
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+        #[command(subcommand)]
+        request: Request,
+    },
+    /** Print the default configuration, with each setting explained */
+    DefaultConfig {
+        /** Write it to the user's config file instead, unless there is one */
+        #[arg(long)]
+        write: bool,
+    },
+}
+
+fn dimensions(text: &str) -> Result<Dimensions> {
+    Dimensions::try_from(text.to_string())
+}
+
+fn key_value(text: &str) -> Result<(String, String)> {
+    let (key, value) = text
+        .split_once('=')
+        .with_context(|| format!("Expected KEY=VALUE, got {text:?}"))?;
+    Ok((key.into(), value.into()))
+}
+
+impl Cli {
+    /** Exits with a usage message if the arguments are invalid, or after `--help` or `--version` */
+    pub fn parse() -> Self {
+        Self::resolve(<Self as Parser>::parse())
+    }
+
+    #[cfg(test)]
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self, clap::Error> {
+        let args = std::iter::once("firn".to_string()).chain(args);
+        Ok(Self::resolve(Self::try_parse_from(args)?))
+    }
+
+    fn resolve(mut self) -> Self {
+        if self.execute.is_none() && !self.program.is_empty() {
+            self.execute = Some(std::mem::take(&mut self.program));
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::parse_from(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_execute_passes_arguments_verbatim() {
+        let cli = parse(&["-e", "vim", "--record", "file.txt"]).unwrap();
+        assert_eq!(cli.execute.unwrap(), ["vim", "--record", "file.txt"]);
+        assert_eq!(cli.record, None);
+
+        let cli = parse(&["--working-directory", "/tmp", "--", "htop"]).unwrap();
+        assert_eq!(cli.execute.unwrap(), ["htop"]);
+        assert_eq!(cli.working_directory, Some("/tmp".into()));
+    }
+
+    #[test]
+    fn test_execute_requires_a_command() {
+        assert!(parse(&["-e"]).is_err());
+    }
+
+    #[test]
+    fn test_env() {
+        let cli = parse(&["--env", "A=1", "--env", "B=x=y"]).unwrap();
+        assert_eq!(
+            cli.env,
+            [("A".into(), "1".into()), ("B".into(), "x=y".into())]
+        );
+        assert!(parse(&["--env", "A"]).is_err());
+    }
+
+    #[test]
+    fn test_serial() {
+        let cli = parse(&["--serial", "/dev/ttyUSB0", "--baud", "9600"]).unwrap();
+        assert_eq!(cli.serial, Some("/dev/ttyUSB0".into()));
+        assert_eq!(cli.baud_rate, Some(9600));
+        assert!(parse(&["--serial", "/dev/ttyUSB0", "--baud", "fast"]).is_err());
+        assert!(parse(&["--baud", "9600"]).is_err());
+    }
+
+    #[test]
+    fn test_fd() {
+        assert_eq!(parse(&["--fd", "3"]).unwrap().fd, Some(3));
+        assert!(parse(&["--fd", "3", "--serial", "/dev/ttyUSB0"]).is_err());
+    }
+
+    #[test]
+    fn test_headless() {
+        let cli = parse(&["--headless", "--size", "100x30", "--timeout", "1.5"]).unwrap();
+        assert!(cli.headless);
+        assert_eq!(
+            cli.size,
+            Some(Dimensions {
+                cols: 100,
+                rows: 30
+            })
+        );
+        assert_eq!(cli.timeout, Some(1.5));
+        assert!(!cli.debug_events);
+        assert!(parse(&["--headless", "--size", "100"]).is_err());
+        assert!(parse(&["--ansi"]).is_err());
+    }
+
+    #[test]
+    fn test_msg() {
+        let cli = parse(&["msg", "send-text", "echo hi\r"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Subcommand::Msg {
+                socket: None,
+                request: Request::SendText {
+                    text: "echo hi\r".into()
+                }
+            })
+        );
+        let cli = parse(&[
+            "msg",
+            "--socket",
+            "/tmp/firn.sock",
+            "new-tab",
+            "--profile",
+            "work",
+        ]);
+        assert_eq!(
+            cli.unwrap().command,
+            Some(Subcommand::Msg {
+                socket: Some("/tmp/firn.sock".into()),
+                request: Request::NewTab {
+                    profile: Some("work".into())
+                }
+            })
+        );
+        assert!(parse(&["msg", "resize", "80"]).is_err());
+    }
+
+    #[test]
+    fn test_options() {
+        let cli = parse(&[
+            "--config",
+            "firn.json",
+            "-o",
+            "font.size=14",
+            "--title",
+            "logs",
+            "--hold",
+            "replay",
+            "session.rec",
+        ])
+        .unwrap();
+        assert_eq!(cli.config, Some("firn.json".into()));
+        assert_eq!(cli.overrides, [("font.size".into(), "14".into())]);
+        assert_eq!(cli.title.as_deref(), Some("logs"));
+        assert!(cli.hold);
+        assert_eq!(
+            cli.command,
+            Some(Subcommand::Replay {
+                path: "session.rec".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use crate::palette;
+use anyhow::Result;
+pub use firn_core::rgb::Rgb;
+use iced::theme::{Palette, Theme};
+use iced::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::error;
+
+/** A color for the cursor or the selection: `#rrggbb`, or `invert` to swap the cell's own colors */
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub enum CellColor {
+    #[default]
+    Invert,
+    Rgb(Rgb),
+}
+
+impl TryFrom<String> for CellColor {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        match text.as_str() {
+            "invert" => Ok(Self::Invert),
+            _ => Rgb::try_from(text).map(Self::Rgb),
+        }
+    }
+}
+
+impl From<CellColor> for String {
+    fn from(color: CellColor) -> Self {
+        match color {
+            CellColor::Invert => "invert".into(),
+            CellColor::Rgb(rgb) => rgb.into(),
+        }
+    }
+}
+
+impl CellColor {
+    /** This color, or `inverted` if it is `invert` */
+    pub fn or(self, inverted: Color) -> Color {
+        match self {
+            Self::Invert => inverted,
+            Self::Rgb(rgb) => color(rgb),
+        }
+    }
+}
+
+/** `rgb` as a color to draw with */
+pub fn color(Rgb(r, g, b): Rgb) -> Color {
+    Color::from_rgb8(r, g, b)
+}
+
+/** The colors the grid is drawn in */
+#[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ColorScheme {
+    pub foreground: Rgb,
+    pub background: Rgb,
+    /** Fill of the cursor; the color of the text under it if `invert` */
+    #[serde(default)]
+    pub cursor: CellColor,
+    /** Text under a block cursor; the cell's background color if `invert` */
+    #[serde(default)]
+    pub cursor_text: CellColor,
+    /** Background of selected cells; their text color if `invert` */
+    #[serde(default)]
+    pub selection: CellColor,
+    /** Text of selected cells; their background color if `invert` */
+    #[serde(default)]
+    pub selection_text: CellColor,
+    /** Black, red, green, yellow, blue, magenta, cyan and white, then their bright variants */
+    pub ansi: [Rgb; 16],
+}
+
+/** Names of the color schemes firn comes with */
+pub const BUILT_IN: [&str; 10] = [
+    "dark",
+    "light",
+    "solarized-dark",
+    "solarized-light",
+    "gruvbox-dark",
+    "dracula",
+    "high-contrast-dark",
+    "high-contrast-light",
+    "deuteranopia",
+    "protanopia",
+];
+
+const fn rgb(hex: u32) -> Rgb {
+    Rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+const fn ansi(hex: [u32; 16]) -> [Rgb; 16] {
+    let mut colors = [Rgb(0, 0, 0); 16];
+    let mut i = 0;
+    while i < 16 {
+        colors[i] = rgb(hex[i]);
+        i += 1;
+    }
+    colors
+}
+
+const XTERM: [u32; 16] = [
+    0x000000, 0xCD0000, 0x00CD00, 0xCDCD00, 0x0000EE, 0xCD00CD, 0x00CDCD, 0xE5E5E5, 0x7F7F7F,
+    0xFF0000, 0x00FF00, 0xFFFF00, 0x5C5CFF, 0xFF00FF, 0x00FFFF, 0xFFFFFF,
+];
+
+const SOLARIZED: [u32; 16] = [
+    0x073642, 0xDC322F, 0x859900, 0xB58900, 0x268BD2, 0xD33682, 0x2AA198, 0xEEE8D5, 0x002B36,
+    0xCB4B16, 0x586E75, 0x657B83, 0x839496, 0x6C71C4, 0x93A1A1, 0xFDF6E3,
+];
+
+impl ColorScheme {
+    /** One of the built-in schemes, by name */
+    pub fn built_in(name: &str) -> Option<Self> {
+        let (foreground, background, cursor, selection, colors) = match name {
+            "dark" => (0xE5E5E5, 0x202225, None, None, XTERM),
+            "light" => (0x1E1E1E, 0xFFFFFF, None, None, XTERM),
+            "solarized-dark" => (
+                0x839496,
+                0x002B36,
+                Some(0x93A1A1),
+                Some(0x073642),
+                SOLARIZED,
+            ),
+            "solarized-light" => (
+                0x657B83,
+                0xFDF6E3,
+                Some(0x586E75),
+                Some(0xEEE8D5),
+                SOLARIZED,
+            ),
+            "gruvbox-dark" => (
+                0xEBDBB2,
+                0x282828,
+                None,
+                Some(0x504945),
+                [
+                    0x282828, 0xCC241D, 0x98971A, 0xD79921, 0x458588, 0xB16286, 0x689D6A, 0xA89984,
+                    0x928374, 0xFB4934, 0xB8BB26, 0xFABD2F, 0x83A598, 0xD3869B, 0x8EC07C, 0xEBDBB2,
+                ],
+            ),
+            "dracula" => (
+                0xF8F8F2,
+                0x282A36,
+                None,
+                Some(0x44475A),
+                [
+                    0x21222C, 0xFF5555, 0x50FA7B, 0xF1FA8C, 0xBD93F9, 0xFF79C6, 0x8BE9FD, 0xF8F8F2,
+                    0x6272A4, 0xFF6E6E, 0x69FF94, 0xFFFFA5, 0xD6ACFF, 0xFF92DF, 0xA4FFFF, 0xFFFFFF,
+                ],
+            ),
+            // Every color but black has a contrast ratio of at least 7:1 with the background
+            "high-contrast-dark" => (
+                0xFFFFFF,
+                0x000000,
+                Some(0xFFFF00),
+                Some(0x1A3A6A),
+                [
+                    0x000000, 0xFF6B6B, 0x5CFF5C, 0xFFFF4D, 0x8AB4FF, 0xFF8AFF, 0x4DFFFF, 0xFFFFFF,
+                    0xA8A8A8, 0xFF9E9E, 0x9EFF9E, 0xFFFF9E, 0xB8D0FF, 0xFFB8FF, 0x9EFFFF, 0xFFFFFF,
+                ],
+            ),
+            "high-contrast-light" => (
+                0x000000,
+                0xFFFFFF,
+                Some(0x0000C0),
+                Some(0xC8DCFF),
+                [
+                    0x000000, 0xA00000, 0x005A00, 0x5C4A00, 0x0000C0, 0x8A008A, 0x004F5C, 0x4A4A4A,
+                    0x333333, 0x8B0000, 0x004000, 0x4A3B00, 0x00008B, 0x6B006B, 0x003A44, 0x000000,
+                ],
+            ),
+            // Okabe and Ito's palette: red and green become vermillion and sky blue
+            "deuteranopia" => (
+                0xE8E8E8,
+                0x1C1C1C,
+                None,
+                Some(0x3A3A3A),
+                [
+                    0x1C1C1C, 0xD55E00, 0x56B4E9, 0xF0E442, 0x0072B2, 0xCC79A7, 0x009E73, 0xE8E8E8,
+                    0x7A7A7A, 0xFF8A3D, 0x8FD3FF, 0xFFF58A, 0x3D9EE0, 0xE8A6CB, 0x3DCCA3, 0xFFFFFF,
+                ],
+            ),
+            // Reds look darker without red cones, so red becomes a brighter orange
+            "protanopia" => (
+                0xE8E8E8,
+                0x1C1C1C,
+                None,
+                Some(0x3A3A3A),
+                [
+                    0x1C1C1C, 0xE69F00, 0x56B4E9, 0xF0E442, 0x0072B2, 0xCC79A7, 0x009E73, 0xE8E8E8,
+                    0x7A7A7A, 0xFFBF40, 0x8FD3FF, 0xFFF58A, 0x3D9EE0, 0xE8A6CB, 0x3DCCA3, 0xFFFFFF,
+                ],
+            ),
+            _ => return None,
+        };
+        let color =
+            |hex: Option<u32>| hex.map_or(CellColor::Invert, |hex| CellColor::Rgb(rgb(hex)));
+        Some(Self {
+            foreground: rgb(foreground),
+            background: rgb(background),
+            cursor: color(cursor),
+            cursor_text: CellColor::Invert,
+            selection: color(selection),
+            // Inverting would draw the text in the background color, on a background that is close to it
+            selection_text: color(selection.map(|_| foreground)),
+            ansi: ansi(colors),
+        })
+    }
+
+    /**
+     * Look a scheme up among the user's own and then the built-in ones,
+     * falling back to "dark" if neither has it
+     */
+    pub fn resolve(name: &str, schemes: &BTreeMap<String, ColorScheme>) -> Self {
+        if let Some(scheme) = schemes.get(name) {
+            return scheme.clone();
+        }
+        Self::built_in(name).unwrap_or_else(|| {
+            error!("Unknown color scheme {name:?}, expected one of `colors` or {BUILT_IN:?}");
+            Self::built_in("dark").unwrap()
+        })
+    }
+
+    /** One of the 256 indexed colors, the first 16 of which the scheme decides */
+    pub fn indexed(&self, n: u8) -> Color {
+        match self.ansi.get(n as usize) {
+            Some(&rgb) => color(rgb),
+            None => palette::indexed(n),
+        }
+    }
+
+    /**
+     * This scheme with each green shifted towards blue wherever it would
+     * look like the red beside it to someone with red-green color blindness.
+     * Colors programs set directly, as RGB, are left alone.
+     */
+    pub fn with_red_green_apart(mut self) -> Self {
+        for (red, green) in [(1, 2), (9, 10)] {
+            let apart = |color| !confusable(self.ansi[red], color);
+            if apart(self.ansi[green]) {
+                continue;
+            }
+            let Rgb(r, g, b) = self.ansi[green];
+            let shifted = Rgb(r / 3, (u16::from(g) * 3 / 4) as u8, b.max(g));
+            // Failing that, lighter or darker than the red too
+            let mix =
+                |Rgb(r, g, b): Rgb, to: u8| Rgb(r / 2 + to / 2, g / 2 + to / 2, b / 2 + to / 2);
+            self.ansi[green] = [shifted, mix(shifted, 0xFF), mix(shifted, 0)]
+                .into_iter()
+                .find(|&color| apart(color))
+                .unwrap_or(shifted);
+        }
+        self
+    }
+
+    /** An iced theme to match, so that the widgets around the grid fit in */
+    pub fn theme(&self) -> Theme {
+        Theme::custom(Palette {
+            background: color(self.background),
+            text: color(self.foreground),
+            primary: color(self.ansi[4]),
+            success: color(self.ansi[2]),
+            danger: color(self.ansi[1]),
+        })
+    }
+}
+
+/**
+ * Whether two colors look alike with deuteranopia or protanopia, going by
+ * Viénot, Brettel and Mollon's simulation of each
+ */
+fn confusable(a: Rgb, b: Rgb) -> bool {
+    const DEUTERANOPIA: [[f32; 3]; 3] = [
+        [0.29275, 0.70725, 0.0],
+        [0.29275, 0.70725, 0.0],
+        [-0.02234, 0.02234, 1.0],
+    ];
+    const PROTANOPIA: [[f32; 3]; 3] = [
+        [0.11238, 0.88762, 0.0],
+        [0.11238, 0.88762, 0.0],
+        [0.00401, -0.00401, 1.0],
+    ];
+    /** Distance in sRGB that is too short to tell two colors apart by at a glance */
+    const THRESHOLD: f32 = 100.0;
+    [DEUTERANOPIA, PROTANOPIA]
+        .iter()
+        .any(|matrix| distance(simulate(a, matrix), simulate(b, matrix)) < THRESHOLD)
+}
+
+fn simulate(Rgb(r, g, b): Rgb, matrix: &[[f32; 3]; 3]) -> [f32; 3] {
+    let linear = [r, g, b].map(|channel| {
+        let channel = f32::from(channel) / 255.0;
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    matrix.map(|row| {
+        let channel =
+            (row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]).clamp(0.0, 1.0);
+        let channel = if channel <= 0.003_130_8 {
+            channel * 12.92
+        } else {
+            1.055 * channel.powf(1.0 / 2.4) - 0.055
+        };
+        channel * 255.0
+    })
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+
+    #[test]
+    fn test_cell_color() {
+        assert_eq!(
+            CellColor::try_from("invert".to_string()).unwrap(),
+            CellColor::Invert
+        );
+        assert_eq!(
+            CellColor::try_from("#000000".to_string()).unwrap(),
+            CellColor::Rgb(Rgb(0, 0, 0))
+        );
+        assert_matches!(CellColor::try_from("inverse".to_string()), Err(_));
+    }
+
+    #[test]
+    fn test_resolve() {
+        for name in BUILT_IN {
+            assert_matches!(ColorScheme::built_in(name), Some(_));
+        }
+        let mut schemes = BTreeMap::new();
+        let mut own = ColorScheme::built_in("light").unwrap();
+        own.cursor = CellColor::Rgb(Rgb(1, 2, 3));
+        schemes.insert("dark".to_string(), own.clone());
+        assert_eq!(ColorScheme::resolve("dark", &schemes), own);
+        assert_eq!(
+            ColorScheme::resolve("no such scheme", &schemes),
+            ColorScheme::built_in("dark").unwrap()
+        );
+        assert_eq!(own.indexed(1), Color::from_rgb8(0xCD, 0, 0));
+        assert_eq!(own.indexed(196), palette::indexed(196));
+    }
+
+    #[test]
+    fn test_red_green_apart() {
+        for name in BUILT_IN {
+            let colors = ColorScheme::built_in(name).unwrap();
+            let apart = colors.clone().with_red_green_apart();
+            for (red, green) in [(1, 2), (9, 10)] {
+                let (red, green) = (apart.ansi[red], apart.ansi[green]);
+                assert!(!confusable(red, green), "{name}: {red:?} and {green:?}");
+            }
+            if name == "deuteranopia" || name == "protanopia" {
+                assert_eq!(apart, colors);
+            }
+        }
+        let xterm = ColorScheme::built_in("dark").unwrap();
+        assert!(confusable(xterm.ansi[1], xterm.ansi[2]));
+    }
+}
@@ -1,37 +1,1089 @@
-use anyhow::Result;
-use serde::Deserialize;
-use std::{fs::File, path::Path};
+use crate::appearance::Appearance;
+use crate::bindings::{Action, Bindings};
+use crate::colors::{self, ColorScheme};
+use crate::export::ExportFormat;
+use crate::metrics::LINE_HEIGHT_RATIO;
+use crate::width::WidthPolicy;
+use crate::{hints, links};
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tracing::info;
 
-#[derive(Clone, Deserialize)]
-pub struct Config {
-    pub shell: String,
-    pub shell_args: Vec<String>,
-    pub read_buf_size: usize,
-    pub channel_buf_size: usize,
-    pub render_lines: usize,
+/** What a table of settings is for, and what each of its settings does, by name */
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub doc: &'static [&'static str],
+    pub fields: &'static [(&'static str, &'static [&'static str])],
+}
+
+/** The text of a doc comment attribute, or nothing for any other attribute */
+macro_rules! doc_text {
+    (doc = $doc:literal) => {
+        $doc
+    };
+    ($($attr:tt)*) => {
+        ""
+    };
+}
+
+/**
+ * Declare a struct of settings along with its `SETTINGS`, which lifts the doc
+ * comments off the struct and its fields for `firn default-config` to annotate
+ * the generated file with
+ */
+macro_rules! settings {
+    (
+        $(#[$($attr:tt)*])*
+        pub struct $name:ident {
+            $(
+                $(#[$($field_attr:tt)*])*
+                pub $field:ident: $type:ty,
+            )*
+        }
+    ) => {
+        $(#[$($attr)*])*
+        pub struct $name {
+            $(
+                $(#[$($field_attr)*])*
+                pub $field: $type,
+            )*
+        }
+
+        impl $name {
+            pub const SETTINGS: Settings = Settings {
+                doc: &[$(doc_text!($($attr)*)),*],
+                fields: &[$((stringify!($field), &[$(doc_text!($($field_attr)*)),*])),*],
+            };
+        }
+    };
+}
+
+/** Settings for every user, which each user's own config file overrides */
+#[cfg(unix)]
+const SYSTEM_CONFIG: &str = "/etc/firn/config.toml";
+
+/** The shell to fall back on when neither `$SHELL` nor the user's passwd entry name one */
+#[cfg(windows)]
+const PLATFORM_SHELL: &str = "powershell.exe";
+#[cfg(target_os = "macos")]
+const PLATFORM_SHELL: &str = "/bin/zsh";
+#[cfg(not(any(windows, target_os = "macos")))]
+const PLATFORM_SHELL: &str = "/bin/sh";
+
+/** The user's login shell: `$SHELL`, else the one in their passwd entry, else the platform's */
+fn default_shell() -> String {
+    if let Some(shell) = std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+    {
+        return shell;
+    }
+    #[cfg(unix)]
+    if let Ok(Some(user)) = nix::unistd::User::from_uid(nix::unistd::getuid()) {
+        if !user.shell.as_os_str().is_empty() {
+            return user.shell.to_string_lossy().into_owned();
+        }
+    }
+    PLATFORM_SHELL.into()
+}
+
+/** What to do once the shell exits */
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ExitBehavior {
+    /** Close the window */
+    #[default]
+    Close,
+    /** Keep the window open, showing the exit status */
+    Hold,
+    /** Start a fresh shell, or hold if it exited within a second of starting */
+    Restart,
+}
+
+/** What the terminal is attached to */
+#[derive(Clone, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum BackendConfig {
+    /** Spawn the shell on a fresh pty */
+    #[default]
+    Pty,
+    /** Talk to a serial device, e.g. `/dev/ttyUSB0`, in raw mode */
+    Serial { device: PathBuf, baud_rate: u32 },
+    /** Talk to an already open file descriptor inherited from firn's parent */
+    Fd { fd: i32 },
+    /** Play back an asciinema cast, at `speed` times the recorded pace */
+    Playback { path: PathBuf, speed: f64 },
+}
+
+/** What Alt does to the characters typed with it */
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Meta {
+    /** Prefix the character with ESC, as readline and emacs expect */
+    #[default]
+    Escape,
+    /** Set the high bit of ASCII characters */
+    HighBit,
+    /** Send the character unchanged */
+    Off,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorShape {
+    #[default]
+    Block,
+    /** A vertical bar at the left edge of the cell */
+    Beam,
+    Underline,
+}
+
+/** When to show the scrollbar */
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollbarVisibility {
+    Always,
+    /** While scrolled back into history, dragging it, or hovering over it */
+    #[default]
+    Auto,
+    Never,
+}
+
+settings! {
+    /** The font the grid is drawn in */
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct FontConfig {
+        /** Family name of the font to draw the grid in; the system's monospace font if unset */
+        pub family: Option<String>,
+        /**
+         * Font files to load at startup, for glyphs the main font lacks such as
+         * CJK, emoji or Nerd Font symbols
+         */
+        pub fallback: Vec<PathBuf>,
+        /** Size in logical pixels, which zooming starts from and returns to */
+        pub size: f32,
+        /** Multiplies the height of each row */
+        pub line_height: f32,
+        /** Multiplies the width of each column */
+        pub letter_spacing: f32,
+        /**
+         * How wide a cell is, relative to the font size, in place of the
+         * advance measured from the font, e.g. for a font whose glyphs are not
+         * all equally wide
+         */
+        pub cell_width: Option<f32>,
+        /** How tall a row is, relative to the font size, before `line_height` multiplies it */
+        pub cell_height: f32,
+        /** Draw bold text in a heavier weight of the font */
+        pub bold_weight: bool,
+    }
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            family: None,
+            fallback: vec![],
+            size: 16.0,
+            line_height: 1.0,
+            letter_spacing: 1.0,
+            cell_width: None,
+            cell_height: LINE_HEIGHT_RATIO,
+            bold_weight: true,
+        }
+    }
+}
+
+settings! {
+    /** Selecting text with the mouse and in copy mode */
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct SelectionConfig {
+        /**
+         * Symbols that count as part of a word, besides letters and digits, when
+         * double-clicking and for the word motions of copy mode
+         */
+        pub word_characters: String,
+        /** Leave out the spaces at the ends of copied lines */
+        pub trim_trailing_whitespace: bool,
+    }
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        Self {
+            word_characters: "-_./~".into(),
+            trim_trailing_whitespace: true,
+        }
+    }
+}
+
+/** A size in cells, written as `<columns>x<rows>` */
+#[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct Dimensions {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl TryFrom<String> for Dimensions {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        let Some((cols, rows)) = text.split_once('x') else {
+            bail!("Expected dimensions like 120x35, got {text:?}");
+        };
+        let parse = |n: &str| -> Result<u16> {
+            match n.parse() {
+                Ok(0) => bail!("Dimensions must not be zero, got {text:?}"),
+                n => n.with_context(|| format!("Invalid dimensions {text:?}")),
+            }
+        };
+        Ok(Self {
+            cols: parse(cols)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+impl From<Dimensions> for String {
+    fn from(dimensions: Dimensions) -> Self {
+        format!("{}x{}", dimensions.cols, dimensions.rows)
+    }
+}
+
+settings! {
+    /** What happens when a program rings the bell (BEL) */
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct BellConfig {
+        /** Flash the grid */
+        pub visual: bool,
+        /** Play the desktop's bell sound, at most once per `audible_interval_ms` */
+        pub audible: bool,
+        /** How long after the bell sounds it may sound again, so that a burst of BELs plays once */
+        pub audible_interval_ms: u64,
+        /** How long the flash lasts */
+        pub duration_ms: u64,
+        /** Ask the window manager to draw attention to the window while it is unfocused */
+        pub urgent: bool,
+    }
+}
+
+impl Default for BellConfig {
+    fn default() -> Self {
+        Self {
+            visual: true,
+            audible: false,
+            audible_interval_ms: 500,
+            duration_ms: 150,
+            urgent: true,
+        }
+    }
+}
+
+/** What a turn of the mouse wheel one way presses in a full-screen program */
+#[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum WheelKey {
+    /** The up or down arrow, `lines` times for each line the wheel scrolls */
+    Arrows,
+    /** Page Up or Page Down, once per turn */
+    Pages,
+    /** Nothing: scroll the view as usual */
+    None,
+}
+
+settings! {
+    /**
+     * The mouse wheel in a program like less, man or vim, which has switched to
+     * the alternate screen without asking for mouse events
+     */
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct AlternateScrollConfig {
+        /** What scrolling up presses: "arrows", "pages", or "none" to scroll the view as usual */
+        pub up: WheelKey,
+        /** What scrolling down presses: "arrows", "pages", or "none" to scroll the view as usual */
+        pub down: WheelKey,
+        /** Arrow presses for each line the wheel scrolls, of which a notch is 3 */
+        pub lines: usize,
+    }
+}
+
+impl Default for AlternateScrollConfig {
+    fn default() -> Self {
+        Self {
+            up: WheelKey::Arrows,
+            down: WheelKey::Arrows,
+            lines: 1,
+        }
+    }
+}
+
+settings! {
+    /**
+     * Watching tabs in the background for output, or for the lack of it, e.g.
+     * when a build finishes
+     */
+    #[derive(Clone, Default, Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct MonitorConfig {
+        /** Mark a tab in the background once its output has stopped for this many seconds */
+        pub silence_secs: Option<u64>,
+        /** Show a desktop notification when a tab in the background gets output or falls silent */
+        pub notify: bool,
+    }
+}
+
+settings! {
+    /** Logging what each session prints to a file, like `script(1)` */
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct SessionLogConfig {
+        /** Log every session from the start, rather than once `ToggleSessionLog` is pressed */
+        pub enabled: bool,
+        /** Where the logs go, a file per session; `$XDG_STATE_HOME/firn/logs` if unset */
+        pub directory: Option<PathBuf>,
+        /** Start each line with the time, in UTC, that it was printed */
+        pub timestamps: bool,
+        /** Leave out escape sequences and control characters, keeping only the text */
+        pub strip_escapes: bool,
+    }
+}
+
+impl Default for SessionLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            timestamps: false,
+            strip_escapes: true,
+        }
+    }
+}
+
+/**
+ * Settings that differ between kinds of session, e.g. a local shell, a serial
+ * console and ssh to production. Whatever a profile leaves unset is taken
+ * from the rest of the config.
+ */
+#[derive(Clone, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub shell: Option<String>,
+    pub shell_args: Option<Vec<String>>,
+    pub backend: Option<BackendConfig>,
+    pub working_directory: Option<PathBuf>,
+    /** Added to the config's own `env` */
+    pub env: BTreeMap<String, String>,
+    pub color_scheme: Option<String>,
+    /**
+     * The font, in place of the config's own. Tabs share the window's font
+     * size, so in a new tab only the family and fallbacks take effect.
+     */
+    pub font: Option<FontConfig>,
+}
+
+/** Whether to reopen the tabs saved when the last window closed */
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreSession {
+    /** Offer to, unless all there was is a lone shell at its prompt */
+    #[default]
+    Ask,
+    Always,
+    /** Unless started with `--restore` */
+    Never,
+}
+
+/** How the window first appears */
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StartupMode {
+    #[default]
+    Windowed,
+    Maximized,
+    Fullscreen,
+}
+
+settings! {
+    /** The window itself */
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct WindowConfig {
+        /**
+         * How opaque the background is, from 0 to 1; text stays opaque. Blurring
+         * what shows through is up to the compositor.
+         */
+        pub opacity: f32,
+        /** Image drawn behind the grid, scaled to cover the window */
+        pub background_image: Option<PathBuf>,
+        /** How strongly the image shows through the background color, from 0 to 1 */
+        pub background_image_opacity: f32,
+        /** Space between the grid and the edges of the window, in logical pixels */
+        pub padding: u16,
+        /** How the window first appears: "windowed", "maximized" or "fullscreen" */
+        pub startup_mode: StartupMode,
+        /** Draw the title bar and borders; without them the window is borderless */
+        pub decorations: bool,
+        /**
+         * How many columns and rows the window starts out with, like "120x35",
+         * overriding its remembered size
+         */
+        pub dimensions: Option<Dimensions>,
+        /** Save the window's size and position on exit and start out the same next time */
+        pub remember_geometry: bool,
+        /** Window title until the shell sets one, which `{title}` in `title_format` stands for */
+        pub title: String,
+        /** Application id, or WM_CLASS, for the window manager to tell firn windows apart by */
+        pub class: String,
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            background_image: None,
+            background_image_opacity: 0.25,
+            padding: 0,
+            startup_mode: StartupMode::default(),
+            decorations: true,
+            dimensions: None,
+            remember_geometry: true,
+            title: "Firn Terminal".into(),
+            class: "firn".into(),
+        }
+    }
+}
+
+settings! {
+    #[derive(Clone, Deserialize, Serialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct Config {
+        /** Program to run, by default the user's login shell */
+        pub shell: String,
+        /** Arguments to run the shell with */
+        pub shell_args: Vec<String>,
+        /** How many bytes to read from the pty at a time */
+        pub read_buf_size: usize,
+        /** How many messages of output may queue up on their way to the window */
+        pub channel_buf_size: usize,
+        /** Upper bound on how many bytes of pty output are coalesced into one message */
+        pub max_chunk_size: usize,
+        /**
+         * After a read from the pty, how long to keep collecting further output
+         * into the same message
+         */
+        pub coalesce_ms: u64,
+        /**
+         * How many lines of history are kept above the screen to scroll back
+         * through; older ones are dropped
+         */
+        #[serde(alias = "render_lines")]
+        pub scrollback_lines: usize,
+        /** Tee every byte read from the pty into this file, for replaying with `firn replay` */
+        pub record_path: Option<PathBuf>,
+        /** Record the session into this file as an asciinema v2 cast */
+        pub cast_path: Option<PathBuf>,
+        /** Take commands from `firn msg` on a socket, which shells find in `$FIRN_SOCKET` */
+        pub remote_control: bool,
+        /** Show the screen to screen readers and read out new output; AT-SPI only, i.e. Linux */
+        pub screen_reader: bool,
+        /**
+         * Show Arabic and Hebrew right to left, reordering each line by the
+         * Unicode Bidirectional Algorithm. Full-screen programs that lay out such
+         * text themselves turn this off while they run, with `CSI 8 l`.
+         */
+        pub bidi: bool,
+        /**
+         * How many cells characters take up, which has to match what programs
+         * expect: "unicode" for Unicode 9 and later, "legacy" for the `wcwidth` of
+         * older systems, or "cjk" for East Asian locales, where characters of
+         * ambiguous width are wide
+         */
+        pub width_policy: WidthPolicy,
+        /** Directory to start the shell in, instead of firn's own working directory */
+        pub working_directory: Option<PathBuf>,
+        /**
+         * What to do once the shell exits: "close" the window, "hold" it open
+         * showing the exit status, or "restart" the shell, holding instead if
+         * it exited within a second of starting
+         */
+        pub exit_behavior: ExitBehavior,
+        /** One of "block", "beam" or "underline" */
+        pub cursor_shape: CursorShape,
+        /** Blink the cursor */
+        pub cursor_blink: bool,
+        /** How long the cursor stays on, and then off, while blinking */
+        pub cursor_blink_interval_ms: u64,
+        /**
+         * Copy the selection to the clipboard as soon as the mouse button is
+         * released, not just to the primary selection
+         */
+        pub copy_on_select: bool,
+        pub selection: SelectionConfig,
+        /** Strip escape and other control characters from pasted text */
+        pub sanitize_paste: bool,
+        /**
+         * Ask before pasting text that would run commands at its line breaks, or
+         * that has control characters in it which are not stripped, showing what
+         * it holds and offering to edit it first
+         */
+        pub confirm_paste: bool,
+        /**
+         * What Alt does to the characters typed with it: "escape" prefixes them
+         * with ESC, as readline and emacs expect, "highbit" sets the high bit of
+         * ASCII characters, and "off" sends them unchanged
+         */
+        pub meta: Meta,
+        /**
+         * Jump back to the live screen when the shell prints something; otherwise
+         * the lines scrolled back to stay in view while output arrives
+         */
+        pub scroll_on_output: bool,
+        /** Jump back to the live screen when typing or pasting */
+        pub scroll_on_keypress: bool,
+        /**
+         * When to show the scrollbar: "always", "never", or "auto" while scrolled
+         * back into history, dragging it, or hovering over it
+         */
+        pub scrollbar: ScrollbarVisibility,
+        /**
+         * How much faster than usual the mouse wheel and touchpad scroll: a wheel
+         * notch is 3 lines times this, and a touchpad its distance times this
+         */
+        pub scroll_multiplier: f32,
+        pub alternate_scroll: AlternateScrollConfig,
+        /** Regular expressions for text to underline on hover and open on Ctrl+click */
+        pub link_patterns: Vec<String>,
+        /**
+         * Command to open file locations such as `src/main.rs:12:5` with, where
+         * `{file}`, `{line}` and `{column}` stand for the location. If empty,
+         * `$VISUAL` or `$EDITOR` opens in a new tab.
+         */
+        pub editor: Vec<String>,
+        /** Regular expressions for what hints mode (Ctrl+Shift+Space) labels besides links */
+        pub hint_patterns: Vec<String>,
+        /**
+         * A regular expression for lines that start with a prompt, for jumping
+         * between prompts when the shell does not mark them with OSC 133
+         */
+        pub prompt_pattern: Option<String>,
+        /**
+         * What the terminal is attached to: `{ type = "pty" }` for a shell,
+         * `{ type = "serial", device = "/dev/ttyUSB0", baud_rate = 115200 }`,
+         * `{ type = "fd", fd = 3 }` for a file descriptor inherited from firn's
+         * parent, or `{ type = "playback", path = "demo.cast", speed = 1.0 }` for
+         * an asciinema cast
+         */
+        pub backend: BackendConfig,
+        /** Extra environment variables for the shell, overriding firn's own */
+        pub env: BTreeMap<String, String>,
+        /** How long a shell gets to exit after being hung up before it is killed */
+        pub kill_timeout_ms: u64,
+        /**
+         * Reopen the tabs from when the last window closed, in the directories
+         * they were in, with the commands they were running typed at the prompt:
+         * "ask" unless all there was is a lone shell at its prompt, "always", or
+         * "never" unless started with `--restore`
+         */
+        pub restore_session: RestoreSession,
+        /** Ask before closing a window or tab whose shell is running something */
+        pub confirm_close: bool,
+        /** Programs that may be closed without asking, by process name */
+        pub confirm_close_ignore: Vec<String>,
+        pub font: FontConfig,
+        /** Name of the color scheme, either one of `colors` or a built-in one */
+        pub color_scheme: String,
+        /** Color schemes of one's own, by name */
+        pub colors: BTreeMap<String, ColorScheme>,
+        /** Color scheme to switch to while the desktop prefers light colors */
+        pub light_color_scheme: Option<String>,
+        /** Color scheme to switch to while the desktop prefers dark colors */
+        pub dark_color_scheme: Option<String>,
+        /**
+         * Shift greens towards blue in color schemes whose reds and greens look
+         * alike with red-green color blindness
+         */
+        pub remap_red_green: bool,
+        /** Draw bold text in one of the 8 standard colors in its bright variant instead */
+        pub bold_as_bright: bool,
+        /**
+         * The window title and tab labels, in which `{title}` is the title the
+         * shell set, `{cwd}` its working directory, `{command}` the program in
+         * the foreground, and `{columns}` and `{rows}` the grid's size
+         */
+        pub title_format: String,
+        pub window: WindowConfig,
+        pub bell: BellConfig,
+        pub session_log: SessionLogConfig,
+        pub monitor: MonitorConfig,
+        /**
+         * Show a line under the grid with the title, working directory, scroll
+         * position and mode
+         */
+        pub status_bar: bool,
+        /**
+         * What Ctrl+Shift+S saves the rows in view, or the selected ones, as:
+         * "png", exactly what is on screen, or "svg", the text and its colors
+         */
+        pub export_format: ExportFormat,
+        /** Where exported images go; the shell's working directory if unset */
+        pub export_directory: Option<PathBuf>,
+        /**
+         * Actions by key, e.g. `"ctrl+shift+c" = "Copy"` or
+         * `"ctrl+shift+enter" = { SendBytes = '\e[13;5u' }`, on top of the
+         * default bindings; `"None"` unbinds a key
+         */
+        pub keybindings: BTreeMap<String, Action>,
+        /** Lua scripts to run at startup, which hook into firn through the `firn` table */
+        pub plugins: Vec<PathBuf>,
+        /** Named sets of settings to start the window, or a new tab, with */
+        pub profiles: BTreeMap<String, Profile>,
+        /** Why the config files could not be used, so that the window can say so */
+        #[serde(skip)]
+        pub load_error: Option<String>,
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            shell: "/usr/bin/bash".into(),
+            shell: default_shell(),
             shell_args: vec![],
             read_buf_size: 1024,
             channel_buf_size: 100,
-            render_lines: 100,
+            max_chunk_size: 64 * 1024,
+            coalesce_ms: 4,
+            scrollback_lines: 10_000,
+            record_path: None,
+            cast_path: None,
+            remote_control: false,
+            screen_reader: false,
+            bidi: true,
+            width_policy: WidthPolicy::default(),
+            working_directory: None,
+            exit_behavior: ExitBehavior::default(),
+            cursor_shape: CursorShape::default(),
+            cursor_blink: true,
+            cursor_blink_interval_ms: 530,
+            copy_on_select: false,
+            selection: SelectionConfig::default(),
+            sanitize_paste: true,
+            confirm_paste: true,
+            meta: Meta::default(),
+            scroll_on_output: false,
+            scroll_on_keypress: true,
+            scrollbar: ScrollbarVisibility::default(),
+            scroll_multiplier: 1.0,
+            alternate_scroll: AlternateScrollConfig::default(),
+            link_patterns: links::DEFAULT_PATTERNS.map(String::from).to_vec(),
+            editor: vec![],
+            hint_patterns: hints::DEFAULT_PATTERNS.map(String::from).to_vec(),
+            prompt_pattern: None,
+            backend: BackendConfig::default(),
+            env: BTreeMap::new(),
+            kill_timeout_ms: 1000,
+            restore_session: RestoreSession::default(),
+            confirm_close: true,
+            confirm_close_ignore: ["bash", "zsh", "fish", "sh", "dash", "nu", "tmux", "screen"]
+                .map(String::from)
+                .to_vec(),
+            font: FontConfig::default(),
+            color_scheme: "dark".into(),
+            colors: BTreeMap::new(),
+            light_color_scheme: None,
+            dark_color_scheme: None,
+            remap_red_green: false,
+            bold_as_bright: false,
+            title_format: "{title}".into(),
+            window: WindowConfig::default(),
+            bell: BellConfig::default(),
+            session_log: SessionLogConfig::default(),
+            monitor: MonitorConfig::default(),
+            status_bar: false,
+            export_format: ExportFormat::default(),
+            export_directory: None,
+            keybindings: BTreeMap::new(),
+            plugins: vec![],
+            profiles: BTreeMap::new(),
+            load_error: None,
         }
     }
 }
 
 impl Config {
-    pub fn from_file(file: File) -> Result<Self> {
-        let config: Self = serde_json::from_reader(file)?;
-        Ok(config)
+    /** Whether the color scheme should follow the desktop's light or dark appearance */
+    pub fn follows_appearance(&self) -> bool {
+        self.light_color_scheme.is_some() || self.dark_color_scheme.is_some()
     }
 
-    pub fn from_path(path: &Path) -> Result<Self> {
-        let file = File::open(path)?;
-        let config = Self::from_file(file)?;
+    /** Name of the color scheme to use while the desktop has the given appearance */
+    pub fn color_scheme_for(&self, appearance: Option<Appearance>) -> &str {
+        let scheme = match appearance {
+            Some(Appearance::Light) => self.light_color_scheme.as_ref(),
+            Some(Appearance::Dark) => self.dark_color_scheme.as_ref(),
+            None => None,
+        };
+        scheme.unwrap_or(&self.color_scheme)
+    }
+
+    /** A color scheme by name, with its reds and greens told apart if `remap_red_green` says */
+    pub fn resolve_colors(&self, name: &str) -> ColorScheme {
+        let colors = ColorScheme::resolve(name, &self.colors);
+        if self.remap_red_green {
+            colors.with_red_green_apart()
+        } else {
+            colors
+        }
+    }
+
+    /** This config with the named profile's settings applied, if there is such a profile */
+    pub fn with_profile(&self, name: &str) -> Option<Self> {
+        let profile = self.profiles.get(name)?.clone();
+        let mut config = self.clone();
+        config.shell = profile.shell.unwrap_or(config.shell);
+        config.shell_args = profile.shell_args.unwrap_or(config.shell_args);
+        config.backend = profile.backend.unwrap_or(config.backend);
+        config.working_directory = profile.working_directory.or(config.working_directory);
+        config.env.extend(profile.env);
+        config.color_scheme = profile.color_scheme.unwrap_or(config.color_scheme);
+        config.font = profile.font.unwrap_or(config.font);
+        Some(config)
+    }
+
+    /**
+     * The config files to read, in order, each overriding the ones before:
+     * the system-wide one, then the user's own. That is `path` if given, which
+     * must then exist, or else `config.toml`, or failing that `config.json`,
+     * in the user's config directory.
+     */
+    pub fn layers(path: Option<PathBuf>) -> Vec<Layer> {
+        let mut layers = vec![];
+        #[cfg(unix)]
+        layers.push(Layer::optional(SYSTEM_CONFIG.into()));
+        let user = match path {
+            Some(path) => Some(Layer::required(path)),
+            None => Self::user_path().map(|toml| {
+                let json = toml.with_extension("json");
+                Layer::optional(if !toml.exists() && json.exists() {
+                    json
+                } else {
+                    toml
+                })
+            }),
+        };
+        layers.extend(user);
+        layers
+    }
+
+    /** Where the user's own config file goes, e.g. `~/.config/firn/config.toml` */
+    pub fn user_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("firn").join("config.toml"))
+    }
+
+    /**
+     * Merge the config files that exist among `layers` over the defaults, then
+     * apply overrides like `("font.size", "14")`, whose values are read as
+     * JSON or else taken as strings. Files ending in `.json` are JSON, the
+     * rest TOML.
+     */
+    pub fn load(layers: &[Layer], overrides: &[(String, String)]) -> Result<Self> {
+        let mut value = Value::Object(Default::default());
+        for layer in layers {
+            if let Some(contents) = layer.read()? {
+                info!("Loaded config from {:?}", layer.path);
+                merge(&mut value, contents);
+            }
+        }
+        for (key, text) in overrides {
+            let mut target = &mut value;
+            for part in key.split('.') {
+                if !target.is_object() {
+                    *target = Value::Object(Default::default());
+                }
+                target = target
+                    .as_object_mut()
+                    .unwrap()
+                    .entry(part)
+                    .or_insert(Value::Null);
+            }
+            *target = serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.clone()));
+        }
+        let config: Self = serde_path_to_error::deserialize(value).map_err(|err| {
+            let key = err.path().to_string();
+            anyhow!("Invalid config at `{key}`: {}", err.into_inner())
+        })?;
+        config.validate()?;
         Ok(config)
     }
+
+    /** Check what deserializing alone does not: that names refer to something and patterns compile */
+    fn validate(&self) -> Result<()> {
+        let mut schemes = vec![
+            ("color_scheme".to_string(), Some(&self.color_scheme)),
+            (
+                "light_color_scheme".into(),
+                self.light_color_scheme.as_ref(),
+            ),
+            ("dark_color_scheme".into(), self.dark_color_scheme.as_ref()),
+        ];
+        for (name, profile) in &self.profiles {
+            let key = format!("profiles.{name}.color_scheme");
+            schemes.push((key, profile.color_scheme.as_ref()));
+        }
+        for (key, name) in schemes {
+            if let Some(name) = name {
+                if !self.colors.contains_key(name) && !colors::BUILT_IN.contains(&name.as_str()) {
+                    bail!(
+                        "Invalid config at `{key}`: no color scheme {name:?} in `colors`, \
+                         nor built in: {:?}",
+                        colors::BUILT_IN
+                    );
+                }
+            }
+        }
+        if let Err(err) = Bindings::new(&self.keybindings) {
+            bail!("Invalid config at `keybindings`: {err:#}");
+        }
+        let patterns = [
+            ("link_patterns", &self.link_patterns),
+            ("hint_patterns", &self.hint_patterns),
+        ];
+        for (key, patterns) in patterns {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if let Err(err) = Regex::new(pattern) {
+                    bail!("Invalid config at `{key}[{i}]`: {err}");
+                }
+            }
+        }
+        if let Some(Err(err)) = self.prompt_pattern.as_deref().map(Regex::new) {
+            bail!("Invalid config at `prompt_pattern`: {err}");
+        }
+        let fractions = [
+            ("window.opacity", self.window.opacity),
+            (
+                "window.background_image_opacity",
+                self.window.background_image_opacity,
+            ),
+        ];
+        for (key, fraction) in fractions {
+            if !(0.0..=1.0).contains(&fraction) {
+                bail!("Invalid config at `{key}`: expected a number from 0 to 1, got {fraction}");
+            }
+        }
+        let positives = [
+            ("font.size", Some(self.font.size)),
+            ("font.cell_width", self.font.cell_width),
+            ("font.cell_height", Some(self.font.cell_height)),
+            ("scroll_multiplier", Some(self.scroll_multiplier)),
+        ];
+        for (key, value) in positives {
+            if let Some(value) = value.filter(|value| *value <= 0.0) {
+                bail!("Invalid config at `{key}`: expected a positive number, got {value}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * The per-user directory for configuration: `$XDG_CONFIG_HOME`, or else
+ * wherever the platform puts it
+ */
+fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(dir.into());
+    }
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA").map(PathBuf::from);
+    }
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    if cfg!(target_os = "macos") {
+        return Some(home.join("Library").join("Application Support"));
+    }
+    Some(home.join(".config"))
+}
+
+/** One config file to read, and whether it is an error for it not to exist */
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub path: PathBuf,
+    pub required: bool,
+}
+
+impl Layer {
+    /** A file firn looks for on its own, which need not be there */
+    pub fn optional(path: PathBuf) -> Self {
+        Self {
+            path,
+            required: false,
+        }
+    }
+
+    /** A file the user asked for by name */
+    pub fn required(path: PathBuf) -> Self {
+        Self {
+            path,
+            required: true,
+        }
+    }
+
+    /** The file's contents, or `None` if it is optional and does not exist */
+    fn read(&self) -> Result<Option<Value>> {
+        let path = &self.path;
+        match std::fs::read_to_string(path) {
+            Ok(text) => parse_layer(path, &text).map(Some),
+            Err(err) if !self.required && err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("Unable to read {path:?}")),
+        }
+    }
+}
+
+fn parse_layer(path: &Path, text: &str) -> Result<Value> {
+    let value = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::from_str(text).with_context(|| format!("Unable to parse {path:?}"))?
+    } else {
+        // TOML's errors quote the offending line, so they go on a line of their own
+        toml::from_str(text).map_err(|err| anyhow!("Unable to parse {path:?}:\n{err}"))?
+    };
+    Ok(value)
+}
+
+/** Merge `layer` into `base`, recursing into tables so that a layer can set a single key of one */
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+
+    #[test]
+    fn test_load() {
+        let overrides = [
+            ("font.size".to_string(), "14".to_string()),
+            ("shell".to_string(), "/bin/zsh".to_string()),
+            ("window.opacity".to_string(), "0.9".to_string()),
+        ];
+        let dir = std::env::temp_dir().join(format!("firn-test-load-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let system = dir.join("system.toml");
+        std::fs::write(
+            &system,
+            "shell = \"/bin/sh\"\n[font]\nsize = 12\nline_height = 1.2\n",
+        )
+        .unwrap();
+        let user = dir.join("user.json");
+        std::fs::write(&user, r#"{"font": {"size": 13}, "window": {"padding": 4}}"#).unwrap();
+        let layers = [
+            Layer::optional(system.clone()),
+            Layer::required(user),
+            Layer::optional(dir.join("missing.toml")),
+        ];
+
+        let config = Config::load(&layers, &overrides).unwrap();
+        assert_eq!(config.font.size, 14.0);
+        assert_eq!(config.font.line_height, 1.2);
+        assert_eq!(config.shell, "/bin/zsh");
+        assert_eq!(config.window.opacity, 0.9);
+        assert_eq!(config.window.padding, 4);
+
+        let invalid = [("font.size".to_string(), "big".to_string())];
+        let Err(err) = Config::load(&layers, &invalid) else {
+            panic!("font.size should not accept a string");
+        };
+        assert!(err.to_string().starts_with("Invalid config at `font.size`"));
+
+        let missing = [Layer::required(dir.join("missing.toml"))];
+        let Err(err) = Config::load(&missing, &[]) else {
+            panic!("a config file passed by name should exist");
+        };
+        assert!(err.to_string().starts_with("Unable to read"));
+
+        std::fs::write(&system, "[font\n").unwrap();
+        assert!(Config::load(&layers, &[]).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_profile() {
+        let profiles = r#"{"prod": {"shell": "ssh", "shell_args": ["prod"], "color_scheme": "dracula", "env": {"A": "1"}}}"#;
+        let config = Config::load(&[], &[("profiles".into(), profiles.into())]).unwrap();
+        let prod = config.with_profile("prod").unwrap();
+        assert_eq!(prod.shell, "ssh");
+        assert_eq!(prod.shell_args, ["prod"]);
+        assert_eq!(prod.color_scheme, "dracula");
+        assert_eq!(prod.env.get("A").map(String::as_str), Some("1"));
+        assert_eq!(prod.font, config.font);
+        assert!(config.with_profile("dev").is_none());
+
+        let typo = r#"{"prod": {"color_scheme": "draculla"}}"#;
+        assert!(Config::load(&[], &[("profiles".into(), typo.into())]).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let error = |key: &str, value: &str| match Config::load(&[], &[(key.into(), value.into())])
+        {
+            Ok(_) => String::new(),
+            Err(err) => err.to_string(),
+        };
+        assert_eq!(error("color_scheme", "dracula"), "");
+        assert!(error("colour_scheme", "dark")
+            .starts_with("Invalid config at `colour_scheme`: unknown field"));
+        assert!(
+            error("font.sise", "12").starts_with("Invalid config at `font.sise`: unknown field")
+        );
+        assert!(error("color_scheme", "nope").starts_with("Invalid config at `color_scheme`"));
+        assert!(error("link_patterns", r#"["ok", "(unclosed"]"#)
+            .starts_with("Invalid config at `link_patterns[1]`"));
+        assert!(error("keybindings", r#"{"ctrl+hyper+c": "Copy"}"#)
+            .starts_with("Invalid config at `keybindings`: Unknown modifier"));
+        assert!(error("window.opacity", "1.5").starts_with("Invalid config at `window.opacity`"));
+        assert!(
+            error("scroll_multiplier", "0").starts_with("Invalid config at `scroll_multiplier`")
+        );
+        assert!(error("colors", r##"{"mine": {"foreground": "red"}}"##)
+            .starts_with("Invalid config at `colors.mine.foreground`"));
+    }
+
+    #[test]
+    fn test_dimensions() {
+        assert_eq!(
+            Dimensions::try_from("120x35".to_string()).unwrap(),
+            Dimensions {
+                cols: 120,
+                rows: 35
+            }
+        );
+        assert_matches!(Dimensions::try_from("120".to_string()), Err(_));
+        assert_matches!(Dimensions::try_from("0x35".to_string()), Err(_));
+        assert_matches!(Dimensions::try_from("wide x 35".to_string()), Err(_));
+    }
 }
@@ -0,0 +1,249 @@
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::widget::{Operation, Tree};
+use iced::advanced::{mouse, overlay, Clipboard, Shell, Widget};
+use iced::event::{self, Event};
+use iced::{Element, Length, Point, Rectangle, Renderer, Size, Theme};
+
+/**
+ * Shows a menu over its content at a point, e.g. where the right mouse
+ * button was pressed. A click anywhere outside the menu dismisses it.
+ */
+pub struct ContextMenu<'a, Message> {
+    content: Element<'a, Message>,
+    /** Where the menu opens, in window coordinates, and the menu itself */
+    menu: Option<(Point, Element<'a, Message>)>,
+    on_dismiss: Message,
+}
+
+impl<'a, Message> ContextMenu<'a, Message> {
+    pub fn new(
+        content: impl Into<Element<'a, Message>>,
+        menu: Option<(Point, Element<'a, Message>)>,
+        on_dismiss: Message,
+    ) -> Self {
+        Self {
+            content: content.into(),
+            menu,
+            on_dismiss,
+        }
+    }
+}
+
+impl<'a, Message: Clone> Widget<Message, Renderer> for ContextMenu<'a, Message> {
+    fn children(&self) -> Vec<Tree> {
+        let mut children = vec![Tree::new(&self.content)];
+        children.extend(self.menu.as_ref().map(|(_, menu)| Tree::new(menu)));
+        children
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        match &self.menu {
+            Some((_, menu)) => tree.diff_children(&[&self.content, menu]),
+            None => tree.diff_children(std::slice::from_ref(&self.content)),
+        }
+    }
+
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.as_widget().layout(renderer, limits);
+        layout::Node::with_children(content.size(), vec![content])
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        self.content.as_widget().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+        );
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+    ) -> Option<overlay::Element<'b, Message, Renderer>> {
+        let Some((position, menu)) = &mut self.menu else {
+            return self.content.as_widget_mut().overlay(
+                &mut tree.children[0],
+                layout.children().next().unwrap(),
+                renderer,
+            );
+        };
+        Some(overlay::Element::new(
+            *position,
+            Box::new(Menu {
+                tree: &mut tree.children[1],
+                menu,
+                on_dismiss: self.on_dismiss.clone(),
+            }),
+        ))
+    }
+}
+
+impl<'a, Message: Clone + 'a> From<ContextMenu<'a, Message>> for Element<'a, Message> {
+    fn from(context_menu: ContextMenu<'a, Message>) -> Self {
+        Element::new(context_menu)
+    }
+}
+
+struct Menu<'a, 'b, Message> {
+    tree: &'b mut Tree,
+    menu: &'b mut Element<'a, Message>,
+    on_dismiss: Message,
+}
+
+impl<'a, 'b, Message: Clone> overlay::Overlay<Message, Renderer> for Menu<'a, 'b, Message> {
+    fn layout(&self, renderer: &Renderer, bounds: Size, position: Point) -> layout::Node {
+        let limits = layout::Limits::new(Size::ZERO, bounds);
+        let mut node = self.menu.as_widget().layout(renderer, &limits);
+        let size = node.size();
+        // Open up or to the left rather than off the edge of the window
+        let x = if position.x + size.width > bounds.width {
+            (position.x - size.width).max(0.0)
+        } else {
+            position.x
+        };
+        let y = if position.y + size.height > bounds.height {
+            (position.y - size.height).max(0.0)
+        } else {
+            position.y
+        };
+        node.move_to(Point::new(x, y));
+        node
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+    ) {
+        self.menu.as_widget().draw(
+            self.tree,
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            &layout.bounds(),
+        );
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        if let Event::Mouse(mouse::Event::ButtonPressed(_)) = event {
+            if !cursor.is_over(layout.bounds()) {
+                shell.publish(self.on_dismiss.clone());
+                return event::Status::Captured;
+            }
+        }
+        self.menu.as_widget_mut().on_event(
+            self.tree,
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            &layout.bounds(),
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.menu
+            .as_widget()
+            .mouse_interaction(self.tree, layout, cursor, viewport, renderer)
+    }
+}
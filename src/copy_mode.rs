@@ -0,0 +1,284 @@
+use crate::data::{is_word_character, DataComponent, Position};
+use crate::search::Search;
+use crate::selection::Selection;
+use std::ops::Range;
+
+/** Ways to move the cursor in copy mode, after vi's */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /** To the start of the next word */
+    WordForward,
+    /** To the start of this word, or the previous one if already there */
+    WordBackward,
+    LineStart,
+    LineEnd,
+    /** To the oldest line of history */
+    Top,
+    /** To the last line */
+    Bottom,
+}
+
+impl Motion {
+    /** The motion a key typed in copy mode stands for */
+    pub fn from_char(ch: char) -> Option<Self> {
+        Some(match ch {
+            'h' => Self::Left,
+            'l' => Self::Right,
+            'k' => Self::Up,
+            'j' => Self::Down,
+            'w' => Self::WordForward,
+            'b' => Self::WordBackward,
+            '0' => Self::LineStart,
+            '$' => Self::LineEnd,
+            'g' => Self::Top,
+            'G' => Self::Bottom,
+            _ => return None,
+        })
+    }
+}
+
+/**
+ * Words are runs of one class of characters, so `w` stops between `foo` and
+ * `.bar` unless `.` is one of the word characters
+ */
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+fn class(data: &DataComponent, position: &Position, word_characters: &str) -> Class {
+    let cell = data.line(position.row).get(position.col);
+    match cell.and_then(|cell| cell.grapheme.as_deref()?.chars().next()) {
+        Some(ch) if is_word_character(ch, word_characters) => Class::Word,
+        Some(ch) if !ch.is_whitespace() => Class::Punctuation,
+        _ => Class::Blank,
+    }
+}
+
+/** The rightmost column the cursor can be on in `row` */
+fn last_col(data: &DataComponent, row: usize) -> usize {
+    data.line(row).len().saturating_sub(1)
+}
+
+/** State of copy mode, where the keyboard moves a cursor through history to select text */
+pub struct CopyMode {
+    pub cursor: Position,
+    /** Where the selection started, while selecting */
+    pub anchor: Option<Position>,
+    /** The search last submitted from copy mode, which `n` and `N` step through */
+    pub search: Option<Search>,
+}
+
+impl CopyMode {
+    pub fn new(cursor: Position) -> Self {
+        Self {
+            cursor,
+            anchor: None,
+            search: None,
+        }
+    }
+
+    /** From the anchor to the cursor, while selecting */
+    pub fn selection(&self) -> Option<Selection> {
+        let mut selection = Selection::new(self.anchor.clone()?);
+        selection.head = self.cursor.clone();
+        Some(selection)
+    }
+
+    /** Start selecting at the cursor, or stop */
+    pub fn toggle_selection(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => Some(self.cursor.clone()),
+        };
+    }
+
+    /** The cell before or after `position` in reading order, staying within `rows` */
+    fn step(
+        data: &DataComponent,
+        position: &Position,
+        forward: bool,
+        rows: &Range<usize>,
+    ) -> Option<Position> {
+        let Position { row, col } = *position;
+        if forward {
+            if col < last_col(data, row) {
+                Some(Position { row, col: col + 1 })
+            } else if row + 1 < rows.end {
+                Some(Position {
+                    row: row + 1,
+                    col: 0,
+                })
+            } else {
+                None
+            }
+        } else if col > 0 {
+            Some(Position { row, col: col - 1 })
+        } else if row > rows.start {
+            Some(Position {
+                row: row - 1,
+                col: last_col(data, row - 1),
+            })
+        } else {
+            None
+        }
+    }
+
+    /** Move the cursor, keeping it within `rows`. Words are as `word_characters` say. */
+    pub fn move_cursor(
+        &mut self,
+        motion: Motion,
+        data: &DataComponent,
+        rows: Range<usize>,
+        word_characters: &str,
+    ) {
+        let Position { row, col } = self.cursor;
+        self.cursor = match motion {
+            Motion::Left => Position {
+                row,
+                col: col.saturating_sub(1),
+            },
+            Motion::Right => Position {
+                row,
+                col: (col + 1).min(last_col(data, row)),
+            },
+            Motion::Up if row > rows.start => Position {
+                row: row - 1,
+                col: col.min(last_col(data, row - 1)),
+            },
+            Motion::Down if row + 1 < rows.end => Position {
+                row: row + 1,
+                col: col.min(last_col(data, row + 1)),
+            },
+            Motion::Up | Motion::Down => return,
+            Motion::WordForward => self.next_word(data, &rows, word_characters),
+            Motion::WordBackward => self.previous_word(data, &rows, word_characters),
+            Motion::LineStart => Position { row, col: 0 },
+            Motion::LineEnd => Position {
+                row,
+                col: last_col(data, row),
+            },
+            Motion::Top => Position {
+                row: rows.start,
+                col: 0,
+            },
+            Motion::Bottom => Position {
+                row: rows.end.saturating_sub(1),
+                col: 0,
+            },
+        };
+    }
+
+    fn next_word(
+        &self,
+        data: &DataComponent,
+        rows: &Range<usize>,
+        word_characters: &str,
+    ) -> Position {
+        let mut position = self.cursor.clone();
+        let mut previous = class(data, &position, word_characters);
+        while let Some(next) = Self::step(data, &position, true, rows) {
+            // A line break separates words like a blank does
+            if next.row != position.row {
+                previous = Class::Blank;
+            }
+            let current = class(data, &next, word_characters);
+            position = next;
+            if current != Class::Blank && current != previous {
+                break;
+            }
+            previous = current;
+        }
+        position
+    }
+
+    fn previous_word(
+        &self,
+        data: &DataComponent,
+        rows: &Range<usize>,
+        word_characters: &str,
+    ) -> Position {
+        let mut position = self.cursor.clone();
+        // Back over any blanks to the end of a word, then to its start
+        loop {
+            let Some(previous) = Self::step(data, &position, false, rows) else {
+                return position;
+            };
+            position = previous;
+            if class(data, &position, word_characters) != Class::Blank {
+                break;
+            }
+        }
+        let word = class(data, &position, word_characters);
+        while let Some(previous) = Self::step(data, &position, false, rows) {
+            if previous.row != position.row || class(data, &previous, word_characters) != word {
+                break;
+            }
+            position = previous;
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::Translator;
+
+    fn position(row: usize, col: usize) -> Position {
+        Position { row, col }
+    }
+
+    #[test]
+    fn test_motions() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write("ls foo.bar\r\n\r\n  baz".as_bytes(), &mut data);
+        let rows = 0..data.line_count();
+        let mut copy_mode = CopyMode::new(position(0, 0));
+        let mut moves = |motion| {
+            copy_mode.move_cursor(motion, &data, rows.clone(), "_");
+            copy_mode.cursor.clone()
+        };
+
+        assert_eq!(moves(Motion::WordForward), position(0, 3));
+        assert_eq!(moves(Motion::WordForward), position(0, 6));
+        assert_eq!(moves(Motion::WordForward), position(0, 7));
+        assert_eq!(moves(Motion::WordForward), position(2, 2));
+        assert_eq!(moves(Motion::WordBackward), position(0, 7));
+        assert_eq!(moves(Motion::WordBackward), position(0, 6));
+        assert_eq!(moves(Motion::LineEnd), position(0, 9));
+        assert_eq!(moves(Motion::Right), position(0, 9));
+        assert_eq!(moves(Motion::Down), position(1, 0));
+        assert_eq!(moves(Motion::Bottom), position(2, 0));
+        assert_eq!(moves(Motion::Down), position(2, 0));
+        assert_eq!(moves(Motion::Top), position(0, 0));
+        assert_eq!(moves(Motion::Up), position(0, 0));
+
+        // With `.` a word character, `foo.bar` is a single word
+        let mut copy_mode = CopyMode::new(position(0, 0));
+        copy_mode.move_cursor(Motion::WordForward, &data, rows.clone(), "-_./~");
+        copy_mode.move_cursor(Motion::WordForward, &data, rows.clone(), "-_./~");
+        assert_eq!(copy_mode.cursor, position(2, 2));
+        copy_mode.move_cursor(Motion::WordBackward, &data, rows, "-_./~");
+        assert_eq!(copy_mode.cursor, position(0, 3));
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut copy_mode = CopyMode::new(position(1, 2));
+        assert_eq!(copy_mode.selection(), None);
+        copy_mode.toggle_selection();
+        copy_mode.cursor = position(0, 4);
+        let selection = copy_mode.selection().unwrap();
+        assert_eq!(selection.ordered(), (&position(0, 4), &position(1, 2)));
+        copy_mode.toggle_selection();
+        assert_eq!(copy_mode.selection(), None);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::tab::Tab;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/** Over how long the rate of messages from the pty is averaged */
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/** Timings behind the debug overlay, kept only while it is showing */
+#[derive(Default)]
+pub struct DebugOverlay {
+    /** When each message from the pty within the last `RATE_WINDOW` arrived */
+    messages: VecDeque<Instant>,
+    last_frame: Option<Instant>,
+    frame_time: Duration,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_message(&mut self, now: Instant) {
+        self.messages.push_back(now);
+        self.expire(now);
+    }
+
+    pub fn record_frame(&mut self, now: Instant) {
+        if let Some(last_frame) = self.last_frame {
+            self.frame_time = now - last_frame;
+        }
+        self.last_frame = Some(now);
+        self.expire(now);
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while self
+            .messages
+            .front()
+            .is_some_and(|time| now - *time > RATE_WINDOW)
+        {
+            self.messages.pop_front();
+        }
+    }
+
+    fn messages_per_second(&self) -> f32 {
+        self.messages.len() as f32 / RATE_WINDOW.as_secs_f32()
+    }
+
+    /** What the overlay shows about `tab`, one line each */
+    pub fn lines(&self, tab: &Tab) -> Vec<String> {
+        let position = tab.data.get_active_position();
+        let modes = tab.data.modes();
+        let modes: Vec<&str> = [
+            (modes.bracketed_paste, "bracketed-paste"),
+            (modes.application_cursor, "app-cursor"),
+            (modes.application_keypad, "app-keypad"),
+            (modes.color_scheme_updates, "color-scheme-updates"),
+            (modes.alternate_screen, "alt-screen"),
+            (modes.mouse_reporting, "mouse"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect();
+        let mut lines = vec![
+            format!("cursor     {},{}", position.row, position.col),
+            format!(
+                "modes      {}",
+                if modes.is_empty() {
+                    "none".into()
+                } else {
+                    modes.join(" ")
+                }
+            ),
+            // DECSTBM is ignored, so the whole screen always scrolls
+            "scroll     whole screen".into(),
+            format!("pending    {} bytes", tab.translator.pending_len()),
+            format!("pty        {:.0} messages/s", self.messages_per_second()),
+            format!(
+                "frame      {:.1} ms",
+                self.frame_time.as_secs_f32() * 1000.0
+            ),
+        ];
+        let mut recent = tab.data.stats().recent().peekable();
+        if recent.peek().is_some() {
+            lines.push("ignored".into());
+            lines.extend(recent.map(|sequence| format!("  {sequence}")));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates() {
+        let mut overlay = DebugOverlay::new();
+        let start = Instant::now();
+        for ms in [0, 100, 200, 300] {
+            overlay.record_message(start + Duration::from_millis(ms));
+        }
+        assert_eq!(overlay.messages_per_second(), 4.0);
+        overlay.record_frame(start + Duration::from_millis(1250));
+        assert_eq!(overlay.messages_per_second(), 1.0);
+        overlay.record_frame(start + Duration::from_millis(1266));
+        assert_eq!(overlay.frame_time, Duration::from_millis(16));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::bindings;
+use crate::config::{
+    AlternateScrollConfig, BellConfig, Config, FontConfig, MonitorConfig, SelectionConfig,
+    SessionLogConfig, Settings, WindowConfig,
+};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fmt::Write;
+
+/** How wide the generated file's comments may get */
+const WIDTH: usize = 80;
+
+/** The table of settings at `path`, or `None` for a table like `keybindings` that holds anything */
+fn settings_at(path: &str) -> Option<Settings> {
+    Some(match path {
+        "" => Config::SETTINGS,
+        "alternate_scroll" => AlternateScrollConfig::SETTINGS,
+        "bell" => BellConfig::SETTINGS,
+        "font" => FontConfig::SETTINGS,
+        "monitor" => MonitorConfig::SETTINGS,
+        "selection" => SelectionConfig::SETTINGS,
+        "session_log" => SessionLogConfig::SETTINGS,
+        "window" => WindowConfig::SETTINGS,
+        _ => return None,
+    })
+}
+
+/** A doc comment's text on one line, without the leading `*` of each line of a block comment */
+fn prose(doc: &[&str]) -> String {
+    let words: Vec<_> = doc
+        .iter()
+        .flat_map(|text| text.lines())
+        .map(|line| {
+            let line = line.trim();
+            line.strip_prefix('*').unwrap_or(line).trim()
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+    words.join(" ")
+}
+
+/** `path` within the table at `parent` */
+fn join(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{parent}.{name}")
+    }
+}
+
+/**
+ * The default config as TOML, each setting preceded by what it does.
+ * Settings that are unset by default are commented out.
+ */
+pub fn render() -> String {
+    let mut defaults = serde_json::to_value(Config::default()).unwrap();
+    // The config only holds changes to the default bindings, which are worth seeing here
+    defaults["keybindings"] = bindings::defaults()
+        .into_iter()
+        .map(|(combo, action)| (combo.to_owned(), serde_json::to_value(action).unwrap()))
+        .collect::<serde_json::Map<_, _>>()
+        .into();
+    let mut toml = String::from(
+        "# firn's configuration, with every setting at its default.\n\
+         # Generated by `firn default-config`.\n\n",
+    );
+    write_table(&mut toml, &defaults, "");
+    toml
+}
+
+fn write_table(toml: &mut String, table: &Value, path: &str) {
+    // A table without settings of its own, like `keybindings`, is written as it stands
+    let Some(settings) = settings_at(path) else {
+        for (key, value) in table.as_object().into_iter().flatten() {
+            write_value(toml, key, value);
+        }
+        return;
+    };
+    let mut sections = vec![];
+    for (name, doc) in settings.fields {
+        // Settings that are never read from the file, like `load_error`, are not serialized
+        let Some(value) = table.get(name) else {
+            continue;
+        };
+        // TOML puts a table's own settings before any table within it
+        if value.as_object().is_some_and(|object| !object.is_empty()) {
+            sections.push((name, doc));
+            continue;
+        }
+        write_comment(toml, &prose(doc));
+        write_value(toml, name, value);
+        toml.push('\n');
+    }
+    for (name, doc) in sections {
+        let path = join(path, name);
+        // A table of settings is described by its own doc comment
+        let doc = settings_at(&path).map_or(*doc, |settings| settings.doc);
+        write_comment(toml, &prose(doc));
+        let _ = writeln!(toml, "[{path}]");
+        write_table(toml, &table[*name], &path);
+        if !toml.ends_with("\n\n") {
+            toml.push('\n');
+        }
+    }
+}
+
+fn write_value(toml: &mut String, key: &str, value: &Value) {
+    let key = if key
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+    {
+        key.to_owned()
+    } else {
+        toml::Value::String(key.into()).to_string()
+    };
+    // TOML has no null to write unset settings as
+    match toml::Value::try_from(value) {
+        Ok(value) => {
+            let _ = writeln!(toml, "{key} = {value}");
+        }
+        Err(_) => {
+            let _ = writeln!(toml, "# {key} = (unset)");
+        }
+    }
+}
+
+/** `doc` as comment lines, broken between words to fit in `WIDTH` */
+fn write_comment(toml: &mut String, doc: &str) {
+    let mut line = String::from("#");
+    for word in doc.split(' ') {
+        if line.len() > 1 && line.len() + 1 + word.len() > WIDTH {
+            let _ = writeln!(toml, "{line}");
+            line = String::from("#");
+        }
+        line.push(' ');
+        line.push_str(word);
+    }
+    let _ = writeln!(toml, "{line}");
+}
+
+/** Write the default config to the user's config file, unless there is one already */
+pub fn write() -> Result<()> {
+    let path = Config::user_path().context("Nowhere to write the config: HOME is not set")?;
+    if path.exists() {
+        bail!("{path:?} already exists");
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Unable to create {dir:?}"))?;
+    }
+    std::fs::write(&path, render()).with_context(|| format!("Unable to write {path:?}"))?;
+    println!("Wrote the default config to {path:?}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Layer;
+
+    #[test]
+    fn test_render() {
+        let toml = render();
+        assert!(toml.contains("# Multiplies the height of each row\nline_height = 1.0\n"));
+        assert!(toml.contains("# second of starting\nexit_behavior = \"close\"\n"));
+        assert!(toml.contains("# family = (unset)\n"));
+        assert!(toml.contains("\n[window]\n"));
+        assert!(toml
+            .contains("# How long a shell gets to exit after being hung up before it is killed\n"));
+        assert!(toml.contains("# same message\ncoalesce_ms = 4\n"));
+        assert!(toml.contains("\n[bell]\n"));
+        assert!(toml.contains("\n[keybindings]\n"));
+        assert!(toml.contains("\n\"ctrl+shift+c\" = \"Copy\"\n"));
+        assert!(toml
+            .lines()
+            .all(|line| line.len() <= WIDTH || !line.starts_with('#')));
+
+        let path =
+            std::env::temp_dir().join(format!("firn-test-render-{}.toml", std::process::id()));
+        std::fs::write(&path, &toml).unwrap();
+        let config = Config::load(&[Layer::required(path.clone())], &[]);
+        std::fs::remove_file(&path).unwrap();
+        let keybindings = bindings::defaults()
+            .into_iter()
+            .map(|(combo, action)| (combo.to_owned(), action))
+            .collect();
+        assert_eq!(
+            serde_json::to_value(config.unwrap()).unwrap(),
+            serde_json::to_value(Config {
+                keybindings,
+                ..Config::default()
+            })
+            .unwrap()
+        );
+    }
+
+    /** Every setting `Config` serializes has a doc comment to annotate it with */
+    #[test]
+    fn test_docs() {
+        fn check(table: &Value, path: &str) {
+            let Some(settings) = settings_at(path) else {
+                return;
+            };
+            for (key, value) in table.as_object().unwrap() {
+                let path = join(path, key);
+                let doc = match settings_at(&path) {
+                    Some(settings) => settings.doc,
+                    None => settings
+                        .fields
+                        .iter()
+                        .find(|(name, _)| name == key)
+                        .map(|(_, doc)| *doc)
+                        .unwrap(),
+                };
+                assert!(!prose(doc).is_empty(), "{path} has no doc comment");
+                check(value, &path);
+            }
+        }
+        check(&serde_json::to_value(Config::default()).unwrap(), "");
+    }
+}
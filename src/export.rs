@@ -0,0 +1,191 @@
+use crate::colors;
+use crate::data::Cell;
+use crate::grid_view::GridView;
+use crate::pen::Attributes;
+use anyhow::Result;
+use iced::font::{Family, Weight};
+use iced::window::Screenshot;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/** What Ctrl+Shift+S saves the grid as */
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /** Exactly what is on screen, pixel for pixel */
+    #[default]
+    Png,
+    /** The text and its colors, drawn in the same font by whatever displays it */
+    Svg,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+        }
+    }
+}
+
+/** A fresh file name in `dir` to export to, going by the time */
+pub fn path(dir: &Path, format: ExportFormat) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    dir.join(format!("firn-{seconds}.{}", format.extension()))
+}
+
+/** Encode a screenshot of the window, already cropped to the grid, as a PNG */
+pub fn png(screenshot: &Screenshot) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, screenshot.size.width, screenshot.size.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()?
+        .write_image_data(&screenshot.bytes)?;
+    Ok(bytes)
+}
+
+fn hex(color: iced::Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/**
+ * The rows of `grid` in view as an SVG document, or only the selected cells
+ * if there is a selection. Cells with the same attributes are drawn as one
+ * run of text, stretched to the width of the grid's columns.
+ */
+pub fn svg<Message>(grid: &GridView<Message>) -> String {
+    let rows = selected_rows(grid);
+    let metrics = grid.metrics;
+    let columns = rows
+        .clone()
+        .map(|row| grid.data.line(row).len())
+        .max()
+        .unwrap_or_default();
+    let width = columns as f32 * metrics.width;
+    let height = rows.len() as f32 * metrics.height;
+    let family = match grid.font.family {
+        Family::Name(name) => name,
+        _ => "monospace",
+    };
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="{}" font-size="{}" xml:space="preserve">"#,
+        escape(family),
+        grid.font_size,
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        hex(colors::color(grid.colors.background))
+    );
+    for (y, row) in rows.clone().enumerate() {
+        let line = grid.data.line(row);
+        let selected = match grid.selection {
+            Some(selection) => selection.columns(row, line.len()),
+            None => 0..line.len(),
+        };
+        let y = y as f32 * metrics.height;
+        for (run, attributes) in runs(&line[selected.clone()]) {
+            let cells = &line[selected.start + run.start..selected.start + run.end];
+            let x = (selected.start + run.start) as f32 * metrics.width;
+            let run_width = cells.len() as f32 * metrics.width;
+            let (foreground, background) = grid.cell_colors(&attributes);
+            if background != colors::color(grid.colors.background) {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{x}" y="{y}" width="{run_width}" height="{}" fill="{}"/>"#,
+                    metrics.height,
+                    hex(background)
+                );
+            }
+            let text: String = cells.iter().map(Cell::text).collect();
+            if attributes.hidden || text.trim().is_empty() {
+                continue;
+            }
+            let mut style = String::new();
+            if grid.font(&attributes).weight == Weight::Bold {
+                style += r#" font-weight="bold""#;
+            }
+            if attributes.italic {
+                style += r#" font-style="italic""#;
+            }
+            match (attributes.underline, attributes.strikethrough) {
+                (true, true) => style += r#" text-decoration="underline line-through""#,
+                (true, false) => style += r#" text-decoration="underline""#,
+                (false, true) => style += r#" text-decoration="line-through""#,
+                (false, false) => {}
+            }
+            if foreground.a < 1.0 {
+                let _ = write!(style, r#" fill-opacity="{}""#, foreground.a);
+            }
+            let _ = writeln!(
+                svg,
+                r#"<text x="{x}" y="{}" dominant-baseline="central" textLength="{run_width}" lengthAdjust="spacingAndGlyphs" fill="{}"{style}>{}</text>"#,
+                y + metrics.height / 2.0,
+                hex(foreground),
+                escape(&text)
+            );
+        }
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+/** The rows in view, narrowed down to those with selected cells if there is a selection */
+pub fn selected_rows<Message>(grid: &GridView<Message>) -> Range<usize> {
+    let rows = grid.visible_rows();
+    let Some(selection) = grid.selection else {
+        return rows;
+    };
+    let (start, end) = selection.ordered();
+    let first = start.row.clamp(rows.start, rows.end);
+    let last = (end.row + 1).clamp(first, rows.end);
+    first..last
+}
+
+/** Split cells into runs with the same attributes */
+fn runs(cells: &[Cell]) -> Vec<(Range<usize>, Attributes)> {
+    let mut runs: Vec<(Range<usize>, Attributes)> = vec![];
+    for (col, cell) in cells.iter().enumerate() {
+        match runs.last_mut() {
+            Some((run, attributes)) if *attributes == cell.attributes => run.end = col + 1,
+            _ => runs.push((col..col + 1, cell.attributes)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataComponent;
+    use crate::translator::Translator;
+
+    #[test]
+    fn test_runs() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"ab\x1B[1mcd\x1B[0me", &mut data);
+        let runs: Vec<_> = runs(data.line(0))
+            .into_iter()
+            .map(|(run, attributes)| (run, attributes.bold))
+            .collect();
+        assert_eq!(runs, vec![(0..2, false), (2..4, true), (4..5, false)]);
+        assert_eq!(escape("<a & b>"), "&lt;a &amp; b&gt;");
+    }
+}
@@ -0,0 +1,183 @@
+use crate::metrics::ADVANCE_RATIO;
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::text::{Renderer as _, Shaping};
+use iced::advanced::widget::{Operation, Tree};
+use iced::advanced::{mouse, overlay, Clipboard, Shell, Widget};
+use iced::event::{self, Event};
+use iced::{window, Element, Font, Length, Rectangle, Renderer, Theme};
+
+/** Measured as a whole, so that rounding in any one glyph's advance averages out */
+const SAMPLE: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/** Differences in the measured advance smaller than this are put down to rounding */
+const TOLERANCE: f32 = 0.001;
+
+/**
+ * Measures how far each character of the grid's font advances, relative to its
+ * size, and reports it whenever it differs from what the app last heard. Only
+ * the renderer knows this, once the font has loaded, and iced hands the
+ * renderer to widgets rather than to the app, hence this wrapper.
+ */
+pub struct FontProbe<'a, Message> {
+    font: Font,
+    size: f32,
+    /** The advance the app already knows of */
+    known: Option<f32>,
+    on_measure: fn(f32) -> Message,
+    content: Element<'a, Message>,
+}
+
+impl<'a, Message> FontProbe<'a, Message> {
+    pub fn new(
+        content: impl Into<Element<'a, Message>>,
+        font: Font,
+        size: f32,
+        known: Option<f32>,
+        on_measure: fn(f32) -> Message,
+    ) -> Self {
+        Self {
+            font,
+            size,
+            known,
+            on_measure,
+            content: content.into(),
+        }
+    }
+
+    fn measure(&self, renderer: &Renderer) -> Option<f32> {
+        let width = renderer.measure_width(SAMPLE, self.size, self.font, Shaping::Basic);
+        let advance = width / SAMPLE.len() as f32 / self.size;
+        (advance.is_finite() && advance > 0.0).then_some(advance)
+    }
+}
+
+impl<'a, Message> Widget<Message, Renderer> for FontProbe<'a, Message> {
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content))
+    }
+
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.as_widget().layout(renderer, limits);
+        layout::Node::with_children(content.size(), vec![content])
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        self.content.as_widget().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        // Every frame starts with this event, so a font that finished loading is noticed
+        if let Event::Window(window::Event::RedrawRequested(_)) = event {
+            // A font that cannot be measured still has to be reported, as the shells wait for it
+            let advance = self.measure(renderer).unwrap_or(ADVANCE_RATIO);
+            if self
+                .known
+                .is_none_or(|known| (known - advance).abs() > TOLERANCE)
+            {
+                shell.publish((self.on_measure)(advance));
+                self.known = Some(advance);
+            }
+        }
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+        );
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+    ) -> Option<overlay::Element<'b, Message, Renderer>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+        )
+    }
+}
+
+impl<'a, Message: 'a> From<FontProbe<'a, Message>> for Element<'a, Message> {
+    fn from(probe: FontProbe<'a, Message>) -> Self {
+        Element::new(probe)
+    }
+}
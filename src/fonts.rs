@@ -0,0 +1,42 @@
+use crate::config::FontConfig;
+use iced::font::{self, Family};
+use iced::{Command, Font};
+use tracing::error;
+
+/**
+ * The font to draw the grid in. iced wants family names to be `'static`, so
+ * this leaks the configured one and should only be called once for each.
+ */
+pub fn primary(config: &FontConfig) -> Font {
+    match &config.family {
+        Some(family) => Font {
+            family: Family::Name(Box::leak(family.clone().into_boxed_str())),
+            monospaced: true,
+            ..Font::DEFAULT
+        },
+        None => Font::MONOSPACE,
+    }
+}
+
+/**
+ * Load the fallback font files into iced. Text is shaped with per-glyph
+ * fallback, so glyphs the primary font lacks are then found in these before
+ * the system's fonts. Files that cannot be read are skipped.
+ */
+pub fn load_fallbacks<Message: 'static>(
+    config: &FontConfig,
+    on_load: fn(Result<(), font::Error>) -> Message,
+) -> Command<Message> {
+    Command::batch(
+        config
+            .fallback
+            .iter()
+            .filter_map(|path| match std::fs::read(path) {
+                Ok(bytes) => Some(font::load(bytes).map(on_load)),
+                Err(err) => {
+                    error!("Unable to read font {path:?}: {err}");
+                    None
+                }
+            }),
+    )
+}
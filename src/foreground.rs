@@ -0,0 +1,54 @@
+/**
+ * Name of the process in the foreground of a shell's terminal, unless that
+ * is the shell itself, i.e. it is waiting at its prompt. Read from `/proc`,
+ * so other platforms never report one.
+ */
+pub fn foreground_process(shell: u32) -> Option<String> {
+    let group = foreground_leader(shell)?;
+    let name = std::fs::read_to_string(format!("/proc/{group}/comm")).ok()?;
+    Some(name.trim_end().to_owned())
+}
+
+/** The arguments the foreground process, as `foreground_process` finds it, was started with */
+pub fn foreground_command(shell: u32) -> Option<Vec<String>> {
+    let group = foreground_leader(shell)?;
+    let cmdline = std::fs::read(format!("/proc/{group}/cmdline")).ok()?;
+    // Each argument ends in a NUL
+    let cmdline = cmdline
+        .strip_suffix(&[0])
+        .filter(|cmdline| !cmdline.is_empty())?;
+    let args = cmdline.split(|&byte| byte == 0);
+    Some(
+        args.map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect(),
+    )
+}
+
+/** The leader of the shell terminal's foreground process group, unless it is the shell */
+fn foreground_leader(shell: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{shell}/stat")).ok()?;
+    foreground_group(&stat).filter(|&group| group != shell)
+}
+
+/**
+ * The terminal's foreground process group (`tpgid`) from a `/proc/<pid>/stat`
+ * line. The command name before it is in parentheses and may contain spaces.
+ */
+fn foreground_group(stat: &str) -> Option<u32> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(5)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_foreground_group() {
+        let stat = "4242 (my shell) S 4200 4242 4242 34816 4300 4194304 1234 0 0 0";
+        assert_eq!(foreground_group(stat), Some(4300));
+        // No terminal at all
+        assert_eq!(foreground_group("1 (init) S 0 1 1 0 -1 4194560"), None);
+        assert_eq!(foreground_group("garbage"), None);
+    }
+}
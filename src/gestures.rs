@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+/** How often a flick scrolls on */
+pub const FLICK_INTERVAL: Duration = Duration::from_millis(16);
+
+/** Slowest a finger may scroll, in lines per second, to flick */
+const MIN_FLICK_SPEED: f32 = 4.0;
+
+/** Speed at which a flick has come to rest, in lines per second */
+const REST_SPEED: f32 = 1.0;
+
+/** How much of its speed a flick still has after a second */
+const FLICK_DECAY: f32 = 0.1;
+
+/** A finger that rests for longer than this before it lifts does not flick */
+const FLICK_RELEASE: Duration = Duration::from_millis(80);
+
+/** How fast a finger dragging the grid scrolls it, to carry on once it lifts */
+#[derive(Default)]
+pub struct Drag {
+    /** In lines per second, back into history if positive */
+    velocity: f32,
+    last_move: Option<Instant>,
+}
+
+impl Drag {
+    /** The finger moved by `lines` at `now` */
+    pub fn moved(&mut self, lines: f32, now: Instant) {
+        if let Some(last_move) = self.last_move {
+            let elapsed = now.duration_since(last_move).as_secs_f32();
+            if elapsed > 0.0 {
+                // Touch events come unevenly, so average out the jitter
+                self.velocity = (self.velocity + lines / elapsed) / 2.0;
+            }
+        }
+        self.last_move = Some(now);
+    }
+
+    /** The speed to flick at as the finger lifts at `now`, unless it stopped or was too slow */
+    pub fn release(&mut self, now: Instant) -> Option<f32> {
+        let drag = std::mem::take(self);
+        let moving = drag
+            .last_move
+            .is_some_and(|last_move| now.duration_since(last_move) < FLICK_RELEASE);
+        (moving && drag.velocity.abs() >= MIN_FLICK_SPEED).then_some(drag.velocity)
+    }
+}
+
+/** Scrolling that carries on after a flick, slowing down until it comes to rest */
+pub struct Flick {
+    velocity: f32,
+    last_step: Instant,
+    /** Scrolling that has not added up to a whole line yet */
+    remainder: f32,
+}
+
+impl Flick {
+    pub fn new(velocity: f32, now: Instant) -> Self {
+        Self {
+            velocity,
+            last_step: now,
+            remainder: 0.0,
+        }
+    }
+
+    /** Lines to scroll by at `now`, or `None` once the flick has come to rest */
+    pub fn step(&mut self, now: Instant) -> Option<isize> {
+        let elapsed = now.saturating_duration_since(self.last_step).as_secs_f32();
+        self.last_step = now;
+        self.remainder += self.velocity * elapsed;
+        self.velocity *= FLICK_DECAY.powf(elapsed);
+        if self.velocity.abs() < REST_SPEED {
+            return None;
+        }
+        let whole = self.remainder.trunc();
+        self.remainder -= whole;
+        Some(whole as isize)
+    }
+}
+
+/**
+ * The font size for a pinch that started at `font_size` with the fingers
+ * `start` apart, now that they are `distance` apart
+ */
+pub fn pinch_zoom(font_size: f32, start: f32, distance: f32) -> f32 {
+    font_size * distance / start.max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flick() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut drag = Drag::default();
+        drag.moved(1.0, at(0));
+        drag.moved(1.0, at(20));
+        drag.moved(1.0, at(40));
+        // Held still before lifting
+        assert_eq!(drag.release(at(500)), None);
+
+        drag.moved(-1.0, at(600));
+        drag.moved(-1.0, at(620));
+        let velocity = drag.release(at(630)).unwrap();
+        assert!((velocity + 25.0).abs() < 0.1, "{velocity}");
+
+        let mut flick = Flick::new(velocity, at(630));
+        let mut scrolled = 0;
+        let mut millis = 630;
+        while let Some(lines) = flick.step(at(millis)) {
+            assert!(lines <= 0);
+            scrolled += lines;
+            millis += 16;
+        }
+        assert!((-12..-8).contains(&scrolled), "{scrolled}");
+        assert!(millis < 2500, "{millis}");
+    }
+
+    #[test]
+    fn test_pinch_zoom() {
+        assert_eq!(pinch_zoom(12.0, 100.0, 150.0), 18.0);
+        assert_eq!(pinch_zoom(12.0, 0.0, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,956 @@
+use crate::bidi;
+use crate::colors::{self, ColorScheme};
+use crate::config::{CursorShape, ScrollbarVisibility};
+use crate::data::{Cell, CellWidth, DataComponent, Position};
+use crate::gestures::{self, Drag};
+use crate::hints::Hints;
+use crate::links::Link;
+use crate::metrics::{CellMetrics, ZOOM_STEP};
+use crate::pen::{Attributes, Color};
+use crate::scrollbar::{self, Thumb};
+use crate::search::SearchMatch;
+use crate::selection::{Selection, SelectionUnit};
+use iced::alignment::{Horizontal, Vertical};
+use iced::font::Weight;
+use iced::keyboard;
+use iced::mouse::{self, Cursor};
+use iced::touch;
+use iced::widget::canvas::{self, event, Frame, Geometry, Path, Program, Stroke, Text};
+use iced::widget::text::{LineHeight, Shaping};
+use iced::{Font, Point, Rectangle, Renderer, Size, Theme, Vector};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use tracing::debug_span;
+
+/**
+ * Draws the grid cell by cell, so that each cell lands exactly where the
+ * pty's notion of rows and columns puts it and carries its own colors and
+ * styles
+ */
+pub struct GridView<'a, Message> {
+    pub data: &'a DataComponent,
+    pub metrics: CellMetrics,
+    pub font: Font,
+    pub font_size: f32,
+    pub colors: &'a ColorScheme,
+    /** Brighten the standard colors of bold text */
+    pub bold_as_bright: bool,
+    /** Draw bold text in the font's bold weight */
+    pub bold_weight: bool,
+    /** How many rows fit into the window */
+    pub rows: usize,
+    /** How many lines back into history the viewport is scrolled, 0 being the live screen */
+    pub scrollback: usize,
+    /** Lines of output that arrived below the view since it was scrolled back */
+    pub new_lines: usize,
+    /** Lines the user marked, shown as ticks on the scrollbar */
+    pub marks: Vec<usize>,
+    pub max_scrollback: usize,
+    pub scrollbar: ScrollbarVisibility,
+    /** Holds the drawn rows until the owner clears it because something was damaged */
+    pub cache: &'a canvas::Cache,
+    pub cursor_shape: CursorShape,
+    /** False during the "off" phase of blinking */
+    pub cursor_visible: bool,
+    /** An unfocused window shows a hollow cursor instead */
+    pub focused: bool,
+    pub selection: Option<&'a Selection>,
+    /** Matches of the open search, highlighted over the text */
+    pub search_matches: &'a [SearchMatch],
+    /** Index of the match last jumped to, which stands out from the rest */
+    pub current_match: Option<usize>,
+    /** Links among the rows in view */
+    pub links: Vec<Link>,
+    /** Labels to draw over the grid in hints mode */
+    pub hints: Option<&'a Hints>,
+    /** Where the keyboard-driven cursor is, in copy mode */
+    pub copy_cursor: Option<&'a Position>,
+    /** Shade the whole grid, as the visual bell */
+    pub flash: bool,
+    /** Show right-to-left text in visual order, unless the program orders it itself */
+    pub bidi: bool,
+    /** Diagnostics to show in a box at the top right, if the debug overlay is on */
+    pub debug_lines: Vec<String>,
+    pub on_selection: fn(SelectionEvent) -> Message,
+    /** Sent on middle click */
+    pub on_paste: Message,
+    /** Sent with the number of lines to scroll back, or forward if negative */
+    pub on_scroll: fn(isize) -> Message,
+    /** Sent on the mouse wheel, with the lines it scrolls up, or down if negative */
+    pub on_wheel: fn(isize) -> Message,
+    /** Multiplies how far the wheel and touchpad scroll */
+    pub scroll_multiplier: f32,
+    /**
+     * Sent when a finger flicks the grid, with the lines per second to keep
+     * scrolling at, and with 0 when a finger lands to stop that
+     */
+    pub on_flick: fn(f32) -> Message,
+    /** Sent on Ctrl+wheel, or on pinching, with the font size to zoom to */
+    pub on_zoom: fn(f32) -> Message,
+    pub on_open_link: fn(String) -> Message,
+    /** Sent on right click with the pointer's position in the window and the link it is over */
+    pub on_context_menu: fn(Point, Option<String>) -> Message,
+}
+
+/** Lines scrolled per notch of a mouse wheel */
+const WHEEL_LINES: f32 = 3.0;
+
+/** Smallest change of font size a pinch zooms by, so that not every touch event zooms */
+const PINCH_STEP: f32 = 0.5;
+
+/** How thick the scrollbar's ticks for marked lines are */
+const MARK_TICK_HEIGHT: f32 = 2.0;
+
+/** Longest pause between the clicks of a double or triple click */
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/** What the mouse is doing to the selection */
+#[derive(Debug, Clone)]
+pub enum SelectionEvent {
+    /** A click, with a double click selecting words and a triple click lines */
+    Started(Position, SelectionUnit),
+    Extended(Position),
+    Finished,
+}
+
+#[derive(Default)]
+pub struct GridState {
+    selecting: bool,
+    /** When and where the last click landed and which of a run of clicks it was */
+    last_click: Option<(Instant, Position, SelectionUnit)>,
+    /** Mouse events do not say which modifiers are held */
+    modifiers: keyboard::Modifiers,
+    /** While dragging the scrollbar, how far below the thumb's top it was grabbed */
+    scrollbar_grab: Option<f32>,
+    /**
+     * Scrolling that has not added up to a whole line yet, since touchpads
+     * scroll a few pixels at a time
+     */
+    wheel_remainder: f32,
+    /** Fingers on the touchscreen, and where they are */
+    fingers: Vec<(touch::Finger, Point)>,
+    /** While two fingers pinch, the font size and how far apart they were when they landed */
+    pinch: Option<(f32, f32)>,
+    /** How fast a single finger is scrolling */
+    drag: Drag,
+}
+
+impl GridState {
+    /** Add to the scrolling still to do, and take the whole lines out of it */
+    fn whole_lines(&mut self, lines: f32) -> isize {
+        // What was left over from scrolling the other way no longer counts
+        if lines * self.wheel_remainder < 0.0 {
+            self.wheel_remainder = 0.0;
+        }
+        self.wheel_remainder += lines;
+        let whole = self.wheel_remainder.trunc();
+        self.wheel_remainder -= whole;
+        whole as isize
+    }
+}
+
+impl<'a, Message> GridView<'a, Message> {
+    /** The line shown in the top row */
+    fn first_line(&self) -> usize {
+        self.data
+            .line_count()
+            .saturating_sub(self.rows + self.scrollback)
+    }
+
+    /** The cell under a point relative to the top left of the grid */
+    fn position_at(&self, point: Point) -> Position {
+        let row = self.first_line() + (point.y / self.metrics.height).max(0.0) as usize;
+        let col = (point.x / self.metrics.width).max(0.0) as usize;
+        let col = match self.visual_order(row) {
+            Some(order) => bidi::logical_column(&order, col),
+            None => col,
+        };
+        Position { row, col }
+    }
+
+    /** Where each cell of a line is shown, if bidi is on and the line has right-to-left text */
+    fn visual_order(&self, row: usize) -> Option<Vec<usize>> {
+        if !self.bidi || row >= self.data.line_count() {
+            return None;
+        }
+        bidi::visual_columns(self.data.line(row))
+    }
+
+    /** The left edge of a cell, given its line's visual order */
+    fn cell_x(&self, order: Option<&[usize]>, col: usize) -> f32 {
+        let col = order
+            .and_then(|order| order.get(col))
+            .copied()
+            .unwrap_or(col);
+        col as f32 * self.metrics.width
+    }
+
+    /**
+     * The spans, as left edge and width, that some of a row's cells cover on
+     * screen. Reordered right-to-left text may split them up.
+     */
+    fn spans(&self, row: usize, columns: Range<usize>) -> Vec<(f32, f32)> {
+        match self.visual_order(row) {
+            Some(order) => columns
+                .map(|col| (self.cell_x(Some(&order), col), self.metrics.width))
+                .collect(),
+            None => vec![(
+                columns.start as f32 * self.metrics.width,
+                columns.len() as f32 * self.metrics.width,
+            )],
+        }
+    }
+
+    /** The link under the mouse pointer, if any */
+    fn hovered_link(&self, state: &GridState, bounds: Rectangle, cursor: Cursor) -> Option<&Link> {
+        let point = cursor.position_in(bounds)?;
+        if self.over_scrollbar(state, bounds, cursor) {
+            return None;
+        }
+        let position = self.position_at(point);
+        self.links
+            .iter()
+            .find(|link| link.row == position.row && link.columns.contains(&position.col))
+    }
+
+    fn draw_link_underline(&self, frame: &mut Frame, link: &Link) {
+        let thickness = (self.font_size / 16.0).max(1.0);
+        let y = (link.row - self.first_line() + 1) as f32 * self.metrics.height - 2.0 * thickness;
+        for (x, width) in self.spans(link.row, link.columns.clone()) {
+            frame.fill_rectangle(
+                Point::new(x, y),
+                Size::new(width, thickness),
+                colors::color(self.colors.foreground),
+            );
+        }
+    }
+
+    /** Shade some of a row's cells, if the row is in view */
+    fn highlight(&self, frame: &mut Frame, row: usize, columns: Range<usize>, color: iced::Color) {
+        if columns.is_empty() || !self.visible_rows().contains(&row) {
+            return;
+        }
+        let y = (row - self.first_line()) as f32 * self.metrics.height;
+        for (x, width) in self.spans(row, columns) {
+            frame.fill_rectangle(
+                Point::new(x, y),
+                Size::new(width, self.metrics.height),
+                color,
+            );
+        }
+    }
+
+    /** Selected cells are drawn again over the cached grid, in the selection's colors */
+    fn draw_selection(&self, frame: &mut Frame, selection: &Selection) {
+        let first_line = self.first_line();
+        for row in self.visible_rows() {
+            let line = self.data.line(row);
+            let order = self.visual_order(row);
+            for col in selection.columns(row, line.len()) {
+                let cell = &line[col];
+                if cell.width == CellWidth::Spacer {
+                    continue;
+                }
+                let (foreground, background) = self.cell_colors(&cell.attributes);
+                let position = Point::new(
+                    self.cell_x(order.as_deref(), col),
+                    (row - first_line) as f32 * self.metrics.height,
+                );
+                frame.fill_rectangle(
+                    position,
+                    self.cell_size(cell),
+                    self.colors.selection.or(foreground),
+                );
+                self.draw_content(
+                    frame,
+                    cell,
+                    position,
+                    self.colors.selection_text.or(background),
+                );
+            }
+        }
+    }
+
+    fn draw_search_matches(&self, frame: &mut Frame, theme: &Theme) {
+        let mut color = theme.palette().success;
+        for (index, found) in self.search_matches.iter().enumerate() {
+            color.a = if Some(index) == self.current_match {
+                0.7
+            } else {
+                0.3
+            };
+            self.highlight(frame, found.row, found.columns.clone(), color);
+        }
+    }
+
+    /** An outline, so that the text under the copy mode cursor and the terminal's own stay apart */
+    fn draw_copy_cursor(&self, frame: &mut Frame, position: &Position, theme: &Theme) {
+        if !self.visible_rows().contains(&position.row) {
+            return;
+        }
+        let thickness = (self.font_size / 8.0).max(1.0);
+        let order = self.visual_order(position.row);
+        let path = Path::rectangle(
+            Point::new(
+                self.cell_x(order.as_deref(), position.col) + thickness / 2.0,
+                (position.row - self.first_line()) as f32 * self.metrics.height + thickness / 2.0,
+            ),
+            Size::new(
+                self.metrics.width - thickness,
+                self.metrics.height - thickness,
+            ),
+        );
+        frame.stroke(
+            &path,
+            Stroke::default()
+                .with_color(theme.palette().primary)
+                .with_width(thickness),
+        );
+    }
+
+    /** Shade each hint still in the running and put what is left of its label at its start */
+    fn draw_hints(&self, frame: &mut Frame, hints: &Hints, theme: &Theme) {
+        let palette = theme.palette();
+        let mut shade = palette.primary;
+        shade.a = 0.3;
+        for hint in hints.remaining() {
+            let link = &hint.link;
+            if !self.visible_rows().contains(&link.row) {
+                continue;
+            }
+            self.highlight(frame, link.row, link.columns.clone(), shade);
+            let label = &hint.label[hints.typed.len()..];
+            let position = Point::new(
+                link.columns.start as f32 * self.metrics.width,
+                (link.row - self.first_line()) as f32 * self.metrics.height,
+            );
+            frame.fill_rectangle(
+                position,
+                Size::new(label.len() as f32 * self.metrics.width, self.metrics.height),
+                palette.primary,
+            );
+            frame.fill_text(Text {
+                content: label.to_owned(),
+                position,
+                color: palette.background,
+                size: self.font_size,
+                line_height: LineHeight::Absolute(self.metrics.height.into()),
+                font: Font {
+                    weight: Weight::Bold,
+                    ..self.font
+                },
+                ..Text::default()
+            });
+        }
+    }
+
+    /** The lines the viewport shows */
+    pub fn visible_rows(&self) -> Range<usize> {
+        let first_line = self.first_line();
+        first_line..(first_line + self.rows).min(self.data.line_count())
+    }
+
+    /** Where the scrollbar's thumb is, if it is showing */
+    fn thumb(&self, state: &GridState, bounds: Rectangle, cursor: Cursor) -> Option<Thumb> {
+        let shown = match self.scrollbar {
+            ScrollbarVisibility::Always => true,
+            ScrollbarVisibility::Auto => {
+                self.scrollback > 0
+                    || state.scrollbar_grab.is_some()
+                    || cursor
+                        .position_in(bounds)
+                        .is_some_and(|point| on_scrollbar(point, bounds))
+            }
+            ScrollbarVisibility::Never => false,
+        };
+        if !shown {
+            return None;
+        }
+        Thumb::new(
+            bounds.height,
+            self.rows,
+            self.max_scrollback,
+            self.scrollback,
+        )
+    }
+
+    /** Whether the pointer is over the scrollbar, and it is showing there */
+    fn over_scrollbar(&self, state: &GridState, bounds: Rectangle, cursor: Cursor) -> bool {
+        cursor
+            .position_in(bounds)
+            .is_some_and(|point| on_scrollbar(point, bounds))
+            && self.thumb(state, bounds, cursor).is_some()
+    }
+
+    /** Scroll so that the thumb's top moves to `top` */
+    fn drag_scrollbar(&self, thumb: Thumb, top: f32, bounds: Rectangle) -> Message {
+        let scrollback = thumb.scrollback_at(top, bounds.height, self.max_scrollback);
+        (self.on_scroll)(scrollback as isize - self.scrollback as isize)
+    }
+
+    fn draw_scrollbar(&self, frame: &mut Frame, thumb: Thumb, dragging: bool, theme: &Theme) {
+        let mut color = theme.palette().text;
+        color.a = 0.1;
+        let left = frame.width() - scrollbar::WIDTH;
+        frame.fill_rectangle(
+            Point::new(left, 0.0),
+            Size::new(scrollbar::WIDTH, frame.height()),
+            color,
+        );
+        color.a = if dragging { 0.6 } else { 0.4 };
+        frame.fill_rectangle(
+            Point::new(left, thumb.top),
+            Size::new(scrollbar::WIDTH, thumb.height),
+            color,
+        );
+        let lines = self.data.line_count() as f32;
+        for &row in &self.marks {
+            let top = (frame.height() * row as f32 / lines).min(frame.height() - MARK_TICK_HEIGHT);
+            frame.fill_rectangle(
+                Point::new(left, top),
+                Size::new(scrollbar::WIDTH, MARK_TICK_HEIGHT),
+                theme.palette().primary,
+            );
+        }
+    }
+
+    /** Tell the user they are looking at history rather than live output */
+    fn draw_scrollback_indicator(&self, frame: &mut Frame, right: f32, theme: &Theme) {
+        let text = format!("{}/{}", self.scrollback, self.data.line_count());
+        let palette = theme.palette();
+        let size = Size::new(
+            (text.chars().count() + 2) as f32 * self.metrics.width,
+            self.metrics.height,
+        );
+        let position = Point::new(right - size.width, 0.0);
+        frame.fill_rectangle(position, size, palette.primary);
+        frame.fill_text(Text {
+            content: text,
+            position: position + Vector::new(self.metrics.width, 0.0),
+            color: palette.background,
+            size: self.font_size,
+            line_height: LineHeight::Absolute(self.metrics.height.into()),
+            font: self.font,
+            ..Text::default()
+        });
+    }
+
+    /**
+     * Where the pill saying how many lines arrived below the view goes, at
+     * the bottom of the grid, and what it says. Clicking it scrolls down to them.
+     */
+    fn new_lines_pill(&self, size: Size) -> Option<(Rectangle, String)> {
+        if self.scrollback == 0 || self.new_lines == 0 {
+            return None;
+        }
+        let text = match self.new_lines {
+            1 => "↓ 1 new line".to_string(),
+            n => format!("↓ {n} new lines"),
+        };
+        let width = (text.chars().count() + 2) as f32 * self.metrics.width;
+        let pill = Rectangle {
+            x: ((size.width - width) / 2.0).max(0.0),
+            y: (size.height - 1.5 * self.metrics.height).max(0.0),
+            width,
+            height: self.metrics.height,
+        };
+        Some((pill, text))
+    }
+
+    fn draw_new_lines_pill(&self, frame: &mut Frame, theme: &Theme) {
+        let Some((pill, text)) = self.new_lines_pill(frame.size()) else {
+            return;
+        };
+        let palette = theme.palette();
+        frame.fill_rectangle(pill.position(), pill.size(), palette.primary);
+        frame.fill_text(Text {
+            content: text,
+            position: pill.position() + Vector::new(self.metrics.width, 0.0),
+            color: palette.background,
+            size: self.font_size,
+            line_height: LineHeight::Absolute(self.metrics.height.into()),
+            font: self.font,
+            shaping: Shaping::Advanced,
+            ..Text::default()
+        });
+    }
+
+    /** Whether the pointer is over the pill that scrolls down to new output */
+    fn over_new_lines_pill(&self, bounds: Rectangle, cursor: Cursor) -> bool {
+        let pill = self.new_lines_pill(bounds.size());
+        cursor
+            .position_in(bounds)
+            .zip(pill)
+            .is_some_and(|(point, (pill, _))| pill.contains(point))
+    }
+
+    /** Below the scrollback indicator, so that both can show at once */
+    fn draw_debug_overlay(&self, frame: &mut Frame, right: f32, theme: &Theme) {
+        let palette = theme.palette();
+        let columns = self
+            .debug_lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or_default();
+        let size = Size::new(
+            (columns + 2) as f32 * self.metrics.width,
+            self.debug_lines.len() as f32 * self.metrics.height,
+        );
+        let position = Point::new(right - size.width, self.metrics.height);
+        let mut background = palette.background;
+        background.a = 0.85;
+        frame.fill_rectangle(position, size, background);
+        for (i, line) in self.debug_lines.iter().enumerate() {
+            frame.fill_text(Text {
+                content: line.clone(),
+                position: position
+                    + Vector::new(self.metrics.width, i as f32 * self.metrics.height),
+                color: palette.text,
+                size: self.font_size,
+                line_height: LineHeight::Absolute(self.metrics.height.into()),
+                font: self.font,
+                ..Text::default()
+            });
+        }
+    }
+
+    fn color(&self, color: Color, default: iced::Color) -> iced::Color {
+        match color {
+            Color::Default => default,
+            Color::Indexed(n) => self.colors.indexed(n),
+            Color::Rgb(r, g, b) => iced::Color::from_rgb8(r, g, b),
+        }
+    }
+
+    /** Foreground and background, with inverse video applied */
+    pub fn cell_colors(&self, attributes: &Attributes) -> (iced::Color, iced::Color) {
+        let foreground = if self.bold_as_bright {
+            attributes.bright_foreground()
+        } else {
+            attributes.foreground
+        };
+        let mut foreground = self.color(foreground, colors::color(self.colors.foreground));
+        let mut background =
+            self.color(attributes.background, colors::color(self.colors.background));
+        if attributes.inverse {
+            std::mem::swap(&mut foreground, &mut background);
+        }
+        if attributes.faint {
+            foreground.a *= 0.5;
+        }
+        (foreground, background)
+    }
+
+    /** iced fonts have no italic style, so italic text is drawn upright */
+    pub fn font(&self, attributes: &Attributes) -> Font {
+        Font {
+            weight: if attributes.bold && self.bold_weight {
+                Weight::Bold
+            } else {
+                Weight::Normal
+            },
+            ..self.font
+        }
+    }
+
+    /** A wide character's cell takes in its spacer too */
+    fn cell_size(&self, cell: &Cell) -> Size {
+        let columns = if cell.width == CellWidth::Wide {
+            2.0
+        } else {
+            1.0
+        };
+        Size::new(columns * self.metrics.width, self.metrics.height)
+    }
+
+    /** Spacers are drawn along with the wide character before them */
+    fn draw_cell(&self, frame: &mut Frame, cell: &Cell, position: Point) {
+        if cell.width == CellWidth::Spacer {
+            return;
+        }
+        let (foreground, background) = self.cell_colors(&cell.attributes);
+        if background != colors::color(self.colors.background) {
+            frame.fill_rectangle(position, self.cell_size(cell), background);
+        }
+        self.draw_content(frame, cell, position, foreground);
+    }
+
+    /** The glyph and lines of a cell, without its background */
+    fn draw_content(
+        &self,
+        frame: &mut Frame,
+        cell: &Cell,
+        position: Point,
+        foreground: iced::Color,
+    ) {
+        let size = self.cell_size(cell);
+        let Some(grapheme) = cell.grapheme.as_ref() else {
+            return;
+        };
+        if !cell.attributes.hidden {
+            frame.fill_text(Text {
+                content: grapheme.clone(),
+                position,
+                color: foreground,
+                size: self.font_size,
+                line_height: LineHeight::Absolute(self.metrics.height.into()),
+                font: self.font(&cell.attributes),
+                horizontal_alignment: Horizontal::Left,
+                vertical_alignment: Vertical::Top,
+                shaping: Shaping::Advanced,
+            });
+        }
+        let thickness = (self.font_size / 16.0).max(1.0);
+        if cell.attributes.underline {
+            let y = position.y + self.metrics.height - 2.0 * thickness;
+            frame.fill_rectangle(
+                Point::new(position.x, y),
+                Size::new(size.width, thickness),
+                foreground,
+            );
+        }
+        if cell.attributes.strikethrough {
+            let y = position.y + self.metrics.height / 2.0;
+            frame.fill_rectangle(
+                Point::new(position.x, y),
+                Size::new(size.width, thickness),
+                foreground,
+            );
+        }
+    }
+}
+
+impl<'a, Message> GridView<'a, Message> {
+    fn draw_cursor(&self, frame: &mut Frame, mut cell: Cell, position: Point) {
+        cell.attributes.faint = false;
+        let (foreground, background) = self.cell_colors(&cell.attributes);
+        let color = self.colors.cursor.or(foreground);
+        let thickness = (self.font_size / 8.0).max(1.0);
+        let size = self.cell_size(&cell);
+        if !self.focused {
+            let path = Path::rectangle(
+                position + Vector::new(thickness / 2.0, thickness / 2.0),
+                Size::new(size.width - thickness, size.height - thickness),
+            );
+            frame.stroke(
+                &path,
+                Stroke::default().with_color(color).with_width(thickness),
+            );
+            return;
+        }
+        match self.cursor_shape {
+            CursorShape::Block => {
+                // Draw the glyph again over the cursor, so it stays readable
+                frame.fill_rectangle(position, size, color);
+                self.draw_content(
+                    frame,
+                    &cell,
+                    position,
+                    self.colors.cursor_text.or(background),
+                );
+            }
+            CursorShape::Beam => {
+                frame.fill_rectangle(position, Size::new(thickness, size.height), color)
+            }
+            CursorShape::Underline => frame.fill_rectangle(
+                position + Vector::new(0.0, size.height - thickness),
+                Size::new(size.width, thickness),
+                color,
+            ),
+        }
+    }
+}
+
+/** Whether a point relative to the top left of the grid is over the scrollbar */
+fn on_scrollbar(point: Point, bounds: Rectangle) -> bool {
+    point.x >= bounds.width - scrollbar::WIDTH
+}
+
+impl<'a, Message: Clone> Program<Message> for GridView<'a, Message> {
+    type State = GridState;
+
+    fn update(
+        &self,
+        state: &mut GridState,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let selection_event = match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if self.over_new_lines_pill(bounds, cursor) =>
+            {
+                let to_bottom = (self.on_scroll)(-(self.scrollback as isize));
+                return (event::Status::Captured, Some(to_bottom));
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if self.over_scrollbar(state, bounds, cursor) =>
+            {
+                let (Some(point), Some(thumb)) = (
+                    cursor.position_in(bounds),
+                    self.thumb(state, bounds, cursor),
+                ) else {
+                    return (event::Status::Ignored, None);
+                };
+                // Clicking the track jumps there, with the thumb centered on the pointer
+                let grab = if thumb.contains(point.y) {
+                    point.y - thumb.top
+                } else {
+                    thumb.height / 2.0
+                };
+                state.scrollbar_grab = Some(grab);
+                return (
+                    event::Status::Captured,
+                    Some(self.drag_scrollbar(thumb, point.y - grab, bounds)),
+                );
+            }
+            canvas::Event::Mouse(mouse::Event::CursorMoved { .. })
+                if state.scrollbar_grab.is_some() =>
+            {
+                let (Some(grab), Some(point), Some(thumb)) = (
+                    state.scrollbar_grab,
+                    cursor.position_from(bounds.position()),
+                    self.thumb(state, bounds, cursor),
+                ) else {
+                    return (event::Status::Ignored, None);
+                };
+                return (
+                    event::Status::Captured,
+                    Some(self.drag_scrollbar(thumb, point.y - grab, bounds)),
+                );
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if state.scrollbar_grab.is_some() =>
+            {
+                state.scrollbar_grab = None;
+                return (event::Status::Captured, None);
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if state.modifiers.control()
+                    && self.hovered_link(state, bounds, cursor).is_some() =>
+            {
+                let target = self
+                    .hovered_link(state, bounds, cursor)
+                    .unwrap()
+                    .target
+                    .clone();
+                return (event::Status::Captured, Some((self.on_open_link)(target)));
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let Some(point) = cursor.position_in(bounds) else {
+                    return (event::Status::Ignored, None);
+                };
+                state.selecting = true;
+                let position = self.position_at(point);
+                let unit = match &state.last_click {
+                    Some((time, last, unit))
+                        if time.elapsed() < MULTI_CLICK_INTERVAL && *last == position =>
+                    {
+                        match unit {
+                            SelectionUnit::Cell => SelectionUnit::Word,
+                            SelectionUnit::Word => SelectionUnit::Line,
+                            SelectionUnit::Line => SelectionUnit::Cell,
+                        }
+                    }
+                    _ => SelectionUnit::Cell,
+                };
+                state.last_click = Some((Instant::now(), position.clone(), unit));
+                SelectionEvent::Started(position, unit)
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Middle))
+                if cursor.is_over(bounds) =>
+            {
+                return (event::Status::Captured, Some(self.on_paste.clone()));
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right))
+                if cursor.is_over(bounds) =>
+            {
+                let link = self
+                    .hovered_link(state, bounds, cursor)
+                    .map(|link| link.target.clone());
+                let message = cursor
+                    .position()
+                    .map(|point| (self.on_context_menu)(point, link));
+                return (event::Status::Captured, message);
+            }
+            canvas::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = modifiers;
+                return (event::Status::Ignored, None);
+            }
+            canvas::Event::Mouse(mouse::Event::WheelScrolled {
+                delta: mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. },
+            }) if state.modifiers.control() && y != 0.0 => {
+                let font_size = self.font_size + ZOOM_STEP * y.signum();
+                return (event::Status::Captured, Some((self.on_zoom)(font_size)));
+            }
+            canvas::Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let lines = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y * WHEEL_LINES,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / self.metrics.height,
+                } * self.scroll_multiplier;
+                let whole = state.whole_lines(lines);
+                let message = (whole != 0).then(|| (self.on_wheel)(whole));
+                return (event::Status::Captured, message);
+            }
+            canvas::Event::Touch(touch::Event::FingerPressed { id, position })
+                if bounds.contains(position) =>
+            {
+                state.fingers.push((id, position));
+                state.drag = Drag::default();
+                if let [(_, a), (_, b)] = state.fingers[..] {
+                    state.pinch = Some((self.font_size, a.distance(b)));
+                }
+                return (event::Status::Captured, Some((self.on_flick)(0.0)));
+            }
+            canvas::Event::Touch(touch::Event::FingerMoved { id, position }) => {
+                let Some(finger) = state.fingers.iter_mut().find(|(finger, _)| *finger == id)
+                else {
+                    return (event::Status::Ignored, None);
+                };
+                let previous = std::mem::replace(&mut finger.1, position);
+                if let (Some((font_size, start)), [(_, a), (_, b)]) =
+                    (state.pinch, &state.fingers[..])
+                {
+                    let zoomed = gestures::pinch_zoom(font_size, start, a.distance(*b));
+                    let message = ((zoomed - self.font_size).abs() >= PINCH_STEP)
+                        .then(|| (self.on_zoom)(zoomed));
+                    return (event::Status::Captured, message);
+                }
+                if state.fingers.len() > 1 {
+                    return (event::Status::Captured, None);
+                }
+                // The lines follow the finger, so dragging down scrolls back
+                let lines = (position.y - previous.y) / self.metrics.height;
+                state.drag.moved(lines, Instant::now());
+                let whole = state.whole_lines(lines);
+                let message = (whole != 0).then(|| (self.on_wheel)(whole));
+                return (event::Status::Captured, message);
+            }
+            canvas::Event::Touch(
+                touch::Event::FingerLifted { id, .. } | touch::Event::FingerLost { id, .. },
+            ) => {
+                let Some(index) = state.fingers.iter().position(|(finger, _)| *finger == id) else {
+                    return (event::Status::Ignored, None);
+                };
+                state.fingers.remove(index);
+                // Neither the end of a pinch nor a finger that stays down flicks
+                if state.pinch.take().is_some() || !state.fingers.is_empty() {
+                    state.drag = Drag::default();
+                    return (event::Status::Captured, None);
+                }
+                let message = state.drag.release(Instant::now()).map(self.on_flick);
+                return (event::Status::Captured, message);
+            }
+            canvas::Event::Mouse(mouse::Event::CursorMoved { .. }) if state.selecting => {
+                let Some(point) = cursor.position_from(bounds.position()) else {
+                    return (event::Status::Ignored, None);
+                };
+                SelectionEvent::Extended(self.position_at(point))
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if state.selecting =>
+            {
+                state.selecting = false;
+                SelectionEvent::Finished
+            }
+            _ => return (event::Status::Ignored, None),
+        };
+        (
+            event::Status::Captured,
+            Some((self.on_selection)(selection_event)),
+        )
+    }
+
+    fn draw(
+        &self,
+        state: &GridState,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> Vec<Geometry> {
+        let first_line = self.first_line();
+        let grid = self.cache.draw(renderer, bounds.size(), |frame| {
+            let _span = debug_span!("draw_grid", rows = self.visible_rows().len()).entered();
+            for row in self.visible_rows() {
+                let y = (row - first_line) as f32 * self.metrics.height;
+                let order = self.visual_order(row);
+                for (col, cell) in self.data.line(row).iter().enumerate() {
+                    let position = Point::new(self.cell_x(order.as_deref(), col), y);
+                    self.draw_cell(frame, cell, position);
+                }
+            }
+        });
+
+        // The cursor moves far more often than cells change, so it gets its own uncached layer
+        let mut frame = Frame::new(renderer, bounds.size());
+        if let Some(selection) = self.selection {
+            self.draw_selection(&mut frame, selection);
+        }
+        self.draw_search_matches(&mut frame, theme);
+        if let Some(position) = self.copy_cursor {
+            self.draw_copy_cursor(&mut frame, position, theme);
+        }
+        if let Some(hints) = self.hints {
+            self.draw_hints(&mut frame, hints, theme);
+        }
+        let mut active = self.data.get_active_position();
+        let line = self.data.line(active.row);
+        // The cursor covers the whole of a wide character
+        if active.col > 0
+            && line
+                .get(active.col)
+                .is_some_and(|cell| cell.width == CellWidth::Spacer)
+        {
+            active.col -= 1;
+        }
+        if self.cursor_visible && self.visible_rows().contains(&active.row) {
+            let order = self.visual_order(active.row);
+            let position = Point::new(
+                self.cell_x(order.as_deref(), active.col),
+                (active.row - first_line) as f32 * self.metrics.height,
+            );
+            let cell = self.data.line(active.row).get(active.col);
+            self.draw_cursor(&mut frame, cell.cloned().unwrap_or_default(), position);
+        }
+        if let Some(link) = self.hovered_link(state, bounds, cursor) {
+            self.draw_link_underline(&mut frame, link);
+        }
+        if self.flash {
+            let mut color = colors::color(self.colors.foreground);
+            color.a = 0.2;
+            frame.fill_rectangle(Point::ORIGIN, frame.size(), color);
+        }
+        let mut right = frame.width();
+        if let Some(thumb) = self.thumb(state, bounds, cursor) {
+            self.draw_scrollbar(&mut frame, thumb, state.scrollbar_grab.is_some(), theme);
+            right -= scrollbar::WIDTH;
+        }
+        if self.scrollback > 0 {
+            self.draw_scrollback_indicator(&mut frame, right, theme);
+        }
+        self.draw_new_lines_pill(&mut frame, theme);
+        if !self.debug_lines.is_empty() {
+            self.draw_debug_overlay(&mut frame, right, theme);
+        }
+        vec![grid, frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &GridState,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> mouse::Interaction {
+        // Hiding the pointer while typing would go here, but iced 0.10 has no hidden
+        // interaction and no window command to make winit hide the cursor
+        if self.over_new_lines_pill(bounds, cursor)
+            || state.modifiers.control() && self.hovered_link(state, bounds, cursor).is_some()
+        {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
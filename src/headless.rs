@@ -0,0 +1,121 @@
+use crate::backend::{self, WindowSize};
+use crate::cast::CastWriter;
+use crate::child;
+use crate::config::Config;
+use crate::recorder::Recorder;
+use anyhow::Result;
+use firn_core::Terminal;
+use std::io::Write;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, timeout_at, Duration, Instant};
+use tracing::{debug, info};
+
+/** How to run the shell without a window, and what to print */
+pub struct Options {
+    pub size: WindowSize,
+    /** Keep the colors and styles, as SGR sequences */
+    pub ansi: bool,
+    /** Print the screen after every read rather than only at the end */
+    pub stream: bool,
+    /** Hang up on the shell after this long, for programs that never exit by themselves */
+    pub timeout: Option<Duration>,
+}
+
+/**
+ * Run the configured shell on a pty of the given size, feeding its output
+ * through the emulator, then print the screen. Returns the exit code firn
+ * should exit with: the shell's own, or 0 if it was hung up on at the timeout.
+ */
+pub fn run(config: &Config, options: &Options) -> Result<i32> {
+    tokio::runtime::Runtime::new()?.block_on(run_async(config, options))
+}
+
+async fn run_async(config: &Config, options: &Options) -> Result<i32> {
+    let pty =
+        backend::from_config(&config.backend)?.spawn(&child::command(config), options.size)?;
+    let (mut reader, mut writer, mut child) = (pty.reader, pty.writer, pty.child);
+    let mut recorder = config
+        .record_path
+        .as_deref()
+        .map(Recorder::create)
+        .transpose()?;
+    let mut cast = config
+        .cast_path
+        .as_deref()
+        .map(|path| CastWriter::create(path, options.size, child::cast_env(config)))
+        .transpose()?;
+    let mut terminal = Terminal::new()?;
+    terminal.data_mut().set_width_policy(config.width_policy);
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    let mut buf = vec![0u8; config.read_buf_size];
+    let mut timed_out = false;
+    loop {
+        let read = match deadline {
+            Some(deadline) => match timeout_at(deadline, reader.read(&mut buf)).await {
+                Ok(read) => read,
+                Err(_) => {
+                    timed_out = true;
+                    break;
+                }
+            },
+            None => reader.read(&mut buf).await,
+        };
+        match read {
+            // Linux reports EIO once the other side of the pty is closed
+            Ok(0) | Err(_) => break,
+            Ok(nbytes) => {
+                debug!("Read {nbytes} bytes from pty");
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record(&buf[..nbytes])?;
+                }
+                if let Some(cast) = cast.as_mut() {
+                    cast.output(&buf[..nbytes])?;
+                }
+                terminal.advance(&buf[..nbytes]);
+                // Programs wait on the answers to their queries, e.g. for the cursor position
+                let responses = terminal.take_responses();
+                if !responses.is_empty() {
+                    writer.write_all(&responses).await?;
+                }
+                if options.stream {
+                    print_screen(&terminal, options)?;
+                }
+            }
+        }
+    }
+    if !options.stream {
+        print_screen(&terminal, options)?;
+    }
+
+    if timed_out {
+        info!("Hanging up after {:?}", options.timeout.unwrap_or_default());
+        child.hangup()?;
+        if timeout(Duration::from_millis(config.kill_timeout_ms), child.wait())
+            .await
+            .is_err()
+        {
+            child.kill()?;
+        }
+        return Ok(0);
+    }
+    let status = child.wait().await?;
+    info!("Shell finished with status {status}");
+    Ok(status.code().unwrap_or(1))
+}
+
+fn print_screen(terminal: &Terminal, options: &Options) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    let rows = options.size.rows.into();
+    if options.ansi {
+        // Each screen replaces the last, rather than scrolling it away
+        if options.stream {
+            stdout.write_all(b"\x1b[H\x1b[2J")?;
+        }
+        terminal.render_ansi_to(&mut stdout, rows)?;
+    } else {
+        terminal.render_to(&mut stdout, rows)?;
+    }
+    stdout.write_all(b"\n")?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::data::DataComponent;
+use crate::links::{Link, LinkDetector};
+use std::ops::Range;
+
+/** What hints mode labels besides links, unless configured otherwise */
+pub const DEFAULT_PATTERNS: [&str; 3] = [
+    // Paths
+    r"(?:~|\.{1,2})?(?:/[\w.+@-]+)+/?",
+    // Git hashes and other hex digests
+    r"\b[0-9a-f]{7,64}\b",
+    // IPv4 addresses, with an optional port
+    r"\b\d{1,3}(?:\.\d{1,3}){3}(?::\d{1,5})?\b",
+];
+
+/** Home row first, so the labels are quick to type */
+const ALPHABET: &str = "asdfjklghqweruiopzxcvnmtyb";
+
+/**
+ * Labels for `count` hints, all of the same length so that none is a prefix
+ * of another
+ */
+fn labels(count: usize) -> Vec<String> {
+    let alphabet: Vec<char> = ALPHABET.chars().collect();
+    let mut length = 1;
+    while alphabet.len().pow(length) < count {
+        length += 1;
+    }
+    (0..count)
+        .map(|mut n| {
+            let mut label = vec![' '; length as usize];
+            for slot in label.iter_mut().rev() {
+                *slot = alphabet[n % alphabet.len()];
+                n /= alphabet.len();
+            }
+            label.into_iter().collect()
+        })
+        .collect()
+}
+
+/** Something on screen that typing its label picks */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hint {
+    pub label: String,
+    pub link: Link,
+}
+
+/** What to do with the hint that was picked */
+#[derive(Clone, Debug, PartialEq)]
+pub enum HintAction {
+    Copy(String),
+    Open(String),
+}
+
+/** State of hints mode, while it is on */
+pub struct Hints {
+    pub hints: Vec<Hint>,
+    /** The start of a label typed so far */
+    pub typed: String,
+}
+
+impl Hints {
+    /** Label whatever `detector` finds among `rows`, or `None` if that is nothing */
+    pub fn new(detector: &LinkDetector, data: &DataComponent, rows: Range<usize>) -> Option<Self> {
+        let links = detector.find(data, rows);
+        if links.is_empty() {
+            return None;
+        }
+        // The most recent output gets the first labels
+        let hints = labels(links.len())
+            .into_iter()
+            .zip(links.into_iter().rev())
+            .map(|(label, link)| Hint { label, link })
+            .collect();
+        Some(Self {
+            hints,
+            typed: String::new(),
+        })
+    }
+
+    /** The hints whose labels start with what was typed so far */
+    pub fn remaining(&self) -> impl Iterator<Item = &Hint> {
+        self.hints
+            .iter()
+            .filter(|hint| hint.label.starts_with(&self.typed))
+    }
+
+    /**
+     * Type the next character of a label, returning what to do once a label
+     * is complete. Typing its last character in upper case opens the hint
+     * instead of copying it. Characters no label continues with are ignored.
+     */
+    pub fn push(&mut self, ch: char) -> Option<HintAction> {
+        let mut typed = self.typed.clone();
+        typed.push(ch.to_ascii_lowercase());
+        let hint = self
+            .hints
+            .iter()
+            .find(|hint| hint.label.starts_with(&typed))?;
+        if hint.label != typed {
+            self.typed = typed;
+            return None;
+        }
+        let target = hint.link.target.clone();
+        Some(if ch.is_uppercase() {
+            HintAction::Open(target)
+        } else {
+            HintAction::Copy(target)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::Translator;
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(labels(3), vec!["a", "s", "d"]);
+        let many = labels(30);
+        assert_eq!(many[0], "aa");
+        assert_eq!(many[29], "sf");
+        assert!(many.iter().all(|label| label.len() == 2));
+    }
+
+    #[test]
+    fn test_hints() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(
+            "commit 3f2a9c1d in ~/src/firn\r\nfrom 10.0.0.1:22\r\n".as_bytes(),
+            &mut data,
+        );
+        let mut patterns = crate::links::DEFAULT_PATTERNS.map(String::from).to_vec();
+        patterns.extend(DEFAULT_PATTERNS.map(String::from));
+        let detector = LinkDetector::new(&patterns);
+        assert!(Hints::new(&detector, &data, 2..2).is_none());
+
+        let mut hints = Hints::new(&detector, &data, 0..data.line_count()).unwrap();
+        let targets: Vec<_> = hints
+            .hints
+            .iter()
+            .map(|hint| (hint.label.as_str(), hint.link.target.as_str()))
+            .collect();
+        assert_eq!(
+            targets,
+            vec![("a", "10.0.0.1:22"), ("s", "~/src/firn"), ("d", "3f2a9c1d")]
+        );
+        assert_eq!(hints.push('x'), None);
+        assert_eq!(hints.push('S'), Some(HintAction::Open("~/src/firn".into())));
+        assert_eq!(hints.push('d'), Some(HintAction::Copy("3f2a9c1d".into())));
+    }
+}
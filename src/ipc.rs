@@ -0,0 +1,299 @@
+use anyhow::{bail, Context, Result};
+use iced::futures::channel::mpsc::{self, Sender};
+use iced::futures::{SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+use serde::{Deserialize, Serialize};
+use std::future::pending;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+/** Tells shells, and so `firn msg`, where their window listens */
+pub const SOCKET_ENV: &str = "FIRN_SOCKET";
+
+/**
+ * A command for a running firn window, sent over its socket as a line of
+ * JSON such as `{"command": "send-text", "text": "ls\r"}`
+ */
+#[derive(clap::Subcommand, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /** Type text into the active tab's shell */
+    SendText { text: String },
+    /** Print the text on the active tab's screen */
+    GetText {
+        /** The scrollback too, not only the rows in view */
+        #[arg(long)]
+        #[serde(default)]
+        scrollback: bool,
+    },
+    /** Resize the window to fit this many columns and rows */
+    Resize { cols: u16, rows: u16 },
+    /** Switch to another color scheme, one of `colors` or the built-in ones */
+    SetColors { scheme: String },
+    /** Open a tab, with the settings of a profile if one is named */
+    NewTab {
+        #[arg(long)]
+        profile: Option<String>,
+    },
+}
+
+/** The answer to a `Request`, also a line of JSON */
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Response {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    pub fn text(text: String) -> Self {
+        Self {
+            text: Some(text),
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<String>) -> Self {
+        Self {
+            text: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/** A request that came in over the socket, and the way back to whoever sent it */
+#[derive(Clone, Debug)]
+pub struct RemoteRequest {
+    pub request: Request,
+    reply: Sender<Response>,
+}
+
+impl RemoteRequest {
+    pub fn reply(mut self, response: Response) {
+        // Each sender has a slot of its own, so this only fails if the client hung up
+        let _ = self.reply.try_send(response);
+    }
+}
+
+/**
+ * Where this firn process listens: in `$XDG_RUNTIME_DIR`, which only its
+ * user may enter, or else in a directory of the user's own under the
+ * shared temporary directory
+ */
+pub fn socket_path() -> PathBuf {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::temp_dir().join(private_dir_name()),
+    };
+    dir.join(format!("firn-{}.sock", std::process::id()))
+}
+
+#[cfg(unix)]
+fn private_dir_name() -> String {
+    format!("firn-{}", nix::unistd::getuid())
+}
+
+#[cfg(not(unix))]
+fn private_dir_name() -> String {
+    "firn".into()
+}
+
+/** Requests arriving on the socket at `path` */
+pub fn subscription(path: PathBuf) -> Subscription<RemoteRequest> {
+    struct Listen;
+
+    subscription::channel(
+        (std::any::TypeId::of::<Listen>(), path.clone()),
+        16,
+        async move |mut output: Sender<RemoteRequest>| {
+            if let Err(err) = serve(&path, &mut output).await {
+                error!("Remote control is unavailable: {err:#}");
+            }
+            pending::<()>().await;
+            unreachable!();
+        },
+    )
+}
+
+/**
+ * Make sure only the user can reach into `dir`: create it if need be, and
+ * refuse one that someone else owns or that others may enter
+ */
+#[cfg(unix)]
+fn ensure_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err).with_context(|| format!("Unable to create {dir:?}")),
+    }
+    let metadata =
+        std::fs::symlink_metadata(dir).with_context(|| format!("Unable to inspect {dir:?}"))?;
+    if !metadata.is_dir() || metadata.uid() != nix::unistd::getuid().as_raw() {
+        bail!("{dir:?} is not a directory of this user's");
+    }
+    if metadata.mode() & 0o077 != 0 {
+        bail!("{dir:?} is open to other users");
+    }
+    Ok(())
+}
+
+/** Remove a socket that a firn which is gone left at `path`, but nothing else */
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() || metadata.uid() != nix::unistd::getuid().as_raw() {
+        bail!("Something other than a socket of this user's is in the way at {path:?}");
+    }
+    // The name holds the pid, so a socket already there was left by a firn that is gone
+    std::fs::remove_file(path).with_context(|| format!("Unable to remove {path:?}"))
+}
+
+#[cfg(unix)]
+async fn serve(path: &Path, output: &mut Sender<RemoteRequest>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(dir) = path.parent() {
+        ensure_private_dir(dir)?;
+    }
+    remove_stale_socket(path)?;
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Unable to listen on {path:?}"))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Unable to restrict access to {path:?}"))?;
+    info!("Listening for remote control on {path:?}");
+    let uid = nix::unistd::getuid().as_raw();
+    loop {
+        let (stream, _) = listener.accept().await?;
+        // Typing into the shell is running commands as its user, so nobody else may
+        match stream.peer_cred() {
+            Ok(peer) if peer.uid() == uid => {}
+            Ok(peer) => {
+                warn!("Refused remote control from user {}", peer.uid());
+                continue;
+            }
+            Err(err) => {
+                warn!("Refused remote control from an unknown user: {err}");
+                continue;
+            }
+        }
+        let output = output.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, output).await {
+                error!("Remote control connection failed: {err:#}");
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+async fn serve(_path: &Path, _output: &mut Sender<RemoteRequest>) -> Result<()> {
+    bail!("Remote control needs Unix sockets")
+}
+
+/** Answer each line of a connection in turn, until the client closes it */
+#[cfg(unix)]
+async fn handle(stream: tokio::net::UnixStream, mut output: Sender<RemoteRequest>) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => {
+                let (reply, mut replies) = mpsc::channel(1);
+                output.send(RemoteRequest { request, reply }).await?;
+                replies
+                    .next()
+                    .await
+                    .unwrap_or_else(|| Response::error("The window closed"))
+            }
+            Err(err) => Response::error(format!("Invalid request: {err}")),
+        };
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/** Send a request to the window listening at `socket`, or else the one this shell runs in */
+#[cfg(unix)]
+pub fn send(socket: Option<&Path>, request: &Request) -> Result<Response> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let socket = match socket {
+        Some(socket) => socket.to_owned(),
+        None => std::env::var_os(SOCKET_ENV)
+            .map(PathBuf::from)
+            .with_context(|| {
+                format!("${SOCKET_ENV} is not set: pass --socket, or enable remote_control")
+            })?,
+    };
+    let mut stream =
+        UnixStream::connect(&socket).with_context(|| format!("Unable to connect to {socket:?}"))?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).context("Invalid response")
+}
+
+#[cfg(not(unix))]
+pub fn send(_socket: Option<&Path>, _request: &Request) -> Result<Response> {
+    bail!("Remote control needs Unix sockets")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_format() {
+        let request: Request =
+            serde_json::from_str(r#"{"command": "send-text", "text": "ls\r"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::SendText {
+                text: "ls\r".into()
+            }
+        );
+        let request: Request = serde_json::from_str(r#"{"command": "get-text"}"#).unwrap();
+        assert_eq!(request, Request::GetText { scrollback: false });
+        assert_eq!(
+            serde_json::to_string(&Request::Resize { cols: 80, rows: 24 }).unwrap(),
+            r#"{"command":"resize","cols":80,"rows":24}"#
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command": "reboot"}"#).is_err());
+        assert_eq!(
+            serde_json::to_string(&Response::error("No such profile")).unwrap(),
+            r#"{"error":"No such profile"}"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("firn-test-{}", std::process::id()));
+        ensure_private_dir(&dir).unwrap();
+        ensure_private_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(ensure_private_dir(&dir).is_err());
+
+        let file = dir.join("not-a-socket");
+        std::fs::write(&file, "").unwrap();
+        assert!(remove_stale_socket(&file).is_err());
+        assert!(file.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
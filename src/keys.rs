@@ -0,0 +1,185 @@
+use crate::config::Meta;
+use crate::data::Modes;
+use iced::keyboard::{KeyCode, Modifiers};
+
+/** How a key is encoded, following xterm */
+enum KeySequence {
+    /** `CSI final` normally, `SS3 final` in application cursor mode, `CSI 1;m final` with modifiers */
+    Cursor(char),
+    /** `SS3 final`, or `CSI 1;m final` with modifiers */
+    Ss3(char),
+    /** `CSI n ~`, or `CSI n;m ~` with modifiers */
+    Tilde(u8),
+    /** `SS3 final`, but only in application keypad mode */
+    Keypad(char),
+}
+
+fn sequence(key_code: KeyCode) -> Option<KeySequence> {
+    use KeySequence::*;
+    Some(match key_code {
+        KeyCode::Up => Cursor('A'),
+        KeyCode::Down => Cursor('B'),
+        KeyCode::Right => Cursor('C'),
+        KeyCode::Left => Cursor('D'),
+        KeyCode::Home => Cursor('H'),
+        KeyCode::End => Cursor('F'),
+        KeyCode::F1 => Ss3('P'),
+        KeyCode::F2 => Ss3('Q'),
+        KeyCode::F3 => Ss3('R'),
+        KeyCode::F4 => Ss3('S'),
+        KeyCode::Insert => Tilde(2),
+        KeyCode::Delete => Tilde(3),
+        KeyCode::PageUp => Tilde(5),
+        KeyCode::PageDown => Tilde(6),
+        KeyCode::F5 => Tilde(15),
+        KeyCode::F6 => Tilde(17),
+        KeyCode::F7 => Tilde(18),
+        KeyCode::F8 => Tilde(19),
+        KeyCode::F9 => Tilde(20),
+        KeyCode::F10 => Tilde(21),
+        KeyCode::F11 => Tilde(23),
+        KeyCode::F12 => Tilde(24),
+        KeyCode::Numpad0 => Keypad('p'),
+        KeyCode::Numpad1 => Keypad('q'),
+        KeyCode::Numpad2 => Keypad('r'),
+        KeyCode::Numpad3 => Keypad('s'),
+        KeyCode::Numpad4 => Keypad('t'),
+        KeyCode::Numpad5 => Keypad('u'),
+        KeyCode::Numpad6 => Keypad('v'),
+        KeyCode::Numpad7 => Keypad('w'),
+        KeyCode::Numpad8 => Keypad('x'),
+        KeyCode::Numpad9 => Keypad('y'),
+        KeyCode::NumpadMultiply => Keypad('j'),
+        KeyCode::NumpadAdd => Keypad('k'),
+        KeyCode::NumpadComma => Keypad('l'),
+        KeyCode::NumpadSubtract => Keypad('m'),
+        KeyCode::NumpadDecimal => Keypad('n'),
+        KeyCode::NumpadDivide => Keypad('o'),
+        KeyCode::NumpadEnter => Keypad('M'),
+        KeyCode::NumpadEquals => Keypad('X'),
+        _ => return None,
+    })
+}
+
+pub fn is_keypad(key_code: KeyCode) -> bool {
+    matches!(sequence(key_code), Some(KeySequence::Keypad(_)))
+}
+
+/** The xterm modifier parameter: 1 plus a bit each for Shift, Alt, Ctrl and Super */
+fn modifier_parameter(modifiers: Modifiers) -> Option<u8> {
+    let parameter = 1
+        + modifiers.shift() as u8
+        + 2 * modifiers.alt() as u8
+        + 4 * modifiers.control() as u8
+        + 8 * modifiers.logo() as u8;
+    (parameter > 1).then_some(parameter)
+}
+
+/**
+ * The bytes a key that produces no text sends, or `None` if the key is left
+ * to `CharacterReceived` (or does nothing)
+ */
+pub fn encode(key_code: KeyCode, modifiers: Modifiers, modes: Modes) -> Option<String> {
+    let modifier = modifier_parameter(modifiers);
+    Some(match (sequence(key_code)?, modifier) {
+        (KeySequence::Cursor(c), None) if modes.application_cursor => format!("\x1BO{c}"),
+        (KeySequence::Cursor(c), None) => format!("\x1B[{c}"),
+        (KeySequence::Ss3(c), None) => format!("\x1BO{c}"),
+        (KeySequence::Cursor(c) | KeySequence::Ss3(c), Some(m)) => format!("\x1B[1;{m}{c}"),
+        (KeySequence::Tilde(n), None) => format!("\x1B[{n}~"),
+        (KeySequence::Tilde(n), Some(m)) => format!("\x1B[{n};{m}~"),
+        (KeySequence::Keypad(c), None) if modes.application_keypad => format!("\x1BO{c}"),
+        (KeySequence::Keypad(_), _) => return None,
+    })
+}
+
+/** The bytes for a typed character, with Alt applied as Meta */
+pub fn encode_character(ch: char, alt: bool, meta: Meta) -> Vec<u8> {
+    match meta {
+        Meta::Escape if alt => format!("\x1B{ch}").into_bytes(),
+        // The single byte, as 8-bit terminals sent it, not that byte as a character in UTF-8
+        Meta::HighBit if alt && ch.is_ascii() => vec![ch as u8 | 0x80],
+        _ => ch.to_string().into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_keys() {
+        let modes = Modes::default();
+        assert_eq!(
+            encode(KeyCode::Up, Modifiers::empty(), modes).unwrap(),
+            "\x1B[A"
+        );
+        assert_eq!(
+            encode(KeyCode::Right, Modifiers::CTRL, modes).unwrap(),
+            "\x1B[1;5C"
+        );
+        let modifiers = Modifiers::SHIFT | Modifiers::ALT;
+        assert_eq!(
+            encode(KeyCode::Home, modifiers, modes).unwrap(),
+            "\x1B[1;4H"
+        );
+
+        let modes = Modes {
+            application_cursor: true,
+            ..Modes::default()
+        };
+        assert_eq!(
+            encode(KeyCode::Up, Modifiers::empty(), modes).unwrap(),
+            "\x1BOA"
+        );
+        assert_eq!(
+            encode(KeyCode::Up, Modifiers::SHIFT, modes).unwrap(),
+            "\x1B[1;2A"
+        );
+    }
+
+    #[test]
+    fn test_function_keys() {
+        let modes = Modes::default();
+        assert_eq!(
+            encode(KeyCode::F1, Modifiers::empty(), modes).unwrap(),
+            "\x1BOP"
+        );
+        assert_eq!(
+            encode(KeyCode::F1, Modifiers::CTRL, modes).unwrap(),
+            "\x1B[1;5P"
+        );
+        assert_eq!(
+            encode(KeyCode::F5, Modifiers::empty(), modes).unwrap(),
+            "\x1B[15~"
+        );
+        assert_eq!(
+            encode(KeyCode::PageUp, Modifiers::SHIFT, modes).unwrap(),
+            "\x1B[5;2~"
+        );
+    }
+
+    #[test]
+    fn test_meta() {
+        assert_eq!(encode_character('b', false, Meta::Escape), b"b");
+        assert_eq!(encode_character('b', true, Meta::Escape), b"\x1Bb");
+        assert_eq!(encode_character('b', true, Meta::HighBit), b"\xE2");
+        assert_eq!(encode_character('é', true, Meta::HighBit), "é".as_bytes());
+        assert_eq!(encode_character('b', true, Meta::Off), b"b");
+    }
+
+    #[test]
+    fn test_keypad() {
+        let modes = Modes::default();
+        assert_eq!(encode(KeyCode::Numpad1, Modifiers::empty(), modes), None);
+        let modes = Modes {
+            application_keypad: true,
+            ..Modes::default()
+        };
+        assert_eq!(
+            encode(KeyCode::Numpad1, Modifiers::empty(), modes).unwrap(),
+            "\x1BOq"
+        );
+        assert_eq!(encode(KeyCode::A, Modifiers::empty(), modes), None);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::data::DataComponent;
+use regex::Regex;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::{debug, error};
+
+/**
+ * What counts as a link unless configured otherwise: URLs, minus trailing
+ * punctuation, and file locations as compilers print them
+ */
+pub const DEFAULT_PATTERNS: [&str; 3] = [
+    r#"\b(?:https?|ftp|file)://[^\s<>"'`]*[^\s<>"'`.,:;!?)\]}]"#,
+    r"\bmailto:[\w.+-]+@[\w-]+(?:\.[\w-]+)+",
+    FILE_LOCATION,
+];
+
+/**
+ * A path to a file with an extension, then a line number and maybe a column.
+ * A bare file name needs one of the extensions compilers and tools print
+ * locations in, so that a host and port like `example.com:80` is not taken
+ * for one.
+ */
+const FILE_LOCATION: &str = concat!(
+    r"(?:(?:(?:~|\.{1,2})?/(?:[\w.+@-]+/)*|(?:[\w.+@-]+/)+)[\w+@-][\w.+@-]*\.[A-Za-z]\w*",
+    r"|[\w+@-][\w.+@-]*\.(?:c|cc|cpp|cs|css|cxx|dart|ex|exs|go|h|hh|hpp|hs|html|java|js|json",
+    r"|jsx|kt|lua|md|ml|mli|nim|php|pl|py|rb|rs|scala|scss|sh|sql|swift|toml|ts|tsx|txt|vue",
+    r"|xml|yaml|yml|zig))",
+    r":\d+(?::\d+)?\b",
+);
+
+/** A place in a file, like `src/main.rs:12:5` */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileLocation {
+    pub path: PathBuf,
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+impl FileLocation {
+    /** `target` as a file location, if it is one rather than a URL */
+    pub fn parse(target: &str) -> Option<Self> {
+        if !Regex::new(&format!("^{FILE_LOCATION}$"))
+            .unwrap()
+            .is_match(target)
+        {
+            return None;
+        }
+        let mut parts = target.split(':');
+        let path = parts.next()?;
+        let line = parts.next()?.parse().ok()?;
+        let column = parts.next().and_then(|column| column.parse().ok());
+        let path = match path.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+            None => path.into(),
+        };
+        Some(Self { path, line, column })
+    }
+
+    /** The configured editor command, with the placeholders filled in */
+    pub fn command(&self, editor: &[String]) -> Command {
+        let args: Vec<String> = editor
+            .iter()
+            .map(|arg| {
+                arg.replace("{file}", &self.path.to_string_lossy())
+                    .replace("{line}", &self.line.to_string())
+                    .replace("{column}", &self.column.unwrap_or(1).to_string())
+            })
+            .collect();
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]);
+        command
+    }
+}
+
+/** Text in the grid that looks like something to open */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub row: usize,
+    pub columns: Range<usize>,
+    pub target: String,
+}
+
+/** Finds links in the grid by matching the configured patterns against each row */
+pub struct LinkDetector {
+    pattern: Regex,
+}
+
+fn combine(patterns: &[impl AsRef<str>]) -> Result<Regex, regex::Error> {
+    let alternatives: Vec<_> = patterns
+        .iter()
+        .map(|pattern| format!("(?:{})", pattern.as_ref()))
+        .collect();
+    Regex::new(&alternatives.join("|"))
+}
+
+impl LinkDetector {
+    /** Falls back to the default patterns if any of `patterns` is invalid */
+    pub fn new(patterns: &[String]) -> Self {
+        let pattern = combine(patterns).unwrap_or_else(|err| {
+            error!("Invalid link pattern, using the defaults instead: {err}");
+            combine(&DEFAULT_PATTERNS).unwrap()
+        });
+        Self { pattern }
+    }
+
+    /** Every link within the given rows */
+    pub fn find(&self, data: &DataComponent, rows: Range<usize>) -> Vec<Link> {
+        data.search(&self.pattern, rows)
+            .into_iter()
+            .map(|found| Link {
+                target: data.text(found.row, found.columns.clone()),
+                row: found.row,
+                columns: found.columns,
+            })
+            .collect()
+    }
+}
+
+/**
+ * `$VISUAL` or else `$EDITOR`, split into a program and its arguments the way
+ * a shell would, so that `code --wait` works
+ */
+pub fn terminal_editor() -> Vec<String> {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find_map(|editor| split_command(&editor))
+        .unwrap_or_else(|| vec!["vi".into()])
+}
+
+fn split_command(command: &str) -> Option<Vec<String>> {
+    let words = shlex::split(command).filter(|words| !words.is_empty());
+    if words.is_none() && !command.trim().is_empty() {
+        error!("Unable to split editor command {command:?}");
+    }
+    words
+}
+
+/** Hand `target` to whatever the desktop opens such things with */
+pub fn open(target: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // `start` would go through cmd, which gives `&` and friends a meaning of their own
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(target);
+    debug!("Opening {target}");
+    run(command);
+}
+
+/** Run a program in the background, logging if it fails */
+pub fn run(mut command: Command) {
+    // Wait on a thread of its own so the program does not linger as a zombie
+    std::thread::spawn(move || match command.status() {
+        Ok(status) if !status.success() => error!("{command:?} failed: {status}"),
+        Err(err) => error!("Failed to run {command:?}: {err}"),
+        Ok(_) => {}
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::Translator;
+
+    #[test]
+    fn test_find() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(
+            "see https://example.com/a_(b)?c=1, or\r\nmailto:me@example.org.\r\n".as_bytes(),
+            &mut data,
+        );
+        let detector = LinkDetector::new(&DEFAULT_PATTERNS.map(String::from));
+        assert_eq!(
+            detector.find(&data, 0..data.line_count()),
+            vec![
+                Link {
+                    row: 0,
+                    columns: 4..33,
+                    target: "https://example.com/a_(b)?c=1".into(),
+                },
+                Link {
+                    row: 1,
+                    columns: 0..21,
+                    target: "mailto:me@example.org".into(),
+                },
+            ]
+        );
+        assert_eq!(detector.find(&data, 1..1), vec![]);
+
+        let fallback = LinkDetector::new(&["(".into()]);
+        assert_eq!(fallback.find(&data, 0..1).len(), 1);
+    }
+
+    #[test]
+    fn test_file_location() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(
+            b"  --> src/main.rs:12:5\r\nat /tmp/x.py:3 from 10.0.0.1:22\r\nlib.c:7: github.com:22 example.com:80 host.tld:443\r\n",
+            &mut data,
+        );
+        let detector = LinkDetector::new(&DEFAULT_PATTERNS.map(String::from));
+        let targets: Vec<_> = detector
+            .find(&data, 0..data.line_count())
+            .into_iter()
+            .map(|link| link.target)
+            .collect();
+        assert_eq!(targets, vec!["src/main.rs:12:5", "/tmp/x.py:3", "lib.c:7"]);
+        assert_eq!(FileLocation::parse("github.com:22"), None);
+        assert_eq!(FileLocation::parse("host.tld:443"), None);
+        assert!(FileLocation::parse("./host.tld:443").is_some());
+
+        let location = FileLocation::parse("src/main.rs:12:5").unwrap();
+        assert_eq!(
+            location,
+            FileLocation {
+                path: "src/main.rs".into(),
+                line: 12,
+                column: Some(5),
+            }
+        );
+        assert_eq!(FileLocation::parse("https://example.com:80/a.rs:1"), None);
+        let command =
+            location.command(&["code".into(), "-g".into(), "{file}:{line}:{column}".into()]);
+        assert_eq!(command.get_program(), "code");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["-g", "src/main.rs:12:5"]
+        );
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command("code --wait"),
+            Some(vec!["code".into(), "--wait".into()])
+        );
+        assert_eq!(
+            split_command("'/opt/my editor/bin/ed' -n"),
+            Some(vec!["/opt/my editor/bin/ed".into(), "-n".into()])
+        );
+        assert_eq!(split_command("  "), None);
+        assert_eq!(split_command("vim 'unterminated"), None);
+    }
+}
@@ -1,39 +1,317 @@
 #![feature(assert_matches)]
-#![feature(try_trait_v2)]
 #![feature(async_closure)]
 
+mod accessibility;
+mod appearance;
+mod backdrop;
+mod backend;
+mod bars;
+mod bindings;
+mod cast;
 mod child;
+mod cli;
+mod colors;
 mod config;
-mod data;
-mod parser;
-mod translator;
+mod context_menu;
+mod copy_mode;
+mod debug_overlay;
+mod default_config;
+mod export;
+mod font_probe;
+mod fonts;
+mod foreground;
+mod gestures;
+mod grid_view;
+mod headless;
+mod hints;
+mod ipc;
+mod keys;
+mod links;
+mod marks;
+mod metrics;
+mod palette;
+mod paste;
+mod plugins;
+mod primary;
+mod prompts;
+mod recorder;
+mod saved_session;
+mod scrollbar;
+mod search;
+mod session_log;
+mod tab;
+mod terminfo;
+mod title_format;
+mod window_state;
 
+use firn_core::{bidi, data, pen, selection, translator, width};
+
+use accessibility::{Accessibility, Screen};
 use anyhow::Result;
-use config::Config;
-use data::DataComponent;
+use appearance::Appearance;
+use backdrop::Backdrop;
+use backend::{ControlKey, WindowSize};
+use bars::{
+    Bars, CONFIG_ERROR_ROWS, CONFIRM_BAR_ROWS, MARK_BAR_ROWS, PASTE_BAR_ROWS, RESTORE_BAR_ROWS,
+    SEARCH_BAR_ROWS, STATUS_BAR_ROWS, TAB_BAR_ROWS,
+};
+use bindings::{Action, Bindings};
+use child::{InputEvent, OutputEvent, SessionEvent, SessionId, SessionManager};
+use cli::{Cli, Subcommand};
+use colors::ColorScheme;
+use config::{BackendConfig, Config, ExitBehavior, RestoreSession, StartupMode, WheelKey};
+use context_menu::ContextMenu;
+use copy_mode::{CopyMode, Motion};
+use data::Position;
+use debug_overlay::DebugOverlay;
+use export::ExportFormat;
+use font_probe::FontProbe;
+use gestures::Flick;
+use grid_view::{GridView, SelectionEvent};
+use hints::{HintAction, Hints};
 use iced::event::{Event, Status};
-use iced::futures::channel::mpsc::Sender;
-use iced::widget::{scrollable, text};
-use iced::{executor, keyboard, Font, Length, Pixels};
-use iced::{subscription, window};
-use iced::{Application, Command, Element, Settings, Subscription, Theme};
-use log::debug;
-use std::path::Path;
-use translator::Translator;
+use iced::widget::{
+    button, canvas, checkbox, column, container, horizontal_space, image, row, text, text_input,
+    Column, Row,
+};
+use iced::{application, clipboard, font, subscription, time, window};
+use iced::{executor, keyboard, Alignment, Font, Length, Point, Size};
+use iced::{theme, Application, Command, Element, Settings, Subscription, Theme};
+use ipc::{RemoteRequest, Request, Response};
+use links::{FileLocation, LinkDetector};
+use marks::MarkBar;
+use metrics::{CellMetrics, MAX_FONT_SIZE, MIN_FONT_SIZE, ZOOM_STEP};
+use plugins::Plugins;
+use primary::PrimarySelection;
+use regex::Regex;
+use saved_session::{SavedSession, SavedTab};
+use search::Search;
+use selection::{Selection, SelectionUnit};
+use session_log::SessionLog;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+use tab::Tab;
+use tracing::{debug, error, info};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::EnvFilter;
+use window_state::WindowState;
 
 struct Firn {
-    data: DataComponent,
-    translator: Translator,
-    scrollable_id: scrollable::Id,
-    child_sender: Option<Sender<child::InputEvent>>,
+    tabs: Vec<Tab>,
+    /** Index of the tab being shown */
+    active: usize,
+    sessions: SessionManager,
+    colors: ColorScheme,
     theme: Theme,
     config: Config,
+    window_size: Size<u32>,
+    /** What the shells were last told their size is */
+    pty_size: Option<WindowSize>,
+    /** Where the window manager last put the window, if it said */
+    window_position: Option<(i32, i32)>,
+    font: Font,
+    font_size: f32,
+    /** How far the font advances per character, relative to its size, once it has been measured */
+    advance: Option<f32>,
+    swallow_character: bool,
+    /** Shells are only spawned once this is known, so that they can be told about it */
+    window_id: Option<u64>,
+    /** The window was asked to close and is waiting for the shells to exit */
+    closing: bool,
+    fullscreen: bool,
+    /** The visual bell is showing */
+    flashing: bool,
+    /** When the bell was last heard, so that a flood of BELs makes a single sound */
+    bell_sounded: Option<Instant>,
+    focused: bool,
+    /** Toggled while the cursor blinks */
+    cursor_visible: bool,
+    /** `CharacterReceived` does not say which modifiers were held */
+    modifiers: keyboard::Modifiers,
+    /** The search bar, while it is open */
+    search: Option<Search>,
+    /** The tabs saved when the last window closed, while the bar offering to reopen them shows */
+    restore_offer: Option<SavedSession>,
+    /** The bar naming a new mark, while it is open */
+    mark_bar: Option<MarkBar>,
+    /** What the desktop last said it prefers, if the colors follow it */
+    appearance: Option<Appearance>,
+    background_image: Option<image::Handle>,
+    links: LinkDetector,
+    /** Finds what hints mode labels: links, and whatever else `hint_patterns` describe */
+    hint_detector: LinkDetector,
+    /** Finds prompts for shells that do not mark them */
+    prompt_pattern: Option<Regex>,
+    /** Labels over the grid, while hints mode is on */
+    hints: Option<Hints>,
+    /** The keyboard-driven cursor for selecting text, while copy mode is on */
+    copy_mode: Option<CopyMode>,
+    /** Closing that waits for the user to confirm it, since programs are still running */
+    pending_close: Option<PendingClose>,
+    /** Scrolling that carries on after a finger flicked the grid */
+    flick: Option<Flick>,
+    /** Clipboard text that waits for the user to look it over before it is pasted */
+    pending_paste: Option<PendingPaste>,
+    context_menu: Option<OpenMenu>,
+    bindings: Bindings,
+    /** Fonts of the profiles that set one, by profile name */
+    profile_fonts: BTreeMap<String, Font>,
+    /** Why the config files were ignored, shown above the grid until dismissed */
+    config_error: Option<String>,
+    debug_overlay: Option<DebugOverlay>,
+    plugins: Plugins,
+    /** What screen readers are shown, if `screen_reader` is on */
+    accessibility: Option<Accessibility>,
+    /** Where selected text goes, to be pasted with the middle button */
+    primary: Option<PrimarySelection>,
+}
+
+/** What would be closed, and the programs that would go with it */
+struct PendingClose {
+    /** A single tab, or else the whole window */
+    tab: Option<SessionId>,
+    processes: Vec<String>,
+}
+
+/** A paste that waits for confirmation */
+struct PendingPaste {
+    text: String,
+    /** The text on one line, once the user chose to edit it */
+    editing: Option<String>,
+    input_id: text_input::Id,
+}
+
+/** Where the context menu was opened, and the link the pointer was over then */
+struct OpenMenu {
+    position: Point,
+    link: Option<String>,
+}
+
+/** Entries of the context menu */
+#[derive(Debug, Clone)]
+pub enum MenuItem {
+    Copy,
+    Paste,
+    SelectAll,
+    CopyLastOutput,
+    ClearScrollback,
+    NewTab,
+    /** A new tab with the settings of this profile */
+    NewProfileTab(String),
+    OpenLink(String),
+}
+
+/**
+ * Size and position to open the window with: configured dimensions take
+ * precedence over the geometry remembered from last time
+ */
+fn initial_geometry(config: &Config) -> (Size<u32>, Option<(i32, i32)>) {
+    let saved = config
+        .window
+        .remember_geometry
+        .then(WindowState::load)
+        .flatten();
+    let position = saved.and_then(|saved| saved.position);
+    let size = match (config.window.dimensions, saved) {
+        (Some(dimensions), _) => CellMetrics::from_font(&config.font, config.font.size, None)
+            .spaced(config.font.line_height, config.font.letter_spacing)
+            .window_size(
+                WindowSize {
+                    rows: dimensions.rows,
+                    cols: dimensions.cols,
+                },
+                config.window.padding,
+            ),
+        (None, Some(saved)) => Size::new(saved.width, saved.height),
+        (None, None) => Size::new(INITIAL_WINDOW_SIZE.0, INITIAL_WINDOW_SIZE.1),
+    };
+    (size, position)
 }
 
+/** Exit status of the firn process itself, set when the shell could not be started */
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+const INITIAL_WINDOW_SIZE: (u32, u32) = (1024, 768);
+const DEFAULT_BAUD_RATE: u32 = 115_200;
+/** Lines of a paste the bar asking about it shows */
+const PASTE_PREVIEW_LINES: usize = 3;
+
 #[derive(Debug, Clone)]
 pub enum Message {
     ApplicationEvent(Event),
-    ChildEvent(child::OutputEvent),
+    ChildEvent(SessionEvent),
+    EscTimeout,
+    /** Time to offer input held back by a full channel to the shells again */
+    RetryInput,
+    WindowId(u64),
+    /** Scroll back this many lines, or forward if negative */
+    Scroll(isize),
+    /** The mouse wheel turned this many lines up, or down if negative */
+    Wheel(isize),
+    CursorBlink,
+    /** A frame was drawn, while the debug overlay is timing them */
+    Frame(Instant),
+    Selection(SelectionEvent),
+    /** Read the clipboard and paste it */
+    Paste,
+    /** Read the primary selection and paste it, on middle click */
+    PastePrimary,
+    ClipboardRead(Option<String>),
+    SearchInput(String),
+    SearchRegex(bool),
+    /** Enter in the search bar: jump to the next match, or the previous with Shift */
+    SearchSubmit,
+    /** Search another batch of older lines */
+    SearchScan,
+    /** Escape in a bar with a text input: the mark bar if it is open, else the paste, else the search */
+    CloseBar,
+    MarkInput(String),
+    /** Enter in the mark bar: mark the line and close the bar */
+    MarkSubmit,
+    /** Scroll the marked line to the top of the view */
+    JumpToMark(usize),
+    /** A finger flicked the grid, scrolling on at this many lines per second, or 0 to stop */
+    Flick(f32),
+    /** Time to scroll a flick on */
+    FlickStep(Instant),
+    SelectTab(usize),
+    FontLoaded(Result<(), font::Error>),
+    /** The font turned out to advance this far per character, relative to its size */
+    FontMeasured(f32),
+    /** Change the font size to this, keeping the window size */
+    Zoom(f32),
+    Appearance(Option<Appearance>),
+    /** Ctrl+click on a link */
+    OpenLink(String),
+    ConfirmClose,
+    CancelClose,
+    /** Paste what the paste bar asks about, as edited if it was */
+    ConfirmPaste,
+    /** Put the paste into a text input to change it before it is sent */
+    EditPaste,
+    PasteInput(String),
+    CancelPaste,
+    /** The visual bell has been showing for long enough */
+    FlashEnded,
+    /** Right click at this point in the window, over this link if any */
+    OpenContextMenu(Point, Option<String>),
+    CloseContextMenu,
+    MenuItem(MenuItem),
+    /** The window as it looked when Ctrl+Shift+S was pressed, to export as a PNG */
+    Screenshot(window::Screenshot),
+    DismissConfigError,
+    /** Reopen the tabs saved when the last window closed */
+    RestoreSession,
+    DismissRestore,
+    /** A command from `firn msg`, to answer */
+    Remote(RemoteRequest),
+    /** Time to see which tabs have fallen silent */
+    CheckSilence,
+    /** Time to read out output that arrived too soon after the last announcement */
+    Announce,
 }
 
 impl Application for Firn {
@@ -42,117 +320,2648 @@ impl Application for Firn {
     type Executor = executor::Default;
     type Flags = Config;
 
-    fn new(config: Config) -> (Self, Command<Message>) {
+    fn new(mut config: Config) -> (Self, Command<Message>) {
+        if config.remote_control {
+            let socket = ipc::socket_path().to_string_lossy().into_owned();
+            config.env.insert(ipc::SOCKET_ENV.into(), socket);
+        }
+        let (window_size, window_position) = initial_geometry(&config);
+        // A serial line or a recording has no shells and directories to reopen
+        let saved = matches!(config.backend, BackendConfig::Pty)
+            .then(SavedSession::load)
+            .flatten();
+        let (restored, restore_offer) = match (config.restore_session, saved) {
+            (RestoreSession::Always, Some(saved)) => (Some(saved), None),
+            (RestoreSession::Ask, Some(saved)) if saved.is_worth_offering() => (None, Some(saved)),
+            _ => (None, None),
+        };
+        let colors = config.resolve_colors(&config.color_scheme);
+        let mut sessions = SessionManager::new();
+        let mut tabs = vec![];
+        if restored.is_none() {
+            let session = sessions.spawn(
+                config.clone(),
+                CellMetrics::from_font(&config.font, config.font.size, None)
+                    .spaced(config.font.line_height, config.font.letter_spacing)
+                    .grid_size(window_size, config.window.padding),
+            );
+            tabs.push(Tab::new(session, &colors, config.width_policy));
+        }
+        let font = fonts::primary(&config.font);
+        let font_size = config.font.size;
+        let background_image = config
+            .window
+            .background_image
+            .clone()
+            .map(image::Handle::from_path);
+        let links = LinkDetector::new(&config.link_patterns);
+        let prompt_pattern = config
+            .prompt_pattern
+            .as_deref()
+            .and_then(|pattern| Regex::new(pattern).ok());
+        let hint_detector =
+            LinkDetector::new(&[&config.link_patterns[..], &config.hint_patterns[..]].concat());
+        let profile_fonts = config
+            .profiles
+            .iter()
+            .filter_map(|(name, profile)| {
+                Some((name.clone(), fonts::primary(profile.font.as_ref()?)))
+            })
+            .collect();
+        let load_fonts = Command::batch(
+            std::iter::once(&config.font)
+                .chain(
+                    config
+                        .profiles
+                        .values()
+                        .filter_map(|profile| profile.font.as_ref()),
+                )
+                .map(|font| fonts::load_fallbacks(font, Message::FontLoaded)),
+        );
+        let config_error = config.load_error.clone();
+        let bindings = Bindings::new(&config.keybindings).unwrap_or_else(|err| {
+            error!("{err:#}, using the default keybindings");
+            Bindings::new(&Default::default()).unwrap()
+        });
+        let plugins = Plugins::new().unwrap();
+        for path in &config.plugins {
+            if let Err(err) = plugins.load(path) {
+                error!("Unable to load a plugin: {err:#}");
+            }
+        }
+        let fullscreen = config.window.startup_mode == StartupMode::Fullscreen;
+        let startup_mode = match config.window.startup_mode {
+            StartupMode::Windowed => Command::none(),
+            StartupMode::Maximized => window::maximize(true),
+            StartupMode::Fullscreen => window::change_mode(window::Mode::Fullscreen),
+        };
+        let check_appearance = if config.follows_appearance() {
+            Command::perform(appearance::detect(), Message::Appearance)
+        } else {
+            Command::none()
+        };
+        let accessibility = config.screen_reader.then(Accessibility::new).flatten();
+        let mut firn = Self {
+            tabs,
+            active: 0,
+            sessions,
+            theme: colors.theme(),
+            colors,
+            config,
+            window_size,
+            pty_size: None,
+            window_position,
+            font,
+            font_size,
+            advance: None,
+            swallow_character: false,
+            window_id: None,
+            closing: false,
+            fullscreen,
+            flashing: false,
+            bell_sounded: None,
+            focused: true,
+            cursor_visible: true,
+            modifiers: keyboard::Modifiers::empty(),
+            search: None,
+            restore_offer,
+            mark_bar: None,
+            appearance: None,
+            background_image,
+            links,
+            prompt_pattern,
+            hint_detector,
+            hints: None,
+            copy_mode: None,
+            pending_close: None,
+            pending_paste: None,
+            flick: None,
+            context_menu: None,
+            config_error,
+            bindings,
+            profile_fonts,
+            debug_overlay: None,
+            plugins,
+            accessibility,
+            primary: PrimarySelection::connect(),
+        };
+        if let Some(saved) = restored {
+            firn.restore(saved);
+        } else if firn.config.session_log.enabled {
+            firn.start_log(0);
+        }
         (
-            Self {
-                data: DataComponent::new(),
-                translator: Translator::new().unwrap(),
-                scrollable_id: scrollable::Id::unique(),
-                child_sender: None,
-                theme: Theme::Dark,
-                config,
-            },
-            Command::none(),
+            firn,
+            Command::batch([
+                window::fetch_id(Message::WindowId),
+                startup_mode,
+                load_fonts,
+                check_appearance,
+            ]),
         )
     }
 
     fn title(&self) -> String {
-        String::from("Firn Terminal")
+        self.tab_title(self.tab(), &self.config.window.title)
     }
 
     fn view(&self) -> Element<Message> {
-        scrollable(
-            text(self.data.render(self.config.render_lines))
-                .font(Font::MONOSPACE)
-                .size(Pixels::from(16)),
+        let grid = self.grid_view();
+        let mut content = Column::new();
+        if let Some(error) = &self.config_error {
+            content = content.push(self.config_error_bar(error));
+        }
+        if let Some(pending) = &self.pending_close {
+            content = content.push(self.confirm_bar(pending));
+        }
+        if let Some(pending) = &self.pending_paste {
+            content = content.push(self.paste_bar(pending));
+        }
+        if let Some(saved) = &self.restore_offer {
+            content = content.push(self.restore_bar(saved));
+        }
+        if self.tabs.len() > 1 {
+            content = content.push(self.tab_bar());
+        }
+        if let Some(search) = &self.search {
+            content = content.push(self.search_bar(search));
+        }
+        if let Some(mark_bar) = &self.mark_bar {
+            content = content.push(self.mark_bar(mark_bar));
+        }
+        let mut content = content.push(
+            container(canvas(grid).width(Length::Fill).height(Length::Fill))
+                .padding(self.config.window.padding),
+        );
+        if self.config.status_bar {
+            content = content.push(self.status_bar());
+        }
+        let content: Element<Message> = match &self.background_image {
+            Some(image) => {
+                let mut wash = colors::color(self.colors.background);
+                wash.a = 1.0 - self.config.window.background_image_opacity;
+                Backdrop::new(image.clone(), wash, content).into()
+            }
+            None => content.into(),
+        };
+        let menu = self
+            .context_menu
+            .as_ref()
+            .map(|menu| (menu.position, self.menu(menu)));
+        let content = ContextMenu::new(content, menu, Message::CloseContextMenu);
+        FontProbe::new(
+            content,
+            self.font,
+            self.font_size,
+            self.advance,
+            Message::FontMeasured,
         )
-        .width(Length::Fill)
-        .id(self.scrollable_id.clone())
         .into()
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
+        let command = self.handle_message(message);
+        // A bar that opened or closed changes how many rows the shells have
+        if self.pty_size != Some(self.pty_size()) {
+            self.resize_pty();
+        }
+        for tab in &mut self.tabs {
+            tab.apply_damage();
+        }
+        self.update_accessibility();
+        command
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let esc_timeout = if self.tabs.iter().any(|tab| tab.translator.is_lone_esc()) {
+            time::every(translator::ESC_TIMEOUT).map(|_| Message::EscTimeout)
+        } else {
+            Subscription::none()
+        };
+        let retry_input = if self.sessions.is_backlogged() {
+            time::every(child::RETRY_INTERVAL).map(|_| Message::RetryInput)
+        } else {
+            Subscription::none()
+        };
+        let cursor_blink = if self.config.cursor_blink && self.focused {
+            let interval = Duration::from_millis(self.config.cursor_blink_interval_ms);
+            time::every(interval).map(|_| Message::CursorBlink)
+        } else {
+            Subscription::none()
+        };
+        let check_appearance = if self.config.follows_appearance() {
+            appearance::subscription().map(Message::Appearance)
+        } else {
+            Subscription::none()
+        };
+        let frames = if self.debug_overlay.is_some() {
+            window::frames().map(Message::Frame)
+        } else {
+            Subscription::none()
+        };
+        let announce = if self
+            .accessibility
+            .as_ref()
+            .is_some_and(Accessibility::is_waiting)
+        {
+            time::every(accessibility::ANNOUNCE_INTERVAL).map(|_| Message::Announce)
+        } else {
+            Subscription::none()
+        };
+        let searching = self
+            .search
+            .iter()
+            .chain(
+                self.copy_mode
+                    .as_ref()
+                    .and_then(|copy_mode| copy_mode.search.as_ref()),
+            )
+            .any(|search| !search.is_complete());
+        let search_scan = if searching {
+            time::every(search::SCAN_INTERVAL).map(|_| Message::SearchScan)
+        } else {
+            Subscription::none()
+        };
+        let watching_silence = self.config.monitor.silence_secs.is_some()
+            && self.tabs.iter().any(|tab| tab.last_output.is_some());
+        let check_silence = if watching_silence {
+            time::every(Duration::from_secs(1)).map(|_| Message::CheckSilence)
+        } else {
+            Subscription::none()
+        };
+        let flick = if self.flick.is_some() {
+            time::every(gestures::FLICK_INTERVAL).map(Message::FlickStep)
+        } else {
+            Subscription::none()
+        };
+        let remote_control = if self.config.remote_control {
+            ipc::subscription(ipc::socket_path()).map(Message::Remote)
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch([
+            esc_timeout,
+            retry_input,
+            cursor_blink,
+            check_appearance,
+            frames,
+            announce,
+            search_scan,
+            check_silence,
+            flick,
+            remote_control,
+            // The shells start out at the size of the grid as the font is measured
+            if self.window_id.is_some() && self.metrics_known() {
+                self.sessions.subscription().map(Message::ChildEvent)
+            } else {
+                Subscription::none()
+            },
+            subscription::events_with(|event, status| match (&event, status) {
+                (Event::Keyboard(_) | Event::Window(_), Status::Ignored) => {
+                    Some(Message::ApplicationEvent(event))
+                }
+                // The search and mark bars' inputs swallow Escape, which is what closes them
+                (
+                    Event::Keyboard(keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Escape,
+                        ..
+                    }),
+                    Status::Captured,
+                ) => Some(Message::CloseBar),
+                _ => None,
+            }),
+        ])
+    }
+
+    fn theme(&self) -> Theme {
+        self.theme.clone()
+    }
+
+    fn style(&self) -> theme::Application {
+        let opacity = self.config.window.opacity;
+        theme::Application::from(move |theme: &Theme| {
+            let palette = theme.palette();
+            application::Appearance {
+                background_color: iced::Color {
+                    a: opacity,
+                    ..palette.background
+                },
+                text_color: palette.text,
+            }
+        })
+    }
+}
+
+impl Firn {
+    fn handle_message(&mut self, message: Message) -> Command<Message> {
         debug!("Recv message: {message:?}");
+        if let Message::ApplicationEvent(Event::Keyboard(_)) = &message {
+            // Keep the cursor solid while typing
+            self.cursor_visible = true;
+        }
+        if let Message::ChildEvent(event) = &message {
+            self.sessions.handle_event(event);
+        }
         match message {
-            Message::ChildEvent(child::OutputEvent::Connected(sender)) => {
-                self.child_sender = Some(sender);
+            Message::ChildEvent(event) => self.handle_session_event(event),
+            Message::SearchInput(query) => {
+                if let Some(search) = self.search.as_mut() {
+                    search.query = query;
+                    search.update(&self.tabs[self.active].data);
+                }
+                self.reveal_current_match();
+                Command::none()
+            }
+            Message::SearchRegex(regex) => {
+                if let Some(search) = self.search.as_mut() {
+                    search.regex = regex;
+                    search.update(&self.tabs[self.active].data);
+                }
+                self.reveal_current_match();
+                Command::none()
+            }
+            Message::SearchSubmit if self.copy_mode.is_some() => {
+                // Like `/` in vi: Enter goes back to moving around, starting from the match
+                let search = self.search.take();
+                let copy_mode = self.copy_mode.as_mut().unwrap();
+                if let Some(found) = search
+                    .as_ref()
+                    .and_then(|search| Some(&search.matches[search.current?]))
+                {
+                    copy_mode.cursor = Position {
+                        row: found.row,
+                        col: found.columns.start,
+                    };
+                }
+                copy_mode.search = search;
+                self.follow_copy_cursor();
+                Command::none()
+            }
+            Message::SearchSubmit => {
+                if let Some(search) = self.search.as_mut() {
+                    if self.modifiers.shift() {
+                        search.previous(&self.tabs[self.active].data);
+                    } else {
+                        search.next();
+                    }
+                }
+                self.reveal_current_match();
+                Command::none()
+            }
+            Message::SearchScan => {
+                let copy_mode_search = self
+                    .copy_mode
+                    .as_mut()
+                    .and_then(|copy_mode| copy_mode.search.as_mut());
+                for search in self.search.iter_mut().chain(copy_mode_search) {
+                    search.scan(&self.tabs[self.active].data);
+                }
+                Command::none()
+            }
+            Message::CloseBar => {
+                if self.mark_bar.take().is_none() && self.pending_paste.take().is_none() {
+                    self.search = None;
+                }
+                Command::none()
+            }
+            Message::MarkInput(name) => {
+                if let Some(mark_bar) = self.mark_bar.as_mut() {
+                    mark_bar.name = name;
+                }
+                Command::none()
+            }
+            Message::MarkSubmit => {
+                if let Some(mark_bar) = self.mark_bar.take() {
+                    self.tab_mut().marks.add(mark_bar.row, &mark_bar.name);
+                }
+                Command::none()
+            }
+            Message::JumpToMark(row) => {
+                self.scroll_to_top(row);
+                Command::none()
+            }
+            Message::Flick(velocity) => {
+                self.flick = (velocity != 0.0).then(|| Flick::new(velocity, Instant::now()));
+                Command::none()
+            }
+            Message::FlickStep(now) => {
+                match self.flick.as_mut().map(|flick| flick.step(now)) {
+                    Some(Some(lines)) => {
+                        if lines != 0 && !self.wheel_to_keys(lines) {
+                            self.scroll(lines);
+                        }
+                    }
+                    _ => self.flick = None,
+                }
+                Command::none()
+            }
+            Message::SelectTab(index) => {
+                self.select_tab(index);
+                Command::none()
+            }
+            Message::Appearance(appearance) => {
+                if appearance != self.appearance {
+                    self.appearance = appearance;
+                    let name = self.config.color_scheme_for(appearance);
+                    self.set_colors(self.config.resolve_colors(name));
+                }
+                Command::none()
+            }
+            Message::ConfirmClose => match self.pending_close.take() {
+                Some(PendingClose { tab: Some(id), .. }) => {
+                    match self.tabs.iter().position(|tab| tab.session == id) {
+                        Some(index) => self.close_tab(index),
+                        None => Command::none(),
+                    }
+                }
+                Some(PendingClose { tab: None, .. }) => self.close_window(),
+                None => Command::none(),
+            },
+            Message::FlashEnded => {
+                self.flashing = false;
+                Command::none()
+            }
+            Message::CancelClose => {
+                self.pending_close = None;
+                Command::none()
+            }
+            Message::ConfirmPaste => {
+                if let Some(pending) = self.pending_paste.take() {
+                    let text = match pending.editing {
+                        Some(edited) => paste::from_one_line(&edited),
+                        None => pending.text,
+                    };
+                    self.paste(&text);
+                }
+                Command::none()
+            }
+            Message::EditPaste => match self.pending_paste.as_mut() {
+                Some(pending) => {
+                    pending.editing = Some(paste::to_one_line(&pending.text));
+                    text_input::focus(pending.input_id.clone())
+                }
+                None => Command::none(),
+            },
+            Message::PasteInput(edited) => {
+                if let Some(pending) = self.pending_paste.as_mut() {
+                    pending.editing = Some(edited);
+                }
+                Command::none()
+            }
+            Message::CancelPaste => {
+                self.pending_paste = None;
+                Command::none()
+            }
+            Message::OpenLink(target) => {
+                self.open_link(&target);
+                Command::none()
+            }
+            Message::OpenContextMenu(position, link) => {
+                self.context_menu = Some(OpenMenu { position, link });
+                Command::none()
+            }
+            Message::DismissConfigError => {
+                self.config_error = None;
+                Command::none()
+            }
+            Message::RestoreSession => {
+                if let Some(saved) = self.restore_offer.take() {
+                    self.restore(saved);
+                }
+                Command::none()
+            }
+            Message::DismissRestore => {
+                self.restore_offer = None;
+                Command::none()
+            }
+            Message::CloseContextMenu => {
+                self.context_menu = None;
+                Command::none()
+            }
+            Message::MenuItem(item) => {
+                self.context_menu = None;
+                self.menu_item(item)
+            }
+            Message::Zoom(font_size) => {
+                self.zoom(font_size);
+                Command::none()
+            }
+            Message::FontLoaded(_) => {
+                // Glyphs drawn before may now come out differently
+                for tab in &self.tabs {
+                    tab.grid_cache.clear();
+                }
+                Command::none()
+            }
+            Message::FontMeasured(advance) => {
+                let first = self.advance.is_none();
+                self.advance = Some(advance);
+                for tab in &self.tabs {
+                    tab.grid_cache.clear();
+                }
+                // The shells are told about the new grid size along with any other change to it
+                match self.config.window.dimensions {
+                    // The window was sized before the font could be measured
+                    Some(dimensions) if first => window::resize(self.metrics().window_size(
+                        WindowSize {
+                            rows: dimensions.rows,
+                            cols: dimensions.cols,
+                        },
+                        self.config.window.padding,
+                    )),
+                    _ => Command::none(),
+                }
+            }
+            Message::Selection(SelectionEvent::Started(position, unit)) => {
+                let (start, end) = self.unit_at(position, unit);
+                self.tab_mut().selection = Some(Selection::spanning(start, end, unit));
+                Command::none()
+            }
+            Message::Selection(SelectionEvent::Extended(position)) => {
+                let Some(unit) = self
+                    .tab()
+                    .selection
+                    .as_ref()
+                    .map(|selection| selection.unit)
+                else {
+                    return Command::none();
+                };
+                let (start, end) = self.unit_at(position, unit);
+                if let Some(selection) = self.tab_mut().selection.as_mut() {
+                    selection.extend(start, end);
+                }
+                Command::none()
+            }
+            Message::Selection(SelectionEvent::Finished) => {
+                let tab = self.tab();
+                match &tab.selection {
+                    // A click without a drag just clears the selection
+                    Some(selection)
+                        if selection.unit == SelectionUnit::Cell
+                            && selection.anchor == selection.head =>
+                    {
+                        self.tab_mut().selection = None;
+                        Command::none()
+                    }
+                    Some(selection) => {
+                        if let Some(primary) = &self.primary {
+                            primary.write(self.selected_text(tab, selection));
+                        }
+                        if self.config.copy_on_select {
+                            self.copy_selection()
+                        } else {
+                            Command::none()
+                        }
+                    }
+                    None => Command::none(),
+                }
+            }
+            Message::Paste => clipboard::read(Message::ClipboardRead),
+            Message::PastePrimary => match &self.primary {
+                Some(primary) => Command::perform(primary.clone().read(), Message::ClipboardRead),
+                None => clipboard::read(Message::ClipboardRead),
+            },
+            Message::ClipboardRead(Some(text)) => {
+                let bracketed = self.tab().data.modes().bracketed_paste;
+                if self.config.confirm_paste
+                    && paste::is_risky(&text, self.config.sanitize_paste, bracketed)
+                {
+                    self.pending_paste = Some(PendingPaste {
+                        text,
+                        editing: None,
+                        input_id: text_input::Id::unique(),
+                    });
+                } else {
+                    self.paste(&text);
+                }
+                Command::none()
+            }
+            Message::CursorBlink => {
+                self.cursor_visible = !self.cursor_visible;
+                Command::none()
+            }
+            Message::Announce => Command::none(),
+            Message::CheckSilence => {
+                self.check_silence();
+                Command::none()
+            }
+            Message::Remote(remote) => {
+                let (response, command) = self.remote_control(remote.request.clone());
+                remote.reply(response);
+                command
+            }
+            Message::Frame(now) => {
+                if let Some(debug_overlay) = self.debug_overlay.as_mut() {
+                    debug_overlay.record_frame(now);
+                }
+                Command::none()
+            }
+            Message::Scroll(lines) => {
+                self.scroll(lines);
+                Command::none()
+            }
+            Message::Wheel(lines) => {
+                if !self.wheel_to_keys(lines) {
+                    self.scroll(lines);
+                }
+                Command::none()
+            }
+            Message::WindowId(id) => {
+                self.window_id = Some(id);
+                if let Some(window_id) = x11_window_id(id) {
+                    self.config
+                        .env
+                        .insert("WINDOWID".into(), window_id.to_string());
+                    self.sessions.set_env("WINDOWID", &window_id.to_string());
+                }
+                Command::none()
+            }
+            Message::RetryInput => {
+                self.sessions.retry();
+                Command::none()
+            }
+            Message::EscTimeout => {
+                for index in 0..self.tabs.len() {
+                    let tab = &mut self.tabs[index];
+                    tab.translator.flush_stale(&mut tab.data);
+                    self.send_responses(index);
+                }
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::CharacterReceived(_)))
+                if self.swallow_character =>
+            {
+                self.swallow_character = false;
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::CharacterReceived(_)))
+                if self.pending_close.is_some() =>
+            {
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                ..
+            })) if self.pending_close.is_some() => {
+                // Both keys also type a character, which would reach the shell once the bar is gone
+                let message = match key_code {
+                    keyboard::KeyCode::Enter | keyboard::KeyCode::NumpadEnter => {
+                        Message::ConfirmClose
+                    }
+                    keyboard::KeyCode::Escape => Message::CancelClose,
+                    _ => return Command::none(),
+                };
+                self.swallow_character = true;
+                self.update(message)
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::CharacterReceived(_)))
+                if self.pending_paste.is_some() =>
+            {
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                ..
+            })) if self.pending_paste.is_some() => {
+                // While editing, the text input has these keys to itself
+                let message = match key_code {
+                    keyboard::KeyCode::Enter | keyboard::KeyCode::NumpadEnter => {
+                        Message::ConfirmPaste
+                    }
+                    keyboard::KeyCode::Escape => Message::CancelPaste,
+                    _ => return Command::none(),
+                };
+                self.swallow_character = true;
+                self.update(message)
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::Escape,
+                ..
+            })) if self.context_menu.is_some() => {
+                self.swallow_character = true;
+                self.context_menu = None;
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            })) if self.bindings.get(key_code, modifiers).is_some() => {
+                self.swallow_character = bindings::types_character(key_code);
+                let action = self.bindings.get(key_code, modifiers).unwrap().clone();
+                self.run_action(action)
+            }
+            Message::Screenshot(screenshot) => {
+                let cropped = screenshot.crop(self.export_region(screenshot.size));
+                match cropped
+                    .map_err(anyhow::Error::from)
+                    .and_then(|cropped| export::png(&cropped))
+                {
+                    Ok(png) => self.export(ExportFormat::Png, &png),
+                    Err(err) => error!("Unable to export the screen: {err:#}"),
+                }
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::CharacterReceived(ch)))
+                if self.copy_mode.is_some() =>
+            {
+                self.copy_mode_key(ch)
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                ..
+            })) if self.copy_mode.is_some() => {
+                // Bound keys keep working; other keys move the cursor or are ignored
+                let motion = match key_code {
+                    keyboard::KeyCode::Escape => {
+                        self.swallow_character = true;
+                        self.exit_copy_mode();
+                        return Command::none();
+                    }
+                    keyboard::KeyCode::Left => Motion::Left,
+                    keyboard::KeyCode::Right => Motion::Right,
+                    keyboard::KeyCode::Up => Motion::Up,
+                    keyboard::KeyCode::Down => Motion::Down,
+                    keyboard::KeyCode::Home => Motion::LineStart,
+                    keyboard::KeyCode::End => Motion::LineEnd,
+                    _ => return Command::none(),
+                };
+                self.move_copy_cursor(motion);
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::CharacterReceived(ch)))
+                if self.hints.is_some() =>
+            {
+                self.pick_hint(ch)
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                ..
+            })) if self.hints.is_some() => {
+                // Keys only type labels until a hint is picked or Escape leaves hints mode
+                if key_code == keyboard::KeyCode::Escape {
+                    self.hints = None;
+                    self.swallow_character = true;
+                }
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: key_code @ (keyboard::KeyCode::Escape | keyboard::KeyCode::End),
+                modifiers,
+            })) if self.tab().scrollback > 0 && modifiers.is_empty() => {
+                // While history is in view these go back to the live screen, not to the shell
+                self.swallow_character = key_code == keyboard::KeyCode::Escape;
+                self.scroll_to_bottom();
                 Command::none()
             }
-            Message::ChildEvent(child::OutputEvent::Disconnected) => window::close(),
-            Message::ChildEvent(child::OutputEvent::Stdout(text)) => {
-                self.translator.write(&text, &mut self.data);
-                scrollable::snap_to(self.scrollable_id.clone(), scrollable::RelativeOffset::END)
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::CharacterReceived(ch)))
+                if control_key(ch).is_some() =>
+            {
+                // Already sent when the key was pressed
+                Command::none()
             }
             Message::ApplicationEvent(Event::Keyboard(keyboard::Event::CharacterReceived(ch))) => {
-                self.send_to_child(child::InputEvent::Stdin(String::from(ch).as_bytes().into()))
-                    .unwrap();
+                // Text composed with an input method also arrives here, one character at a time,
+                // once it is committed. iced 0.10 neither turns on winit's IME support nor passes
+                // on its preedit events, so there is no composition in progress to draw.
+                let text = keys::encode_character(ch, self.modifiers.alt(), self.config.meta);
+                self.send_input(InputEvent::Stdin(text));
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::ModifiersChanged(
+                modifiers,
+            ))) => {
+                self.modifiers = modifiers;
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            })) if modifiers.control() && control_key_code(key_code).is_some() => {
+                // Handle these on key press rather than waiting for the control character, which
+                // some input methods and keyboard layouts never deliver
+                let key = control_key_code(key_code).unwrap();
+                self.send_input(InputEvent::Control(key));
                 Command::none()
             }
             Message::ApplicationEvent(Event::Keyboard(keyboard::Event::KeyPressed {
                 key_code,
-                modifiers: _,
+                modifiers,
             })) => {
-                let text = match key_code {
-                    keyboard::KeyCode::Up => Some("\u{1b}[A".to_string()),
-                    keyboard::KeyCode::Down => Some("\u{1b}[B".to_string()),
-                    keyboard::KeyCode::Right => Some("\u{1b}[C".to_string()),
-                    keyboard::KeyCode::Left => Some("\u{1b}[D".to_string()),
-                    _ => None,
-                };
-                if let Some(text) = text {
-                    debug!("Send character to shell: {text}");
-                    self.send_to_child(child::InputEvent::Stdin(text.into_bytes()))
-                        .unwrap();
+                if let Some(text) = keys::encode(key_code, modifiers, self.tab().data.modes()) {
+                    debug!("Send key to shell: {text:?}");
+                    // Keypad keys also produce a character, which the sequence replaces
+                    self.swallow_character = keys::is_keypad(key_code);
+                    self.send_input(InputEvent::Stdin(text.into_bytes()));
                 }
                 Command::none()
             }
+            Message::ApplicationEvent(Event::Window(window::Event::Focused)) => {
+                self.focused = true;
+                if let Some(accessibility) = self.accessibility.as_mut() {
+                    accessibility.set_focused(true);
+                }
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Window(window::Event::Unfocused)) => {
+                self.focused = false;
+                if let Some(accessibility) = self.accessibility.as_mut() {
+                    accessibility.set_focused(false);
+                }
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Window(window::Event::CloseRequested)) => {
+                let processes = self.busy_processes(&self.tabs);
+                if processes.is_empty() || self.closing {
+                    self.close_window()
+                } else {
+                    self.pending_close = Some(PendingClose {
+                        tab: None,
+                        processes,
+                    });
+                    Command::none()
+                }
+            }
+            Message::ApplicationEvent(Event::Window(window::Event::FileDropped(path))) => {
+                // Several files arrive one event each, so each is followed by a space
+                let text = format!("{} ", paste::shell_quote(&path.to_string_lossy()));
+                self.paste(&text);
+                Command::none()
+            }
+            Message::ApplicationEvent(Event::Window(window::Event::Moved { x, y })) => {
+                self.window_position = Some((x, y));
+                self.set_accessibility_bounds();
+                Command::none()
+            }
             Message::ApplicationEvent(Event::Window(window::Event::Resized { width, height })) => {
-                // XXX 10x20 is approximate at best
-                self.send_to_child(child::InputEvent::Resize(
-                    pty_process::Size::new_with_pixel(
-                        (height / 20) as u16,
-                        (width / 10) as u16,
-                        0,
-                        0,
-                    ),
-                ))
-                .unwrap();
+                self.window_size = Size::new(width, height);
+                self.set_accessibility_bounds();
+                for tab in &self.tabs {
+                    tab.grid_cache.clear();
+                }
+                self.resize_pty();
                 Command::none()
             }
             _ => Command::none(),
         }
     }
 
-    fn subscription(&self) -> Subscription<Message> {
-        Subscription::batch([
-            child::subscribe_to_pty(self.config.clone()).map(Message::ChildEvent),
-            subscription::events_with(|event, status| match (&event, status) {
-                (Event::Keyboard(_) | Event::Window(_), Status::Ignored) => {
-                    Some(Message::ApplicationEvent(event))
+    fn handle_session_event(
+        &mut self,
+        SessionEvent { id, event }: SessionEvent,
+    ) -> Command<Message> {
+        let Some(index) = self.tabs.iter().position(|tab| tab.session == id) else {
+            return Command::none();
+        };
+        let tab = &mut self.tabs[index];
+        match event {
+            OutputEvent::Connected(_) => {
+                if let Some(input) = tab.pending_input.take() {
+                    self.sessions
+                        .send(id, InputEvent::Stdin(input.into_bytes()));
                 }
-                _ => None,
-            }),
-        ])
+                Command::none()
+            }
+            OutputEvent::Spawned(_) => Command::none(),
+            OutputEvent::Disconnected(_) | OutputEvent::Failed(_) if self.closing => {
+                self.sessions.remove(id);
+                if self.sessions.any_running() {
+                    Command::none()
+                } else {
+                    self.close()
+                }
+            }
+            OutputEvent::Failed(err) => {
+                EXIT_CODE.store(1, Ordering::Relaxed);
+                let message = format!("\r\n[failed to start shell: {err}]");
+                tab.translator.write(message.as_bytes(), &mut tab.data);
+                Command::none()
+            }
+            OutputEvent::Disconnected(status) => match self.config.exit_behavior {
+                ExitBehavior::Close => self.close_tab(index),
+                ExitBehavior::Restart if !self.sessions.exited_early(id) => {
+                    tab.reset(&self.colors, self.config.width_policy);
+                    self.sessions.restart(id);
+                    Command::none()
+                }
+                behavior => {
+                    let status = match status.code() {
+                        Some(code) => format!("status {code}"),
+                        None => status.to_string(),
+                    };
+                    let note = if behavior == ExitBehavior::Restart {
+                        error!("Not restarting a shell that exited with {status} at once");
+                        "; too soon to restart"
+                    } else {
+                        ""
+                    };
+                    let message = format!("\r\n[process exited with {status}{note}]");
+                    tab.translator.write(message.as_bytes(), &mut tab.data);
+                    Command::none()
+                }
+            },
+            OutputEvent::Stdout(text) => {
+                tab.silent = false;
+                if self.config.monitor.silence_secs.is_some() {
+                    tab.last_output = Some(Instant::now());
+                }
+                if let Some(debug_overlay) = self.debug_overlay.as_mut() {
+                    debug_overlay.record_message(Instant::now());
+                }
+                if let Some(log) = tab.log.as_mut() {
+                    if let Err(err) = log.write(&text) {
+                        error!("Unable to log the session to {:?}: {err:#}", log.path());
+                        tab.log = None;
+                    }
+                }
+                let line_count = tab.data.line_count();
+                let first_row = tab.data.get_active_position().row;
+                let title = tab.data.title().map(str::to_owned);
+                tab.translator.write(&text, &mut tab.data);
+                let new_lines = tab.data.line_count() - line_count;
+                let rang = tab.data.take_bell();
+                // Lines the cursor has moved past are finished, for plugins to match
+                let finished: Vec<_> = if self.plugins.watches_output()
+                    || (self.accessibility.is_some() && index == self.active)
+                {
+                    (first_row..tab.data.get_active_position().row)
+                        .map(|row| tab.data.text(row, 0..tab.data.line(row).len()))
+                        .collect()
+                } else {
+                    vec![]
+                };
+                let title = Some(tab.data.title())
+                    .filter(|new| *new != title.as_deref())
+                    .map(|new| new.unwrap_or_default().to_owned());
+                let rows = self.view_rows();
+                let dropped = self.tabs[index]
+                    .data
+                    .limit_scrollback(rows, self.config.scrollback_lines);
+                if dropped > 0 {
+                    self.drop_lines(index, dropped);
+                }
+                let mut bell = if rang {
+                    self.ring_bell(index)
+                } else {
+                    Command::none()
+                };
+                self.send_responses(index);
+                for line in finished {
+                    if let Some(accessibility) = self.accessibility.as_mut() {
+                        if index == self.active {
+                            accessibility.announce(&line);
+                        }
+                    }
+                    let requests = self.plugins.on_output(line.trim_end());
+                    bell = Command::batch([bell, self.plugin_requests(index, requests)]);
+                }
+                if let Some(title) = title {
+                    let requests = self.plugins.on_title_change(&title);
+                    bell = Command::batch([bell, self.plugin_requests(index, requests)]);
+                }
+                if rang {
+                    let requests = self.plugins.on_bell();
+                    bell = Command::batch([bell, self.plugin_requests(index, requests)]);
+                }
+                if index != self.active {
+                    let tab = &mut self.tabs[index];
+                    if !tab.activity {
+                        tab.activity = true;
+                        self.notify(index, "has new output");
+                    }
+                    return bell;
+                }
+                if self.config.scroll_on_output && self.copy_mode.is_none() {
+                    self.scroll_to_bottom();
+                } else if self.tab().scrollback > 0 {
+                    // Keep the same lines in view as new ones push them up
+                    self.scroll(new_lines as isize);
+                    self.tab_mut().new_lines += new_lines;
+                }
+                // Lines above the one the output started on are as they were
+                let changed = first_row.saturating_sub(dropped);
+                let copy_mode_search = self
+                    .copy_mode
+                    .as_mut()
+                    .and_then(|copy_mode| copy_mode.search.as_mut());
+                for search in self.search.iter_mut().chain(copy_mode_search) {
+                    search.refresh(&self.tabs[index].data, changed);
+                }
+                bell
+            }
+        }
     }
 
-    fn theme(&self) -> Theme {
-        self.theme.clone()
-    }
-}
+    /** Renumber the rows remembered for a tab after its oldest lines were dropped */
+    fn drop_lines(&mut self, index: usize, dropped: usize) {
+        let tab = &mut self.tabs[index];
+        tab.selection = tab
+            .selection
+            .take()
+            .and_then(|selection| selection.shifted_up(dropped));
+        tab.marks.shift_up(dropped);
+        if index != self.active {
+            return;
+        }
+        if let Some(mark_bar) = self.mark_bar.as_mut() {
+            mark_bar.row = mark_bar.row.saturating_sub(dropped);
+        }
+        // Hints are labels on the rows in view, which have just moved
+        self.hints = None;
+        if let Some(search) = self.search.as_mut() {
+            search.shift_up(dropped);
+        }
+        if let Some(copy_mode) = self.copy_mode.as_mut() {
+            for position in std::iter::once(&mut copy_mode.cursor).chain(&mut copy_mode.anchor) {
+                position.row = position.row.saturating_sub(dropped);
+            }
+            if let Some(search) = copy_mode.search.as_mut() {
+                search.shift_up(dropped);
+            }
+        }
+    }
 
-impl Firn {
-    fn send_to_child(&mut self, message: child::InputEvent) -> Result<()> {
-        if let Some(child_sender) = self.child_sender.as_mut() {
-            child_sender.try_send(message)?;
+    /**
+     * Show that the bell rang in a tab: on its label if it is in the
+     * background, else by flashing the grid, and by asking for attention if
+     * the window is unfocused
+     */
+    fn ring_bell(&mut self, index: usize) -> Command<Message> {
+        let mut commands = vec![];
+        if index != self.active {
+            self.tabs[index].bell = true;
+        } else if self.config.bell.visual && !self.flashing {
+            self.flashing = true;
+            let duration = Duration::from_millis(self.config.bell.duration_ms);
+            commands.push(Command::perform(tokio::time::sleep(duration), |()| {
+                Message::FlashEnded
+            }));
+        }
+        let since_last_sound = self.bell_sounded.map(|instant| instant.elapsed());
+        let interval = Duration::from_millis(self.config.bell.audible_interval_ms);
+        if self.config.bell.audible && since_last_sound.is_none_or(|since| since >= interval) {
+            self.bell_sounded = Some(Instant::now());
+            if let Some(command) = bell_sound() {
+                links::run(command);
+            }
+        }
+        if self.config.bell.urgent && !self.focused {
+            commands.push(window::request_user_attention(Some(
+                window::UserAttention::Informational,
+            )));
+        }
+        Command::batch(commands)
+    }
+
+    /**
+     * Mark the tabs whose output stopped `monitor.silence_secs` ago, unless
+     * they are in view and it was seen to stop
+     */
+    fn check_silence(&mut self) {
+        let Some(secs) = self.config.monitor.silence_secs else {
+            return;
+        };
+        for index in 0..self.tabs.len() {
+            let tab = &mut self.tabs[index];
+            let Some(last_output) = tab.last_output else {
+                continue;
+            };
+            if last_output.elapsed() < Duration::from_secs(secs) {
+                continue;
+            }
+            tab.last_output = None;
+            if index == self.active && self.focused {
+                continue;
+            }
+            // The tab showing is only notified about, while the window is unfocused
+            tab.silent = index != self.active;
+            self.notify(index, &format!("has been silent for {secs}s"));
+        }
+    }
+
+    /** Tell the desktop what happened in a tab, if `monitor.notify` is on */
+    fn notify(&self, index: usize, what: &str) {
+        if !self.config.monitor.notify {
+            return;
+        }
+        let title = self.tabs[index]
+            .data
+            .title()
+            .unwrap_or(&self.config.window.title);
+        if let Some(command) = notification(&format!("{title} {what}")) {
+            links::run(command);
+        }
+    }
+
+    fn tab(&self) -> &Tab {
+        &self.tabs[self.active]
+    }
+
+    /** A tab's title as `title_format` lays it out, with `fallback` until the shell sets one */
+    fn tab_title(&self, tab: &Tab, fallback: &str) -> String {
+        title_format::format(&self.config.title_format, |name| {
+            Some(match name {
+                "title" => tab.data.title().unwrap_or(fallback).to_owned(),
+                "cwd" => self
+                    .working_directory(tab)
+                    .map_or(String::new(), |path| title_format::tilde(&path)),
+                "command" => self
+                    .sessions
+                    .pid(tab.session)
+                    .and_then(foreground::foreground_process)
+                    .unwrap_or_default(),
+                "columns" => self.pty_size().cols.to_string(),
+                "rows" => self.pty_size().rows.to_string(),
+                _ => return None,
+            })
+        })
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active]
+    }
+
+    /**
+     * Open a tab with a fresh shell, starting in this tab's working directory
+     * unless the profile says where
+     */
+    fn new_tab(&mut self, profile: Option<&str>) {
+        self.open_tab(profile, self.current_working_directory());
+    }
+
+    /** Open a tab in `working_directory`, unless the profile says where */
+    fn open_tab(&mut self, profile: Option<&str>, working_directory: Option<PathBuf>) {
+        let profile = profile.and_then(|name| Some((name, self.config.profiles.get(name)?)));
+        let mut config = match profile {
+            Some((name, _)) => self.config.with_profile(name).unwrap(),
+            None => self.config.clone(),
+        };
+        let profile_directory = profile.and_then(|(_, profile)| profile.working_directory.clone());
+        if let Some(working_directory) = profile_directory.or(working_directory) {
+            config.working_directory = Some(working_directory);
+        }
+        let colors = profile
+            .and_then(|(_, profile)| profile.color_scheme.as_ref())
+            .map(|name| self.config.resolve_colors(name));
+        let session = self.sessions.spawn(config, self.pty_size());
+        let mut tab = Tab::new(
+            session,
+            colors.as_ref().unwrap_or(&self.colors),
+            self.config.width_policy,
+        );
+        tab.profile = profile.map(|(name, _)| name.to_owned());
+        tab.colors = colors;
+        tab.font = profile.and_then(|(name, _)| self.profile_fonts.get(name).copied());
+        self.tabs.push(tab);
+        self.select_tab(self.tabs.len() - 1);
+        if self.config.session_log.enabled {
+            self.start_log(self.active);
+        }
+    }
+
+    /** Open the saved tabs after the ones there are, each with its command typed at the prompt */
+    fn restore(&mut self, saved: SavedSession) {
+        let first = self.tabs.len();
+        for tab in saved.tabs {
+            self.open_tab(tab.profile.as_deref(), tab.working_directory);
+            self.tabs.last_mut().unwrap().pending_input = tab.command;
+        }
+        self.select_tab((first + saved.active).min(self.tabs.len() - 1));
+    }
+
+    /** The tabs as they are now, to reopen next time */
+    fn save_session(&self) {
+        if !matches!(self.config.backend, BackendConfig::Pty) {
+            return;
+        }
+        let tabs = self.tabs.iter().map(|tab| {
+            let pid = self.sessions.pid(tab.session);
+            SavedTab {
+                profile: tab.profile.clone(),
+                working_directory: self.working_directory(tab),
+                command: pid.and_then(foreground::foreground_command).map(|args| {
+                    let args: Vec<String> =
+                        args.iter().map(|arg| paste::shell_quote(arg)).collect();
+                    args.join(" ")
+                }),
+            }
+        });
+        let saved = SavedSession {
+            tabs: tabs.collect(),
+            active: self.active,
+        };
+        if let Err(err) = saved.save() {
+            error!("Unable to save the session: {err:#}");
+        }
+    }
+
+    /** Log a tab's output from now on, to a fresh file in `session_log.directory` */
+    fn start_log(&mut self, index: usize) {
+        let config = &self.config.session_log;
+        let Some(dir) = config
+            .directory
+            .clone()
+            .or_else(|| Some(window_state::state_dir()?.join("firn").join("logs")))
+        else {
+            error!("Nowhere to log the session: HOME is not set");
+            return;
+        };
+        let path = session_log::path(&dir, index);
+        match SessionLog::open(&path, config.timestamps, config.strip_escapes) {
+            Ok(log) => {
+                info!("Logging the session to {path:?}");
+                self.tabs[index].log = Some(log);
+            }
+            Err(err) => error!("Unable to log the session: {err:#}"),
+        }
+    }
+
+    fn select_tab(&mut self, index: usize) {
+        self.active = index;
+        self.hints = None;
+        self.copy_mode = None;
+        self.mark_bar = None;
+        let tab = &mut self.tabs[index];
+        tab.activity = false;
+        tab.bell = false;
+        tab.silent = false;
+        tab.last_output = None;
+        if let Some(search) = self.search.as_mut() {
+            search.update(&tab.data);
+        }
+    }
+
+    /** Switch to the tab `offset` places to the right, wrapping around */
+    fn cycle_tab(&mut self, offset: isize) {
+        let index = (self.active as isize + offset).rem_euclid(self.tabs.len() as isize);
+        self.select_tab(index as usize);
+    }
+
+    /**
+     * Programs running in the foreground of these tabs' shells that closing
+     * would kill, leaving out those the config says are fine to close
+     */
+    fn busy_processes(&self, tabs: &[Tab]) -> Vec<String> {
+        if !self.config.confirm_close {
+            return vec![];
+        }
+        tabs.iter()
+            .filter_map(|tab| foreground::foreground_process(self.sessions.pid(tab.session)?))
+            .filter(|name| !self.config.confirm_close_ignore.contains(name))
+            .collect()
+    }
+
+    /** Give the shells a chance to exit cleanly; the window closes once they have */
+    fn close_window(&mut self) -> Command<Message> {
+        // Before the shells exit and take what they were running with them
+        self.save_session();
+        self.closing = true;
+        if self.sessions.hangup() == 0 {
+            self.close()
+        } else {
+            Command::none()
+        }
+    }
+
+    /** Open a link, or a file location in the editor */
+    fn open_link(&mut self, target: &str) {
+        let Some(mut location) = FileLocation::parse(target) else {
+            links::open(target);
+            return;
+        };
+        if location.path.is_relative() {
+            // Compilers print paths relative to where they were run
+            if let Some(dir) = self.current_working_directory() {
+                location.path = dir.join(&location.path);
+            }
+        }
+        if !self.config.editor.is_empty() {
+            links::run(location.command(&self.config.editor));
+            return;
+        }
+        // A terminal editor needs a terminal, so it gets a tab of its own
+        let mut editor = links::terminal_editor();
+        let mut config = self.config.clone();
+        config.shell = editor.remove(0);
+        config.shell_args = editor;
+        config.shell_args.extend([
+            format!("+{}", location.line),
+            location.path.to_string_lossy().into_owned(),
+        ]);
+        let session = self.sessions.spawn(config, self.pty_size());
+        self.tabs
+            .push(Tab::new(session, &self.colors, self.config.width_policy));
+        self.select_tab(self.tabs.len() - 1);
+    }
+
+    /** Drop a tab whose shell has exited, closing the window along with the last one */
+    fn close_tab(&mut self, index: usize) -> Command<Message> {
+        let tab = self.tabs.remove(index);
+        self.sessions.remove(tab.session);
+        if self.tabs.is_empty() {
+            // Every shell was exited on purpose, which leaves nothing to come back to
+            SavedSession::remove();
+            return self.close();
+        }
+        if self.active > index || self.active == self.tabs.len() {
+            self.active -= 1;
+        }
+        self.select_tab(self.active);
+        Command::none()
+    }
+
+    fn run_action(&mut self, action: Action) -> Command<Message> {
+        match action {
+            Action::Copy => return self.copy_selection(),
+            Action::Paste => return clipboard::read(Message::ClipboardRead),
+            Action::PasteOneLine => {
+                return clipboard::read(|text| {
+                    Message::ClipboardRead(text.as_deref().map(paste::join_lines))
+                })
+            }
+            Action::PasteReplacing {
+                pattern,
+                replacement,
+            } => match Regex::new(&pattern) {
+                Ok(regex) => {
+                    return clipboard::read(move |text| {
+                        Message::ClipboardRead(
+                            text.map(|text| regex.replace_all(&text, &replacement).into_owned()),
+                        )
+                    })
+                }
+                Err(err) => error!("Unable to paste with {pattern:?} replaced: {err:#}"),
+            },
+            Action::Search => {
+                let search = self.search.get_or_insert_with(Search::new);
+                return text_input::focus(search.input_id.clone());
+            }
+            Action::NewTab => self.new_tab(None),
+            Action::CloseTab => {
+                let tab = self.tab();
+                let processes = self.busy_processes(std::slice::from_ref(tab));
+                if processes.is_empty() {
+                    return self.close_tab(self.active);
+                }
+                self.pending_close = Some(PendingClose {
+                    tab: Some(tab.session),
+                    processes,
+                });
+            }
+            Action::NewWindow => {
+                if let Err(err) = self.open_new_window() {
+                    error!("Unable to open a new window: {err}");
+                }
+            }
+            Action::PreviousTab => self.cycle_tab(-1),
+            Action::NextTab => self.cycle_tab(1),
+            Action::Hints => {
+                self.hints = match self.hints {
+                    Some(_) => None,
+                    None => {
+                        let tab = self.tab();
+                        let rows = tab.visible_rows(self.view_rows());
+                        Hints::new(&self.hint_detector, &tab.data, rows)
+                    }
+                };
+            }
+            Action::CopyMode => {
+                if self.copy_mode.is_some() {
+                    self.exit_copy_mode();
+                } else {
+                    let rows = self.tab().visible_rows(self.view_rows());
+                    let mut cursor = self.tab().data.get_active_position();
+                    if !rows.contains(&cursor.row) {
+                        cursor = Position {
+                            row: rows.end.saturating_sub(1),
+                            col: 0,
+                        };
+                    }
+                    self.copy_mode = Some(CopyMode::new(cursor));
+                }
+            }
+            Action::Export => match self.config.export_format {
+                ExportFormat::Png => return window::screenshot(Message::Screenshot),
+                ExportFormat::Svg => {
+                    let svg = export::svg(&self.grid_view());
+                    self.export(ExportFormat::Svg, svg.as_bytes());
+                }
+            },
+            Action::ZoomIn => self.zoom(self.font_size + ZOOM_STEP),
+            Action::ZoomOut => self.zoom(self.font_size - ZOOM_STEP),
+            Action::ZoomReset => self.zoom(self.config.font.size),
+            Action::ScrollPageUp | Action::ScrollPageDown => {
+                let page = (self.view_rows() as isize - 1).max(1);
+                self.scroll(if action == Action::ScrollPageUp {
+                    page
+                } else {
+                    -page
+                });
+            }
+            Action::ToggleFullscreen => {
+                self.fullscreen = !self.fullscreen;
+                return window::change_mode(if self.fullscreen {
+                    window::Mode::Fullscreen
+                } else {
+                    window::Mode::Windowed
+                });
+            }
+            Action::ToggleMaximized => return window::toggle_maximize(),
+            Action::ToggleDecorations => return window::toggle_decorations(),
+            Action::PreviousPrompt => self.jump_to_prompt(true),
+            Action::NextPrompt => self.jump_to_prompt(false),
+            Action::AddMark => {
+                let row = self.mark_row();
+                let mark_bar = self.mark_bar.insert(MarkBar::new(row));
+                return text_input::focus(mark_bar.input_id.clone());
+            }
+            Action::PreviousMark => self.jump_to_mark(true),
+            Action::NextMark => self.jump_to_mark(false),
+            Action::SelectLastOutput => {
+                self.select_last_output();
+            }
+            Action::CopyLastOutput => {
+                if self.select_last_output() {
+                    return self.copy_selection();
+                }
+            }
+            Action::ToggleSessionLog => {
+                if let Some(log) = self.tabs[self.active].log.take() {
+                    info!("Stopped logging the session to {:?}", log.path());
+                } else {
+                    self.start_log(self.active);
+                }
+            }
+            Action::ToggleDebugOverlay => {
+                self.debug_overlay = match self.debug_overlay {
+                    Some(_) => None,
+                    None => Some(DebugOverlay::new()),
+                };
+            }
+            Action::SendText(text) => self.send_input(InputEvent::Stdin(text.into_bytes())),
+            Action::SendBytes(bytes) => self.send_input(InputEvent::Stdin(bytes.0)),
+            Action::Plugin(name) => match self.plugins.run_action(&name) {
+                Ok(requests) => return self.plugin_requests(self.active, requests),
+                Err(err) => error!("Plugin action failed: {err:#}"),
+            },
+            Action::None => {}
+        }
+        Command::none()
+    }
+
+    fn copy_selection(&self) -> Command<Message> {
+        let tab = self.tab();
+        match &tab.selection {
+            Some(selection) => clipboard::write(self.selected_text(tab, selection)),
+            None => Command::none(),
+        }
+    }
+
+    /**
+     * The text of a selection, to copy. Rows are never wrapped, so a line
+     * longer than the window is copied whole without joining anything.
+     */
+    fn selected_text(&self, tab: &Tab, selection: &Selection) -> String {
+        let trim = self.config.selection.trim_trailing_whitespace;
+        tab.data.selected_text(selection, trim)
+    }
+
+    /**
+     * First and last cell of the `unit` at `position`. Rows are never wrapped,
+     * so a row is a whole logical line already.
+     */
+    fn unit_at(&self, position: Position, unit: SelectionUnit) -> (Position, Position) {
+        let data = &self.tab().data;
+        if position.row >= data.line_count() {
+            return (position.clone(), position);
+        }
+        match unit {
+            SelectionUnit::Cell => (position.clone(), position),
+            SelectionUnit::Word => data.word_at(&position, &self.config.selection.word_characters),
+            SelectionUnit::Line => {
+                let row = position.row;
+                let end = data.line(row).len().saturating_sub(1);
+                (Position { row, col: 0 }, Position { row, col: end })
+            }
+        }
+    }
+
+    /** The lines copy mode can move through: those in view and the history above them */
+    fn reachable_rows(&self) -> Range<usize> {
+        let line_count = self.tab().data.line_count();
+        line_count.saturating_sub(self.view_rows() + self.max_scrollback())..line_count
+    }
+
+    fn move_copy_cursor(&mut self, motion: Motion) {
+        let rows = self.reachable_rows();
+        if let Some(copy_mode) = self.copy_mode.as_mut() {
+            let word_characters = &self.config.selection.word_characters;
+            copy_mode.move_cursor(motion, &self.tabs[self.active].data, rows, word_characters);
+        }
+        self.follow_copy_cursor();
+    }
+
+    /** Keep the selection and the view up with the copy mode cursor */
+    fn follow_copy_cursor(&mut self) {
+        let Some(copy_mode) = self.copy_mode.as_ref() else {
+            return;
+        };
+        let row = copy_mode.cursor.row;
+        self.tabs[self.active].selection = copy_mode.selection();
+        self.reveal(row);
+    }
+
+    fn copy_mode_key(&mut self, ch: char) -> Command<Message> {
+        let Some(copy_mode) = self.copy_mode.as_mut() else {
+            return Command::none();
+        };
+        match ch {
+            'v' => copy_mode.toggle_selection(),
+            'y' => {
+                let command = self.copy_selection();
+                self.exit_copy_mode();
+                return command;
+            }
+            'q' => self.exit_copy_mode(),
+            '/' => {
+                let search = self.search.get_or_insert_with(Search::new);
+                return text_input::focus(search.input_id.clone());
+            }
+            'n' | 'N' => {
+                let Some(search) = copy_mode.search.as_mut() else {
+                    return Command::none();
+                };
+                let found = if ch == 'n' {
+                    search.next()
+                } else {
+                    search.previous(&self.tabs[self.active].data)
+                };
+                if let Some(found) = found {
+                    copy_mode.cursor = Position {
+                        row: found.row,
+                        col: found.columns.start,
+                    };
+                }
+            }
+            _ => {
+                if let Some(motion) = Motion::from_char(ch) {
+                    self.move_copy_cursor(motion);
+                }
+                return Command::none();
+            }
+        }
+        self.follow_copy_cursor();
+        Command::none()
+    }
+
+    fn exit_copy_mode(&mut self) {
+        if self.copy_mode.take().is_some() {
+            self.tab_mut().selection = None;
         }
+    }
+
+    /** Type a character of a hint's label, acting on the hint once it is complete */
+    fn pick_hint(&mut self, ch: char) -> Command<Message> {
+        let Some(action) = self.hints.as_mut().and_then(|hints| hints.push(ch)) else {
+            return Command::none();
+        };
+        self.hints = None;
+        match action {
+            HintAction::Copy(text) => clipboard::write(text),
+            HintAction::Open(target) => {
+                links::open(&target);
+                Command::none()
+            }
+        }
+    }
+
+    fn close(&self) -> Command<Message> {
+        // A fullscreen window's size says nothing about the window to come back to
+        if self.config.window.remember_geometry && !self.fullscreen {
+            let state = WindowState {
+                width: self.window_size.width,
+                height: self.window_size.height,
+                position: self.window_position,
+            };
+            if let Err(err) = state.save() {
+                error!("Unable to save the window geometry: {err:#}");
+            }
+        }
+        for tab in &self.tabs {
+            if !tab.data.stats().is_empty() {
+                info!("Ignored sequences:\n{}", tab.data.stats());
+            }
+        }
+        if self.config.remote_control {
+            let _ = std::fs::remove_file(ipc::socket_path());
+        }
+        window::close()
+    }
+
+    fn grid_view(&self) -> GridView<Message> {
+        let tab = self.tab();
+        // Copy mode keeps the search it submitted around for `n` and `N`
+        let search = self.search.as_ref().or(self
+            .copy_mode
+            .as_ref()
+            .and_then(|copy_mode| copy_mode.search.as_ref()));
+        GridView {
+            data: &tab.data,
+            metrics: self.metrics(),
+            font: tab.font.unwrap_or(self.font),
+            font_size: self.font_size,
+            colors: tab.colors.as_ref().unwrap_or(&self.colors),
+            bold_as_bright: self.config.bold_as_bright,
+            bold_weight: self.config.font.bold_weight,
+            rows: self.view_rows(),
+            scrollback: tab.scrollback,
+            new_lines: tab.new_lines,
+            marks: tab.marks.rows(),
+            max_scrollback: self.max_scrollback(),
+            scrollbar: self.config.scrollbar,
+            cache: &tab.grid_cache,
+            cursor_shape: self.config.cursor_shape,
+            cursor_visible: self.cursor_visible || !self.focused,
+            focused: self.focused,
+            selection: tab.selection.as_ref(),
+            search_matches: search.map_or(&[], |search| &search.matches),
+            current_match: search.and_then(|search| search.current),
+            links: self
+                .links
+                .find(&tab.data, tab.visible_rows(self.view_rows())),
+            hints: self.hints.as_ref(),
+            copy_cursor: self.copy_mode.as_ref().map(|copy_mode| &copy_mode.cursor),
+            flash: self.flashing,
+            bidi: self.config.bidi && !tab.data.modes().explicit_bidi,
+            debug_lines: self
+                .debug_overlay
+                .as_ref()
+                .map_or(vec![], |debug_overlay| debug_overlay.lines(tab)),
+            on_selection: Message::Selection,
+            on_paste: Message::PastePrimary,
+            on_scroll: Message::Scroll,
+            on_wheel: Message::Wheel,
+            on_flick: Message::Flick,
+            scroll_multiplier: self.config.scroll_multiplier,
+            on_zoom: Message::Zoom,
+            on_open_link: Message::OpenLink,
+            on_context_menu: Message::OpenContextMenu,
+        }
+    }
+
+    /**
+     * The part of a screenshot of the whole window, `size` physical pixels
+     * large, that shows the rows being exported
+     */
+    fn export_region(&self, size: Size<u32>) -> iced::Rectangle<u32> {
+        let grid = self.grid_view();
+        let visible = grid.visible_rows();
+        let rows = export::selected_rows(&grid);
+        let metrics = self.metrics();
+        let bars = self.bars().above();
+        let padding = f32::from(self.config.window.padding);
+        let scale = size.width as f32 / self.window_size.width as f32;
+        let top = padding + (bars + rows.start - visible.start) as f32 * metrics.height;
+        let width = self.window_size.width as f32 - 2.0 * padding;
+        let height = rows.len() as f32 * metrics.height;
+        iced::Rectangle {
+            x: (padding * scale) as u32,
+            y: (top * scale) as u32,
+            width: ((width * scale) as u32).min(size.width),
+            height: ((height * scale) as u32).min(size.height),
+        }
+    }
+
+    /** Write an exported image next to the shell, or wherever configured */
+    fn export(&self, format: ExportFormat, bytes: &[u8]) {
+        let dir = self
+            .config
+            .export_directory
+            .clone()
+            .or_else(|| self.current_working_directory())
+            .unwrap_or_default();
+        let path = export::path(&dir, format);
+        match std::fs::write(&path, bytes) {
+            Ok(()) => info!("Exported the screen to {path:?}"),
+            Err(err) => error!("Unable to export the screen to {path:?}: {err}"),
+        }
+    }
+
+    fn tab_bar(&self) -> Element<Message> {
+        let fallback = Path::new(&self.config.shell)
+            .file_name()
+            .map_or(self.config.shell.clone(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        let tabs = self.tabs.iter().enumerate().map(|(index, tab)| {
+            let style = if index == self.active {
+                theme::Button::Primary
+            } else {
+                theme::Button::Secondary
+            };
+            button(text(tab.label(&self.tab_title(tab, &fallback))))
+                .style(style)
+                .on_press(Message::SelectTab(index))
+                .into()
+        });
+        let height = TAB_BAR_ROWS as f32 * self.metrics().height;
+        container(Row::with_children(tabs.collect()).spacing(4))
+            .height(height)
+            .padding([0, 8])
+            .center_y()
+            .into()
+    }
+
+    fn menu(&self, menu: &OpenMenu) -> Element<Message> {
+        let item = |label: String, item: Option<MenuItem>| {
+            button(text(label))
+                .style(theme::Button::Text)
+                .width(Length::Fill)
+                .padding([4, 12])
+                .on_press_maybe(item.map(Message::MenuItem))
+                .into()
+        };
+        let copy = self.tab().selection.as_ref().map(|_| MenuItem::Copy);
+        let last_output = prompts::last_output(&self.tab().data, self.prompt_pattern.as_ref())
+            .map(|_| MenuItem::CopyLastOutput);
+        let mut items = vec![
+            item("Copy".into(), copy),
+            item("Paste".into(), Some(MenuItem::Paste)),
+            item("Select All".into(), Some(MenuItem::SelectAll)),
+            item("Copy Last Output".into(), last_output),
+            item("Clear Scrollback".into(), Some(MenuItem::ClearScrollback)),
+            item("New Tab".into(), Some(MenuItem::NewTab)),
+        ];
+        for profile in self.config.profiles.keys() {
+            let label = format!("New Tab: {profile}");
+            items.push(item(label, Some(MenuItem::NewProfileTab(profile.clone()))));
+        }
+        if let Some(link) = &menu.link {
+            items.push(item(
+                "Open Link".into(),
+                Some(MenuItem::OpenLink(link.clone())),
+            ));
+        }
+        container(Column::with_children(items))
+            .style(theme::Container::Box)
+            .width(180)
+            .padding(4)
+            .into()
+    }
+
+    fn menu_item(&mut self, item: MenuItem) -> Command<Message> {
+        match item {
+            MenuItem::Copy => return self.copy_selection(),
+            MenuItem::Paste => return self.update(Message::Paste),
+            MenuItem::CopyLastOutput => return self.run_action(Action::CopyLastOutput),
+            MenuItem::SelectAll => {
+                let data = &self.tab().data;
+                let row = data.line_count() - 1;
+                let end = Position {
+                    row,
+                    col: data.line(row).len().saturating_sub(1),
+                };
+                let start = Position { row: 0, col: 0 };
+                self.tab_mut().selection =
+                    Some(Selection::spanning(start, end, SelectionUnit::Line));
+            }
+            MenuItem::ClearScrollback => {
+                self.copy_mode = None;
+                self.hints = None;
+                let rows = self.view_rows();
+                let tab = &mut self.tabs[self.active];
+                let lines = tab.data.line_count();
+                tab.data.clear_scrollback(rows);
+                tab.marks.shift_up(lines - tab.data.line_count());
+                tab.scrollback = 0;
+                tab.selection = None;
+                if let Some(search) = self.search.as_mut() {
+                    search.update(&tab.data);
+                }
+            }
+            MenuItem::NewTab => self.new_tab(None),
+            MenuItem::NewProfileTab(profile) => self.new_tab(Some(&profile)),
+            MenuItem::OpenLink(target) => self.open_link(&target),
+        }
+        Command::none()
+    }
+
+    fn config_error_bar(&self, error: &str) -> Element<Message> {
+        let height = CONFIG_ERROR_ROWS as f32 * self.metrics().height;
+        container(
+            row![
+                text(format!("{error}; using the default settings")).width(Length::Fill),
+                button(text("Dismiss"))
+                    .style(theme::Button::Secondary)
+                    .on_press(Message::DismissConfigError),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+        )
+        .style(theme::Container::Box)
+        .width(Length::Fill)
+        .height(height)
+        .padding([0, 8])
+        .center_y()
+        .into()
+    }
+
+    fn restore_bar(&self, saved: &SavedSession) -> Element<Message> {
+        let tabs = match saved.tabs.len() {
+            1 => "the tab".to_string(),
+            n => format!("the {n} tabs"),
+        };
+        let height = RESTORE_BAR_ROWS as f32 * self.metrics().height;
+        container(
+            row![
+                text(format!("Reopen {tabs} from when firn last closed?")).width(Length::Fill),
+                button(text("Reopen"))
+                    .style(theme::Button::Primary)
+                    .on_press(Message::RestoreSession),
+                button(text("Dismiss"))
+                    .style(theme::Button::Secondary)
+                    .on_press(Message::DismissRestore),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+        )
+        .width(Length::Fill)
+        .height(height)
+        .padding([0, 8])
+        .center_y()
+        .into()
+    }
+
+    fn confirm_bar(&self, pending: &PendingClose) -> Element<Message> {
+        let what = if pending.tab.is_some() {
+            "this tab"
+        } else {
+            "the window"
+        };
+        let question = format!(
+            "Close {what}? Still running: {}",
+            pending.processes.join(", ")
+        );
+        let height = CONFIRM_BAR_ROWS as f32 * self.metrics().height;
+        container(
+            row![
+                text(question),
+                button(text("Close"))
+                    .style(theme::Button::Destructive)
+                    .on_press(Message::ConfirmClose),
+                button(text("Cancel"))
+                    .style(theme::Button::Secondary)
+                    .on_press(Message::CancelClose),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+        )
+        .height(height)
+        .padding([0, 8])
+        .center_y()
+        .into()
+    }
+
+    fn paste_bar(&self, pending: &PendingPaste) -> Element<Message> {
+        let question = if paste::has_controls(&pending.text) {
+            "Paste text with control characters in it?".to_string()
+        } else {
+            let lines = pending.text.lines().count();
+            format!("Paste {lines} lines? Each line break runs the line before it.")
+        };
+        let buttons = row![
+            text(question).width(Length::Fill),
+            button(text("Paste"))
+                .style(theme::Button::Primary)
+                .on_press(Message::ConfirmPaste),
+            button(text("Edit"))
+                .style(theme::Button::Secondary)
+                .on_press(Message::EditPaste),
+            button(text("Cancel"))
+                .style(theme::Button::Secondary)
+                .on_press(Message::CancelPaste),
+        ]
+        .spacing(8)
+        .align_items(Alignment::Center);
+        let contents: Element<Message> = match &pending.editing {
+            Some(edited) => text_input("", edited)
+                .id(pending.input_id.clone())
+                .on_input(Message::PasteInput)
+                .on_submit(Message::ConfirmPaste)
+                .font(self.font)
+                .into(),
+            None => text(paste::preview(&pending.text, PASTE_PREVIEW_LINES))
+                .font(self.font)
+                .into(),
+        };
+        let height = PASTE_BAR_ROWS as f32 * self.metrics().height;
+        container(column![buttons, contents].spacing(8))
+            .width(Length::Fill)
+            .height(height)
+            .padding([4, 8])
+            .into()
+    }
+
+    fn status_bar(&self) -> Element<Message> {
+        let tab = self.tab();
+        let mode = if self.copy_mode.is_some() {
+            "COPY"
+        } else if self.hints.is_some() {
+            "HINTS"
+        } else if self.search.is_some() {
+            "SEARCH"
+        } else {
+            ""
+        };
+        let directory = tab
+            .data
+            .working_directory()
+            .map_or(String::new(), |path| path.display().to_string());
+        let size = self.metrics().height / self.config.font.cell_height;
+        container(
+            row![
+                text(mode).size(size),
+                text(self.title()).size(size),
+                horizontal_space(Length::Fill),
+                text(directory).size(size),
+                text(tab.scroll_position(self.max_scrollback())).size(size),
+            ]
+            .spacing(16)
+            .align_items(Alignment::Center),
+        )
+        .style(theme::Container::Box)
+        .width(Length::Fill)
+        .height(STATUS_BAR_ROWS as f32 * self.metrics().height)
+        .padding([0, 8])
+        .center_y()
+        .into()
+    }
+
+    fn search_bar(&self, search: &Search) -> Element<Message> {
+        let status = match (&search.error, search.counter()) {
+            (Some(err), _) => err.clone(),
+            (None, Some(counter)) => counter,
+            (None, None) if search.query.is_empty() || !search.is_complete() => String::new(),
+            (None, None) => "No matches".into(),
+        };
+        let height = SEARCH_BAR_ROWS as f32 * self.metrics().height;
+        container(
+            row![
+                text_input("Search", &search.query)
+                    .id(search.input_id.clone())
+                    .on_input(Message::SearchInput)
+                    .on_submit(Message::SearchSubmit),
+                checkbox("Regex", search.regex, Message::SearchRegex),
+                text(status),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+        )
+        .height(height)
+        .padding([0, 8])
+        .center_y()
+        .into()
+    }
+
+    fn mark_bar(&self, mark_bar: &MarkBar) -> Element<Message> {
+        let input = text_input("Name a mark", &mark_bar.name)
+            .id(mark_bar.input_id.clone())
+            .on_input(Message::MarkInput)
+            .on_submit(Message::MarkSubmit)
+            .width(200);
+        let marks = self.tab().marks.iter().map(|mark| {
+            button(text(&mark.name))
+                .style(theme::Button::Secondary)
+                .on_press(Message::JumpToMark(mark.row))
+                .into()
+        });
+        let height = MARK_BAR_ROWS as f32 * self.metrics().height;
+        container(
+            Row::with_children(std::iter::once(input.into()).chain(marks).collect())
+                .spacing(8)
+                .align_items(Alignment::Center),
+        )
+        .height(height)
+        .padding([0, 8])
+        .center_y()
+        .into()
+    }
+
+    /** The bars that show, as `view` lays them out */
+    fn bars(&self) -> Bars {
+        Bars {
+            config_error: self.config_error.is_some(),
+            confirm_close: self.pending_close.is_some(),
+            paste: self.pending_paste.is_some(),
+            restore: self.restore_offer.is_some(),
+            tabs: self.tabs.len() > 1,
+            search: self.search.is_some(),
+            mark: self.mark_bar.is_some(),
+            status: self.config.status_bar,
+        }
+    }
+
+    /** How many rows of the grid are in view, which the bars cut into */
+    fn view_rows(&self) -> usize {
+        self.bars().grid_rows(usize::from(self.grid_size().rows))
+    }
+
+    /** The most lines the view can scroll back */
+    fn max_scrollback(&self) -> usize {
+        let lines = self.tab().data.line_count();
+        lines.saturating_sub(self.view_rows())
+    }
+
+    /** Scroll so that the current search match is in view, centering it if it was not */
+    fn reveal_current_match(&mut self) {
+        if let Some(row) = self
+            .search
+            .as_ref()
+            .and_then(|search| Some(search.matches[search.current?].row))
+        {
+            self.reveal(row);
+        }
+    }
+
+    /** Scroll the prompt above or below the top of the view up to it */
+    fn jump_to_prompt(&mut self, previous: bool) {
+        let rows = self.view_rows();
+        let tab = self.tab();
+        let prompts = prompts::prompt_rows(&tab.data, self.prompt_pattern.as_ref());
+        let (above, below) = prompts::around(&prompts, tab.visible_rows(rows).start);
+        if let Some(row) = if previous { above } else { below } {
+            self.scroll_to_top(row);
+        }
+    }
+
+    /**
+     * Press arrow or page keys for the mouse wheel in a full-screen program
+     * that takes no mouse events, like less, whose text firn could not scroll
+     * back through. Returns whether it did.
+     */
+    fn wheel_to_keys(&mut self, lines: isize) -> bool {
+        let tab = self.tab();
+        let modes = tab.data.modes();
+        if !modes.alternate_screen || modes.mouse_reporting || tab.scrollback > 0 {
+            return false;
+        }
+        let alternate_scroll = &self.config.alternate_scroll;
+        let (wheel_key, arrow, page) = if lines > 0 {
+            (
+                alternate_scroll.up,
+                keyboard::KeyCode::Up,
+                keyboard::KeyCode::PageUp,
+            )
+        } else {
+            (
+                alternate_scroll.down,
+                keyboard::KeyCode::Down,
+                keyboard::KeyCode::PageDown,
+            )
+        };
+        let (key_code, presses) = match wheel_key {
+            WheelKey::Arrows => (arrow, lines.unsigned_abs() * alternate_scroll.lines),
+            WheelKey::Pages => (page, 1),
+            WheelKey::None => return false,
+        };
+        if let Some(text) = keys::encode(key_code, keyboard::Modifiers::empty(), modes) {
+            let bytes = text.repeat(presses).into_bytes();
+            self.send_to_child(InputEvent::Stdin(bytes));
+        }
+        true
+    }
+
+    /** Like `jump_to_prompt`, for the tab's marks */
+    fn jump_to_mark(&mut self, previous: bool) {
+        let tab = self.tab();
+        let marks = tab.marks.rows();
+        let (above, below) = prompts::around(&marks, tab.visible_rows(self.view_rows()).start);
+        if let Some(row) = if previous { above } else { below } {
+            self.scroll_to_top(row);
+        }
+    }
+
+    /** Scroll so that `row` is the top one in view, or as close to it as the history allows */
+    fn scroll_to_top(&mut self, row: usize) {
+        let tab = self.tab();
+        let scrollback = tab.data.line_count().saturating_sub(row + self.view_rows());
+        self.scroll(scrollback as isize - tab.scrollback as isize);
+    }
+
+    /**
+     * The line a new mark goes on: the copy mode cursor's, the top one in
+     * view while scrolled back, or else the cursor's
+     */
+    fn mark_row(&self) -> usize {
+        let tab = self.tab();
+        if let Some(copy_mode) = &self.copy_mode {
+            copy_mode.cursor.row
+        } else if tab.scrollback > 0 {
+            tab.visible_rows(self.view_rows()).start
+        } else {
+            tab.data.get_active_position().row
+        }
+    }
+
+    /** Returns whether there was any output to select */
+    fn select_last_output(&mut self) -> bool {
+        let tab = self.tab();
+        let Some(output) = prompts::last_output(&tab.data, self.prompt_pattern.as_ref()) else {
+            return false;
+        };
+        let (first, last) = (*output.start(), *output.end());
+        let end = tab.data.line(last).len().saturating_sub(1);
+        self.tab_mut().selection = Some(Selection::spanning(
+            Position { row: first, col: 0 },
+            Position {
+                row: last,
+                col: end,
+            },
+            SelectionUnit::Line,
+        ));
+        self.reveal(first);
+        true
+    }
+
+    /** Scroll so that `row` is in view, centering it if it was not */
+    fn reveal(&mut self, row: usize) {
+        let rows = self.view_rows();
+        let tab = self.tab();
+        if !tab.visible_rows(rows).contains(&row) {
+            let scrollback = tab.data.line_count().saturating_sub(row + rows / 2 + 1);
+            self.scroll(scrollback as isize - tab.scrollback as isize);
+        }
+    }
+
+    fn scroll(&mut self, lines: isize) {
+        let max_scrollback = self.max_scrollback();
+        let tab = self.tab_mut();
+        tab.scrollback = tab
+            .scrollback
+            .saturating_add_signed(lines)
+            .min(max_scrollback);
+        if tab.scrollback == 0 {
+            tab.new_lines = 0;
+        }
+        tab.grid_cache.clear();
+    }
+
+    fn scroll_to_bottom(&mut self) {
+        let tab = self.tab_mut();
+        tab.new_lines = 0;
+        if tab.scrollback > 0 {
+            tab.scrollback = 0;
+            tab.grid_cache.clear();
+        }
+    }
+
+    /** Paste text into the active tab, sanitized and bracketed as configured */
+    fn paste(&mut self, text: &str) {
+        let bracketed = self.tab().data.modes().bracketed_paste;
+        let text = paste::prepare(text, self.config.sanitize_paste, bracketed);
+        self.send_input(InputEvent::Paste(text));
+    }
+
+    /** Send something the user typed or pasted, bringing the live screen back into view if configured */
+    fn send_input(&mut self, message: InputEvent) {
+        if self.config.scroll_on_keypress {
+            self.scroll_to_bottom();
+        }
+        self.send_to_child(message)
+    }
+
+    fn send_to_child(&mut self, message: InputEvent) {
+        self.sessions.send(self.tab().session, message)
+    }
+
+    /**
+     * The shell's current working directory, preferring what it reported via
+     * OSC 7 and falling back to asking the OS about the child process
+     */
+    fn current_working_directory(&self) -> Option<PathBuf> {
+        self.working_directory(self.tab())
+    }
+
+    /** Where a tab's shell is, as it said with OSC 7 or else as `/proc` says */
+    fn working_directory(&self, tab: &Tab) -> Option<PathBuf> {
+        if let Some(working_directory) = tab.data.working_directory() {
+            return Some(working_directory.into());
+        }
+        let pid = self.sessions.pid(tab.session)?;
+        std::fs::read_link(format!("/proc/{pid}/cwd")).ok()
+    }
+
+    /** Launch another firn window, starting in this session's working directory */
+    fn open_new_window(&self) -> Result<()> {
+        let mut command = std::process::Command::new(std::env::current_exe()?);
+        if let Some(working_directory) = self.current_working_directory() {
+            command.arg("--working-directory").arg(working_directory);
+        }
+        command.spawn()?;
         Ok(())
     }
+
+    /**
+     * Carry out what a plugin hook asked for, typing into the tab whose
+     * output or action ran it
+     */
+    fn plugin_requests(&mut self, index: usize, requests: Vec<Request>) -> Command<Message> {
+        let mut commands = vec![];
+        for request in requests {
+            let response = match request {
+                Request::SendText { text } => {
+                    let input = InputEvent::Stdin(text.into_bytes());
+                    self.sessions.send(self.tabs[index].session, input);
+                    continue;
+                }
+                request => {
+                    let (response, command) = self.remote_control(request);
+                    commands.push(command);
+                    response
+                }
+            };
+            if let Some(err) = response.error {
+                error!("Plugin hook failed: {err}");
+            }
+        }
+        Command::batch(commands)
+    }
+
+    /** Show screen readers the rows in view of the active tab, and read out new output */
+    fn update_accessibility(&mut self) {
+        if self.accessibility.is_none() {
+            return;
+        }
+        let tab = self.tab();
+        let rows = tab.visible_rows(self.view_rows());
+        let cursor = tab.data.get_active_position();
+        let screen = Screen {
+            title: self.title(),
+            rows: rows
+                .clone()
+                .map(|row| {
+                    let text = tab.data.text(row, 0..tab.data.line(row).len());
+                    text.trim_end().to_owned()
+                })
+                .collect(),
+            cursor: rows
+                .contains(&cursor.row)
+                .then(|| (cursor.row - rows.start, cursor.col)),
+        };
+        if let Some(accessibility) = self.accessibility.as_mut() {
+            accessibility.update(screen, Instant::now());
+        }
+    }
+
+    fn set_accessibility_bounds(&mut self) {
+        if let Some(accessibility) = self.accessibility.as_mut() {
+            let size = (self.window_size.width, self.window_size.height);
+            accessibility.set_bounds(self.window_position.unwrap_or_default(), size);
+        }
+    }
+
+    /** Carry out a command from `firn msg` */
+    fn remote_control(&mut self, request: Request) -> (Response, Command<Message>) {
+        match request {
+            Request::SendText { text } => {
+                self.send_input(InputEvent::Stdin(text.into_bytes()));
+                (Response::default(), Command::none())
+            }
+            Request::GetText { scrollback } => {
+                let rows = if scrollback {
+                    usize::MAX
+                } else {
+                    self.view_rows()
+                };
+                (
+                    Response::text(self.tab().data.render(rows)),
+                    Command::none(),
+                )
+            }
+            Request::Resize { cols, rows } => {
+                // The shell gets the rows asked for, with the bars showing on top of them
+                let bar_rows = self.grid_size().rows.saturating_sub(self.pty_size().rows);
+                let rows = rows.saturating_add(bar_rows);
+                let size = self
+                    .metrics()
+                    .window_size(WindowSize { rows, cols }, self.config.window.padding);
+                (Response::default(), window::resize(size))
+            }
+            Request::SetColors { scheme } => {
+                if !self.config.colors.contains_key(&scheme)
+                    && ColorScheme::built_in(&scheme).is_none()
+                {
+                    return (
+                        Response::error(format!("No color scheme named {scheme:?}")),
+                        Command::none(),
+                    );
+                }
+                self.set_colors(self.config.resolve_colors(&scheme));
+                (Response::default(), Command::none())
+            }
+            Request::NewTab { profile } => {
+                if let Some(name) = &profile {
+                    if !self.config.profiles.contains_key(name) {
+                        return (
+                            Response::error(format!("No profile named {name:?} in the config")),
+                            Command::none(),
+                        );
+                    }
+                }
+                self.new_tab(profile.as_deref());
+                (Response::default(), Command::none())
+            }
+        }
+    }
+
+    /** Switch color schemes, telling the shells about the new default colors */
+    fn set_colors(&mut self, colors: ColorScheme) {
+        self.theme = colors.theme();
+        for index in 0..self.tabs.len() {
+            let tab = &mut self.tabs[index];
+            if tab.colors.is_some() {
+                // Tabs opened with a profile keep its colors
+                continue;
+            }
+            tab.data
+                .set_default_colors(colors.foreground, colors.background);
+            tab.grid_cache.clear();
+            self.send_responses(index);
+        }
+        self.colors = colors;
+    }
+
+    /** Redraw at another font size, telling the shells how many cells now fit */
+    fn zoom(&mut self, font_size: f32) {
+        let font_size = font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        if font_size == self.font_size {
+            return;
+        }
+        self.font_size = font_size;
+        for tab in &self.tabs {
+            tab.grid_cache.clear();
+        }
+        self.scroll(0);
+        self.resize_pty();
+    }
+
+    /** The size of a cell at the current zoom */
+    fn metrics(&self) -> CellMetrics {
+        let font = &self.config.font;
+        CellMetrics::from_font(font, self.font_size, self.advance)
+            .spaced(font.line_height, font.letter_spacing)
+    }
+
+    /** Whether the cells' width is known, rather than the fallback until the font is measured */
+    fn metrics_known(&self) -> bool {
+        self.advance.is_some() || self.config.font.cell_width.is_some()
+    }
+
+    fn grid_size(&self) -> WindowSize {
+        self.metrics()
+            .grid_size(self.window_size, self.config.window.padding)
+    }
+
+    /** The size the shells see: the rows of the grid that the bars leave in view */
+    fn pty_size(&self) -> WindowSize {
+        self.bars().pty_size(self.grid_size())
+    }
+
+    /**
+     * Tell the children about the current size, e.g. after the window or font
+     * size changed, or a bar opened
+     */
+    fn resize_pty(&mut self) {
+        let size = self.pty_size();
+        self.pty_size = Some(size);
+        for tab in &self.tabs {
+            self.sessions.resize(tab.session, size);
+        }
+    }
+
+    /** Answer the queries a tab's shell made, which it expects on its input */
+    fn send_responses(&mut self, index: usize) {
+        let tab = &mut self.tabs[index];
+        let responses = tab.data.take_responses();
+        if !responses.is_empty() {
+            self.sessions
+                .send(tab.session, InputEvent::Stdin(responses));
+        }
+    }
+}
+
+/**
+ * `WINDOWID` is the X11 window, which is what winit's window id holds there;
+ * Wayland and other platforms have no equivalent
+ */
+fn x11_window_id(id: u64) -> Option<u64> {
+    let is_x11 =
+        cfg!(all(unix, not(target_os = "macos"))) && std::env::var_os("WAYLAND_DISPLAY").is_none();
+    is_x11.then_some(id)
+}
+
+/** The signal or EOF key that, with Ctrl held, `key_code` stands for */
+fn control_key_code(key_code: keyboard::KeyCode) -> Option<ControlKey> {
+    match key_code {
+        keyboard::KeyCode::C => Some(ControlKey::Interrupt),
+        keyboard::KeyCode::D => Some(ControlKey::EndOfFile),
+        keyboard::KeyCode::Z => Some(ControlKey::Suspend),
+        keyboard::KeyCode::Backslash => Some(ControlKey::Quit),
+        _ => None,
+    }
+}
+
+/** A command that shows a desktop notification, where there is a standard one */
+fn notification(text: &str) -> Option<std::process::Command> {
+    if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("osascript");
+        let script = format!("display notification {text:?} with title \"firn\"");
+        command.args(["-e", &script]);
+        Some(command)
+    } else if cfg!(unix) {
+        // The freedesktop notification daemon's client, from libnotify
+        let mut command = std::process::Command::new("notify-send");
+        command.args(["--app-name=firn", "firn", text]);
+        Some(command)
+    } else {
+        None
+    }
+}
+
+/** A command that plays the desktop's bell sound, where there is a standard one */
+fn bell_sound() -> Option<std::process::Command> {
+    if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("osascript");
+        command.args(["-e", "beep"]);
+        Some(command)
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("powershell.exe");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            "[System.Media.SystemSounds]::Beep.Play()",
+        ]);
+        Some(command)
+    } else if cfg!(unix) {
+        // libcanberra plays the XDG sound theme's bell
+        let mut command = std::process::Command::new("canberra-gtk-play");
+        command.arg("--id=bell");
+        Some(command)
+    } else {
+        None
+    }
+}
+
+/** The key whose control character is `ch` */
+fn control_key(ch: char) -> Option<ControlKey> {
+    [
+        ControlKey::Interrupt,
+        ControlKey::EndOfFile,
+        ControlKey::Suspend,
+        ControlKey::Quit,
+    ]
+    .into_iter()
+    .find(|key| u32::from(key.character()) == u32::from(ch))
+}
+
+/**
+ * Log to stderr what `RUST_LOG` asks for, e.g. `RUST_LOG=firn::child=debug`,
+ * or else only errors
+ */
+fn init_tracing(debug_events: bool) {
+    let mut filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::ERROR.into())
+        .from_env_lossy();
+    if debug_events {
+        filter = filter.add_directive(format!("{}=trace", translator::EVENTS).parse().unwrap());
+    }
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
 }
 
 fn main() -> anyhow::Result<()> {
-    env_logger::init();
-    let config = Config::from_path(Path::new("config.json")).unwrap_or_default();
+    let cli = Cli::parse();
+    init_tracing(cli.debug_events);
+    if cli.install_terminfo {
+        return terminfo::install();
+    }
+    let playback = match cli.command {
+        Some(Subcommand::Replay { path }) => {
+            print!("{}", recorder::replay(&path)?);
+            return Ok(());
+        }
+        Some(Subcommand::DefaultConfig { write: false }) => {
+            print!("{}", default_config::render());
+            return Ok(());
+        }
+        Some(Subcommand::DefaultConfig { write: true }) => return default_config::write(),
+        Some(Subcommand::Msg { socket, request }) => {
+            let response = ipc::send(socket.as_deref(), &request)?;
+            if let Some(error) = response.error {
+                anyhow::bail!(error);
+            }
+            if let Some(text) = response.text {
+                print!("{text}");
+            }
+            return Ok(());
+        }
+        Some(Subcommand::Play { path, speed }) => Some(BackendConfig::Playback { path, speed }),
+        None => None,
+    };
+
+    let mut config =
+        Config::load(&Config::layers(cli.config), &cli.overrides).unwrap_or_else(|err| {
+            error!("{err:#}, using the defaults");
+            Config {
+                load_error: Some(format!("{err:#}")),
+                ..Config::default()
+            }
+        });
+    if let Some(name) = cli.profile {
+        match config.with_profile(&name) {
+            Some(profile) => config = profile,
+            None => {
+                error!("No profile named {name:?} in the config");
+                config.load_error = Some(format!("No profile named {name:?} in the config"));
+            }
+        }
+    }
+    if let Some(record_path) = cli.record {
+        config.record_path = Some(record_path);
+    }
+    if let Some(cast_path) = cli.record_cast {
+        config.cast_path = Some(cast_path);
+    }
+    if let Some(working_directory) = cli.working_directory {
+        config.working_directory = Some(working_directory);
+    }
+    config.env.extend(cli.env);
+    if let Some(title) = cli.title {
+        config.window.title = title;
+    }
+    if let Some(class) = cli.class {
+        config.window.class = class;
+    }
+    if cli.hold {
+        config.exit_behavior = ExitBehavior::Hold;
+    }
+    if cli.restore {
+        config.restore_session = RestoreSession::Always;
+    }
+    if let Some(mut program) = cli.execute {
+        config.shell = program.remove(0);
+        config.shell_args = program;
+    }
+    if let Some(device) = cli.serial {
+        let baud_rate = match (cli.baud_rate, &config.backend) {
+            (Some(baud_rate), _) => baud_rate,
+            (None, BackendConfig::Serial { baud_rate, .. }) => *baud_rate,
+            (None, _) => DEFAULT_BAUD_RATE,
+        };
+        config.backend = BackendConfig::Serial { device, baud_rate };
+    }
+    if let Some(fd) = cli.fd {
+        config.backend = BackendConfig::Fd { fd };
+    }
+    if let Some(playback) = playback {
+        config.backend = playback;
+        // Leave the last frame up once the cast is over
+        config.exit_behavior = ExitBehavior::Hold;
+    }
+    if cli.headless {
+        let size = cli.size.or(config.window.dimensions).map_or(
+            WindowSize { rows: 24, cols: 80 },
+            |size| WindowSize {
+                rows: size.rows,
+                cols: size.cols,
+            },
+        );
+        let options = headless::Options {
+            size,
+            ansi: cli.ansi,
+            stream: cli.stream,
+            timeout: cli.timeout.map(Duration::from_secs_f64),
+        };
+        std::process::exit(headless::run(&config, &options)?);
+    }
 
-    Firn::run(Settings::with_flags(config))?;
-    Ok(())
+    let (size, position) = initial_geometry(&config);
+    let mut settings = Settings::with_flags(config);
+    settings.window.size = (size.width, size.height);
+    if let Some((x, y)) = position {
+        settings.window.position = window::Position::Specific(x, y);
+    }
+    settings.window.transparent = settings.flags.window.opacity < 1.0;
+    settings.window.decorations = settings.flags.window.decorations;
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        settings.window.platform_specific.application_id = settings.flags.window.class.clone();
+    }
+    settings.exit_on_close_request = false;
+    Firn::run(settings)?;
+    match EXIT_CODE.load(Ordering::Relaxed) {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
 }
@@ -0,0 +1,87 @@
+use iced::widget::text_input;
+
+/** A line the user named, to jump back to */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mark {
+    pub row: usize,
+    pub name: String,
+}
+
+/** A tab's marks, in the order of their lines */
+#[derive(Default)]
+pub struct Marks(Vec<Mark>);
+
+impl Marks {
+    /** Mark a line, renaming its mark if it has one. Without a name it is numbered. */
+    pub fn add(&mut self, row: usize, name: &str) {
+        let name = match name.trim() {
+            "" => format!("Mark {}", self.0.len() + 1),
+            name => name.to_owned(),
+        };
+        match self.0.binary_search_by_key(&row, |mark| mark.row) {
+            Ok(index) => self.0[index].name = name,
+            Err(index) => self.0.insert(index, Mark { row, name }),
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<Mark> {
+        self.0.iter()
+    }
+
+    pub fn rows(&self) -> Vec<usize> {
+        self.0.iter().map(|mark| mark.row).collect()
+    }
+
+    /** Renumber the marks after `dropped` lines went from the top, forgetting those on them */
+    pub fn shift_up(&mut self, dropped: usize) {
+        self.0
+            .retain_mut(|mark| match mark.row.checked_sub(dropped) {
+                Some(row) => {
+                    mark.row = row;
+                    true
+                }
+                None => false,
+            });
+    }
+}
+
+/** State of the bar that names a new mark and lists the ones there are */
+pub struct MarkBar {
+    pub name: String,
+    /** The line to mark, where the view or cursor was when the bar opened */
+    pub row: usize,
+    pub input_id: text_input::Id,
+}
+
+impl MarkBar {
+    pub fn new(row: usize) -> Self {
+        Self {
+            name: String::new(),
+            row,
+            input_id: text_input::Id::unique(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marks() {
+        let mut marks = Marks::default();
+        marks.add(40, "build");
+        marks.add(10, " ");
+        marks.add(25, "tests");
+        assert_eq!(marks.rows(), [10, 25, 40]);
+        assert_eq!(marks.iter().next().unwrap().name, "Mark 2");
+
+        marks.add(25, "failing tests");
+        assert_eq!(marks.rows(), [10, 25, 40]);
+        assert_eq!(marks.iter().nth(1).unwrap().name, "failing tests");
+
+        marks.shift_up(20);
+        assert_eq!(marks.rows(), [5, 20]);
+        assert_eq!(marks.iter().next().unwrap().name, "failing tests");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::backend::WindowSize;
+use crate::config::FontConfig;
+use iced::Size;
+
+/**
+ * Horizontal advance of a monospace glyph, relative to the font size, until
+ * the font has been measured. This holds for the common monospace faces
+ * (DejaVu Sans Mono, Menlo, Consolas).
+ */
+pub const ADVANCE_RATIO: f32 = 0.6;
+
+/**
+ * Matches iced's default `LineHeight::Relative(1.3)`; `font.cell_height`
+ * overrides it. iced lays text out at the line height it is given rather than
+ * the font's own, which it does not expose, so this is what rows are drawn at.
+ */
+pub const LINE_HEIGHT_RATIO: f32 = 1.3;
+
+/** How much each zoom step changes the font size by */
+pub const ZOOM_STEP: f32 = 1.0;
+/** Bounds on zooming, so the grid stays legible and at least a few cells fit */
+pub const MIN_FONT_SIZE: f32 = 6.0;
+pub const MAX_FONT_SIZE: f32 = 72.0;
+
+/** The size of a single cell of the grid, in logical pixels */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellMetrics {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl CellMetrics {
+    /**
+     * Cells of `font` at `font_size`, before `spaced` stretches them, given
+     * the advance measured from the font if it has been
+     */
+    pub fn from_font(font: &FontConfig, font_size: f32, advance: Option<f32>) -> Self {
+        let advance = font.cell_width.or(advance).unwrap_or(ADVANCE_RATIO);
+        Self {
+            width: font_size * advance,
+            height: font_size * font.cell_height,
+        }
+    }
+
+    /** Stretch the cells by these factors, for looser or denser text */
+    pub fn spaced(self, line_height: f32, letter_spacing: f32) -> Self {
+        Self {
+            width: self.width * letter_spacing,
+            height: self.height * line_height,
+        }
+    }
+
+    /** The size of window that fits `grid` exactly, padding included */
+    pub fn window_size(&self, grid: WindowSize, padding: u16) -> Size<u32> {
+        let padding = 2 * u32::from(padding);
+        Size::new(
+            (f32::from(grid.cols) * self.width).ceil() as u32 + padding,
+            (f32::from(grid.rows) * self.height).ceil() as u32 + padding,
+        )
+    }
+
+    /** How many rows and columns fit into a window of the given size, inside its padding */
+    pub fn grid_size(&self, window_size: Size<u32>, padding: u16) -> WindowSize {
+        let padding = 2 * u32::from(padding);
+        let height = window_size.height.saturating_sub(padding) as f32;
+        let width = window_size.width.saturating_sub(padding) as f32;
+        let rows = (height / self.height).floor().max(1.0);
+        let cols = (width / self.width).floor().max(1.0);
+        WindowSize {
+            rows: rows as u16,
+            cols: cols as u16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_size() {
+        let metrics = CellMetrics::from_font(&FontConfig::default(), 10.0, None);
+        assert_eq!(
+            metrics.grid_size(Size::new(600, 260), 0),
+            WindowSize {
+                rows: 20,
+                cols: 100
+            }
+        );
+        assert_eq!(
+            metrics.grid_size(Size::new(600, 260), 10),
+            WindowSize { rows: 18, cols: 96 }
+        );
+        assert_eq!(
+            metrics.spaced(2.0, 1.5).grid_size(Size::new(600, 260), 0),
+            WindowSize { rows: 10, cols: 66 }
+        );
+        let narrow = FontConfig {
+            cell_width: Some(0.5),
+            cell_height: 1.2,
+            ..FontConfig::default()
+        };
+        assert_eq!(
+            CellMetrics::from_font(&narrow, 10.0, Some(0.7)).grid_size(Size::new(600, 260), 0),
+            WindowSize {
+                rows: 21,
+                cols: 120
+            }
+        );
+        let grid = WindowSize {
+            rows: 35,
+            cols: 120,
+        };
+        assert_eq!(
+            CellMetrics::from_font(&FontConfig::default(), 10.0, Some(0.5))
+                .grid_size(Size::new(600, 260), 0),
+            WindowSize {
+                rows: 20,
+                cols: 120
+            }
+        );
+        let size = metrics.window_size(grid, 4);
+        assert_eq!(size, Size::new(728, 463));
+        assert_eq!(metrics.grid_size(size, 4), grid);
+    }
+}
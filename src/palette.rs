@@ -0,0 +1,55 @@
+use iced::Color;
+
+/** The 16 ANSI colors, as xterm draws them */
+const ANSI: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xCD, 0x00, 0x00),
+    (0x00, 0xCD, 0x00),
+    (0xCD, 0xCD, 0x00),
+    (0x00, 0x00, 0xEE),
+    (0xCD, 0x00, 0xCD),
+    (0x00, 0xCD, 0xCD),
+    (0xE5, 0xE5, 0xE5),
+    (0x7F, 0x7F, 0x7F),
+    (0xFF, 0x00, 0x00),
+    (0x00, 0xFF, 0x00),
+    (0xFF, 0xFF, 0x00),
+    (0x5C, 0x5C, 0xFF),
+    (0xFF, 0x00, 0xFF),
+    (0x00, 0xFF, 0xFF),
+    (0xFF, 0xFF, 0xFF),
+];
+
+/**
+ * One of the 256 indexed colors: the 16 ANSI colors, then a 6x6x6 color
+ * cube, then a ramp of 24 greys
+ */
+pub fn indexed(n: u8) -> Color {
+    let (r, g, b) = match n {
+        0..=15 => ANSI[n as usize],
+        16..=231 => {
+            let n = n - 16;
+            let level = |i: u8| if i == 0 { 0 } else { 55 + 40 * i };
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        232..=255 => {
+            let grey = 8 + 10 * (n - 232);
+            (grey, grey, grey)
+        }
+    };
+    Color::from_rgb8(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indexed() {
+        assert_eq!(indexed(1), Color::from_rgb8(0xCD, 0, 0));
+        assert_eq!(indexed(16), Color::from_rgb8(0, 0, 0));
+        assert_eq!(indexed(196), Color::from_rgb8(0xFF, 0, 0));
+        assert_eq!(indexed(231), Color::from_rgb8(0xFF, 0xFF, 0xFF));
+        assert_eq!(indexed(255), Color::from_rgb8(0xEE, 0xEE, 0xEE));
+    }
+}
@@ -0,0 +1,144 @@
+const BRACKETED_PASTE_START: &[u8] = b"\x1B[200~";
+const BRACKETED_PASTE_END: &[u8] = b"\x1B[201~";
+
+/**
+ * Whether a pasted character may reach the child when sanitizing. Escape and
+ * the other C0 and C1 controls could otherwise run commands or break out of
+ * a bracketed paste; tabs and newlines are kept.
+ */
+fn is_safe(ch: char) -> bool {
+    matches!(ch, '\t' | '\n' | '\r') || !ch.is_control()
+}
+
+/** How line breaks are shown while a paste is edited on one line */
+const LINE_BREAK: char = '↵';
+
+/**
+ * Whether pasting `text` could do what the user did not see coming: run
+ * commands at its line breaks, unless the shell takes it as a bracketed
+ * paste, or send control characters that sanitizing does not strip
+ */
+pub fn is_risky(text: &str, sanitize: bool, bracketed: bool) -> bool {
+    (!bracketed && text.contains(['\n', '\r'])) || (!sanitize && has_controls(text))
+}
+
+/** Whether `text` has control characters that sanitizing would strip */
+pub fn has_controls(text: &str) -> bool {
+    !text.chars().all(is_safe)
+}
+
+/** The first `max_lines` lines of a paste, with control characters escaped so that they show */
+pub fn preview(text: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut preview: Vec<String> = lines
+        .iter()
+        .take(max_lines)
+        .map(|line| {
+            line.chars()
+                .map(|ch| match is_safe(ch) {
+                    true => ch.to_string(),
+                    false => ch.escape_debug().to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    if lines.len() > max_lines {
+        preview.push(format!("… and {} more lines", lines.len() - max_lines));
+    }
+    preview.join("\n")
+}
+
+/** A paste with its line breaks turned into spaces, and none at the end */
+pub fn join_lines(text: &str) -> String {
+    text.trim_end_matches(['\n', '\r'])
+        .replace("\r\n", " ")
+        .replace(['\n', '\r'], " ")
+}
+
+/** A paste on one line, to edit in a text input */
+pub fn to_one_line(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace(['\n', '\r'], &LINE_BREAK.to_string())
+}
+
+/** An edited paste with its line breaks back */
+pub fn from_one_line(text: &str) -> String {
+    text.replace(LINE_BREAK, "\n")
+}
+
+/** Turn clipboard text into the bytes to send to the child */
+pub fn prepare(text: &str, sanitize: bool, bracketed: bool) -> Vec<u8> {
+    // Like a typed Enter, newlines reach the child as carriage returns
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    let text: String = if sanitize {
+        text.chars().filter(|&ch| is_safe(ch)).collect()
+    } else {
+        text
+    };
+    if bracketed {
+        [BRACKETED_PASTE_START, text.as_bytes(), BRACKETED_PASTE_END].concat()
+    } else {
+        text.into_bytes()
+    }
+}
+
+/**
+ * Quote `text` for a POSIX shell, e.g. a path dropped onto the window. Text
+ * made only of characters no shell treats specially is left as it is.
+ */
+pub fn shell_quote(text: &str) -> String {
+    let plain = |ch: char| ch.is_alphanumeric() || "-_./~+:@%,=".contains(ch);
+    if !text.is_empty() && text.chars().all(plain) {
+        return text.to_owned();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare() {
+        assert_eq!(prepare("a\r\nb\nc", false, false), b"a\rb\rc");
+        assert_eq!(
+            prepare("x\x1B[201~;rm\x07\u{9B}", true, false),
+            b"x[201~;rm"
+        );
+        assert_eq!(prepare("ls\x03", false, true), b"\x1B[200~ls\x03\x1B[201~");
+    }
+
+    #[test]
+    fn test_is_risky() {
+        assert!(!is_risky("ls -l", false, false));
+        assert!(is_risky("ls\nrm -rf build", true, false));
+        assert!(!is_risky("ls\nrm -rf build", true, true));
+        assert!(is_risky("ls\x1B[201~", false, true));
+        assert!(!is_risky("ls\x1B[201~", true, true));
+    }
+
+    #[test]
+    fn test_join_lines() {
+        assert_eq!(join_lines("cd build &&\r\nmake\n\n"), "cd build && make");
+        assert_eq!(join_lines("a\n\nb"), "a  b");
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(
+            preview("a\x1Bb\nc\nd\ne", 2),
+            "a\\u{1b}b\nc\n… and 2 more lines"
+        );
+        let edited = to_one_line("make\r\nmake install\n");
+        assert_eq!(edited, "make↵make install↵");
+        assert_eq!(from_one_line(&edited), "make\nmake install\n");
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/tmp/notes-1.txt"), "/tmp/notes-1.txt");
+        assert_eq!(shell_quote("/tmp/my notes"), "'/tmp/my notes'");
+        assert_eq!(shell_quote("it's $HOME"), r"'it'\''s $HOME'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,296 @@
+use crate::ipc::Request;
+use anyhow::{Context, Result};
+use mlua::{Function, IntoLuaMulti, Lua, RegistryKey, Variadic};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use tracing::error;
+
+/**
+ * Lua scripts from the `plugins` setting, which register hooks through the
+ * global `firn` table:
+ *
+ * - `firn.on_output(pattern, fn)` calls `fn(line, captures...)` for each line
+ *   of output matching the regular expression
+ * - `firn.on_title_change(fn)` calls `fn(title)` when a shell sets its title
+ * - `firn.on_bell(fn)` calls `fn()` when the bell rings
+ * - `firn.action(name, fn)` makes `{ Plugin = "name" }` a keybinding action
+ *
+ * Hooks act on firn through `firn.send_text(text)`, `firn.new_tab(profile)`,
+ * `firn.set_colors(scheme)` and `firn.resize(cols, rows)`, which are carried
+ * out once the hook returns, and can log with `firn.log(message)`. A hook that
+ * fails is logged and its requests dropped, and the other hooks still run.
+ */
+pub struct Plugins {
+    lua: Lua,
+    hooks: Rc<RefCell<Hooks>>,
+    /** What the running hook asked firn to do */
+    requests: Rc<RefCell<Vec<Request>>>,
+}
+
+#[derive(Default)]
+struct Hooks {
+    output: Vec<(Regex, RegistryKey)>,
+    title_change: Vec<RegistryKey>,
+    bell: Vec<RegistryKey>,
+    actions: HashMap<String, RegistryKey>,
+}
+
+impl Plugins {
+    pub fn new() -> Result<Self> {
+        let plugins = Self {
+            lua: Lua::new(),
+            hooks: Rc::default(),
+            requests: Rc::default(),
+        };
+        plugins.register_api()?;
+        Ok(plugins)
+    }
+
+    fn register_api(&self) -> mlua::Result<()> {
+        let lua = &self.lua;
+        let firn = lua.create_table()?;
+
+        let hooks = self.hooks.clone();
+        let on_output = lua.create_function(move |lua, (pattern, hook): (String, Function)| {
+            let pattern = Regex::new(&pattern).map_err(mlua::Error::external)?;
+            let hook = lua.create_registry_value(hook)?;
+            hooks.borrow_mut().output.push((pattern, hook));
+            Ok(())
+        })?;
+        firn.set("on_output", on_output)?;
+        let hooks = self.hooks.clone();
+        let on_title_change = lua.create_function(move |lua, hook: Function| {
+            let hook = lua.create_registry_value(hook)?;
+            hooks.borrow_mut().title_change.push(hook);
+            Ok(())
+        })?;
+        firn.set("on_title_change", on_title_change)?;
+        let hooks = self.hooks.clone();
+        let on_bell = lua.create_function(move |lua, hook: Function| {
+            let hook = lua.create_registry_value(hook)?;
+            hooks.borrow_mut().bell.push(hook);
+            Ok(())
+        })?;
+        firn.set("on_bell", on_bell)?;
+        let hooks = self.hooks.clone();
+        let action = lua.create_function(move |lua, (name, hook): (String, Function)| {
+            let hook = lua.create_registry_value(hook)?;
+            hooks.borrow_mut().actions.insert(name, hook);
+            Ok(())
+        })?;
+        firn.set("action", action)?;
+
+        let requests = self.requests.clone();
+        let send_text = lua.create_function(move |_, text: String| {
+            requests.borrow_mut().push(Request::SendText { text });
+            Ok(())
+        })?;
+        firn.set("send_text", send_text)?;
+        let requests = self.requests.clone();
+        let new_tab = lua.create_function(move |_, profile: Option<String>| {
+            requests.borrow_mut().push(Request::NewTab { profile });
+            Ok(())
+        })?;
+        firn.set("new_tab", new_tab)?;
+        let requests = self.requests.clone();
+        let set_colors = lua.create_function(move |_, scheme: String| {
+            requests.borrow_mut().push(Request::SetColors { scheme });
+            Ok(())
+        })?;
+        firn.set("set_colors", set_colors)?;
+        let requests = self.requests.clone();
+        let resize = lua.create_function(move |_, (cols, rows): (u16, u16)| {
+            requests.borrow_mut().push(Request::Resize { cols, rows });
+            Ok(())
+        })?;
+        firn.set("resize", resize)?;
+        let log = lua.create_function(|_, message: String| {
+            tracing::info!("{message}");
+            Ok(())
+        })?;
+        firn.set("log", log)?;
+
+        lua.globals().set("firn", firn)
+    }
+
+    /** Whether any hook wants lines of output, which are otherwise not worth collecting */
+    pub fn watches_output(&self) -> bool {
+        !self.hooks.borrow().output.is_empty()
+    }
+
+    pub fn load(&self, path: &Path) -> Result<()> {
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("Unable to read {path:?}"))?;
+        self.load_source(&path.display().to_string(), &source)
+    }
+
+    fn load_source(&self, name: &str, source: &str) -> Result<()> {
+        self.lua
+            .load(source)
+            .set_name(name)
+            .exec()
+            .with_context(|| format!("Plugin {name} failed"))
+    }
+
+    /** Run the hooks whose pattern matches a line of output */
+    pub fn on_output(&self, line: &str) -> Vec<Request> {
+        let calls: Vec<_> = self
+            .hooks
+            .borrow()
+            .output
+            .iter()
+            .filter_map(|(pattern, hook)| {
+                let captures = pattern.captures(line)?;
+                let arguments: Variadic<Option<String>> = captures
+                    .iter()
+                    .map(|capture| capture.map(|capture| capture.as_str().to_owned()))
+                    .collect();
+                Some((self.lua.registry_value::<Function>(hook), arguments))
+            })
+            .collect();
+        for (hook, arguments) in calls {
+            self.call(hook, arguments);
+        }
+        self.requests.take()
+    }
+
+    pub fn on_title_change(&self, title: &str) -> Vec<Request> {
+        for hook in self.functions(|hooks| &hooks.title_change) {
+            self.call(hook, title);
+        }
+        self.requests.take()
+    }
+
+    pub fn on_bell(&self) -> Vec<Request> {
+        for hook in self.functions(|hooks| &hooks.bell) {
+            self.call(hook, ());
+        }
+        self.requests.take()
+    }
+
+    /** Run the action a plugin registered under `name` */
+    pub fn run_action(&self, name: &str) -> Result<Vec<Request>> {
+        let hook = match self.hooks.borrow().actions.get(name) {
+            Some(hook) => self.lua.registry_value::<Function>(hook)?,
+            None => anyhow::bail!("No plugin action named {name:?}"),
+        };
+        let result = hook.call::<_, ()>(());
+        let requests = self.requests.take();
+        result?;
+        Ok(requests)
+    }
+
+    /** Call one hook, logging it and dropping what it asked for if it fails */
+    fn call<'lua>(
+        &'lua self,
+        hook: mlua::Result<Function<'lua>>,
+        arguments: impl IntoLuaMulti<'lua>,
+    ) {
+        let before = self.requests.borrow().len();
+        if let Err(err) = hook.and_then(|hook| hook.call::<_, ()>(arguments)) {
+            error!("Plugin hook failed: {err}");
+            self.requests.borrow_mut().truncate(before);
+        }
+    }
+
+    /**
+     * The hooks of one kind, taken out of `hooks` first so that they can
+     * register more hooks while they run
+     */
+    fn functions(&self, kind: impl Fn(&Hooks) -> &Vec<RegistryKey>) -> Vec<mlua::Result<Function>> {
+        kind(&self.hooks.borrow())
+            .iter()
+            .map(|hook| self.lua.registry_value(hook))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks() {
+        let plugins = Plugins::new().unwrap();
+        plugins
+            .load_source(
+                "test.lua",
+                r#"
+                firn.on_output("^error: (\\w+)", function(line, word)
+                    firn.send_text("echo " .. word .. "\r")
+                end)
+                local bells = 0
+                firn.on_bell(function()
+                    bells = bells + 1
+                    if bells == 2 then firn.new_tab("work") end
+                end)
+                firn.on_title_change(function(title) firn.set_colors(title) end)
+                firn.action("wide", function() firn.resize(200, 50) end)
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            plugins.on_output("error: disk full"),
+            [Request::SendText {
+                text: "echo disk\r".into()
+            }]
+        );
+        assert_eq!(plugins.on_output("all fine"), []);
+        assert_eq!(plugins.on_bell(), []);
+        assert_eq!(
+            plugins.on_bell(),
+            [Request::NewTab {
+                profile: Some("work".into())
+            }]
+        );
+        assert_eq!(
+            plugins.on_title_change("light"),
+            [Request::SetColors {
+                scheme: "light".into()
+            }]
+        );
+        assert_eq!(
+            plugins.run_action("wide").unwrap(),
+            [Request::Resize {
+                cols: 200,
+                rows: 50
+            }]
+        );
+        assert!(plugins.run_action("narrow").is_err());
+        assert!(plugins
+            .load_source("bad.lua", r#"firn.on_output("(", print)"#)
+            .is_err());
+    }
+
+    #[test]
+    fn test_failing_hook() {
+        let plugins = Plugins::new().unwrap();
+        plugins
+            .load_source(
+                "test.lua",
+                r#"
+                firn.on_output("disk", function()
+                    firn.send_text("lost")
+                    error("broken")
+                end)
+                firn.on_output("full", function() firn.send_text("kept") end)
+                firn.action("broken", function()
+                    firn.new_tab(nil)
+                    error("broken")
+                end)
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            plugins.on_output("disk full"),
+            [Request::SendText {
+                text: "kept".into()
+            }]
+        );
+        assert!(plugins.run_action("broken").is_err());
+        assert_eq!(plugins.on_bell(), []);
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(all(unix, not(target_os = "macos")))]
+use {
+    std::sync::{Arc, Mutex},
+    tracing::error,
+};
+
+/**
+ * The PRIMARY selection, which holds whatever text was last selected and is
+ * pasted with the middle mouse button, apart from the CLIPBOARD. Reached
+ * through X11, whose selections Wayland compositors share through XWayland.
+ */
+#[derive(Clone)]
+pub struct PrimarySelection {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    clipboard: Arc<Mutex<clipboard_x11::Clipboard>>,
+}
+
+impl PrimarySelection {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn connect() -> Option<Self> {
+        match clipboard_x11::Clipboard::connect() {
+            Ok(clipboard) => Some(Self {
+                clipboard: Arc::new(Mutex::new(clipboard)),
+            }),
+            Err(err) => {
+                error!("Unable to reach the primary selection: {err}");
+                None
+            }
+        }
+    }
+
+    /** Other platforms have no primary selection, and paste the clipboard on middle click */
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub fn connect() -> Option<Self> {
+        None
+    }
+
+    pub fn write(&self, _text: String) {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if let Err(err) = self.clipboard.lock().unwrap().write_primary(_text) {
+            error!("Unable to set the primary selection: {err}");
+        }
+    }
+
+    /** Waits for the selection's owner, which may be slow to answer, on a thread of its own */
+    pub async fn read(self) -> Option<String> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let read =
+                tokio::task::spawn_blocking(move || self.clipboard.lock().unwrap().read_primary());
+            match read.await {
+                Ok(Ok(text)) => Some(text),
+                Ok(Err(err)) => {
+                    error!("Unable to read the primary selection: {err}");
+                    None
+                }
+                Err(err) => {
+                    error!("Unable to read the primary selection: {err}");
+                    None
+                }
+            }
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        None
+    }
+}
@@ -0,0 +1,94 @@
+use crate::data::{DataComponent, PromptMark};
+use regex::Regex;
+use std::ops::RangeInclusive;
+
+/**
+ * Rows that prompts start on, top to bottom. They come from the shell's
+ * OSC 133 marks if it sends them, or else from the lines `pattern` matches.
+ */
+pub fn prompt_rows(data: &DataComponent, pattern: Option<&Regex>) -> Vec<usize> {
+    let marks = data.prompt_marks();
+    let mut rows: Vec<_> = if marks.is_empty() {
+        let Some(pattern) = pattern else {
+            return vec![];
+        };
+        data.search(pattern, 0..data.line_count())
+            .into_iter()
+            .map(|found| found.row)
+            .collect()
+    } else {
+        marks
+            .iter()
+            .filter(|(_, mark)| *mark == PromptMark::Prompt)
+            .map(|(position, _)| position.row)
+            .collect()
+    };
+    rows.dedup();
+    rows
+}
+
+/** The last prompt above `row`, and the first one below it */
+pub fn around(prompts: &[usize], row: usize) -> (Option<usize>, Option<usize>) {
+    let i = prompts.partition_point(|&prompt| prompt < row);
+    let below = prompts[i..].iter().find(|&&prompt| prompt > row);
+    (i.checked_sub(1).map(|i| prompts[i]), below.copied())
+}
+
+/**
+ * The rows the last finished command wrote to: from its OSC 133 `C` mark
+ * to the `D` after it, or else the rows between the last two prompts
+ */
+pub fn last_output(data: &DataComponent, pattern: Option<&Regex>) -> Option<RangeInclusive<usize>> {
+    let marks = data.prompt_marks();
+    if marks.is_empty() {
+        let prompts = prompt_rows(data, pattern);
+        let [.., previous, last] = prompts[..] else {
+            return None;
+        };
+        return (previous + 1 < last).then(|| previous + 1..=last - 1);
+    }
+    let finished = marks
+        .iter()
+        .rposition(|(_, mark)| *mark == PromptMark::Finished)?;
+    let (start, _) = marks[..finished]
+        .iter()
+        .rfind(|(_, mark)| *mark == PromptMark::Output)?;
+    let (end, _) = &marks[finished];
+    // The mark comes at the start of the line after output that ended with a newline
+    let last = if end.col == 0 {
+        end.row.checked_sub(1)?
+    } else {
+        end.row
+    };
+    (start.row <= last).then_some(start.row..=last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::Translator;
+
+    #[test]
+    fn test_prompts() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write(b"$ ls\r\na b\r\nc\r\n$ true\r\n$ ", &mut data);
+        let pattern = Regex::new(r"^\$ ").unwrap();
+        assert!(prompt_rows(&data, None).is_empty());
+        let prompts = prompt_rows(&data, Some(&pattern));
+        assert_eq!(prompts, [0, 3, 4]);
+        assert_eq!(around(&prompts, 3), (Some(0), Some(4)));
+        assert_eq!(around(&prompts, 2), (Some(0), Some(3)));
+        assert_eq!(around(&prompts, 0), (None, Some(3)));
+        // The last command wrote nothing
+        assert_eq!(last_output(&data, Some(&pattern)), None);
+
+        let mut data = DataComponent::new();
+        translator.write(
+            b"\x1B]133;A\x07$ ls\r\n\x1B]133;C\x07a b\r\nc\r\n\x1B]133;D;0\x07\x1B]133;A\x07$ ",
+            &mut data,
+        );
+        assert_eq!(prompt_rows(&data, Some(&pattern)), [0, 3]);
+        assert_eq!(last_output(&data, None), Some(1..=2));
+    }
+}
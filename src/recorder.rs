@@ -0,0 +1,47 @@
+use anyhow::Result;
+use firn_core::recording::{self, Chunk};
+use firn_core::Terminal;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/**
+ * Tees the raw bytes read from the pty into a file, so that rendering bugs
+ * can be reproduced later with `firn replay`
+ */
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, data: &[u8]) -> Result<()> {
+        let chunk = Chunk {
+            time: self.start.elapsed().as_secs_f64(),
+            data: data.into(),
+        };
+        serde_json::to_writer(&mut self.writer, &chunk)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/** Replay a recording through a fresh `Terminal` and return the rendered screen */
+pub fn replay(path: &Path) -> Result<String> {
+    let mut terminal = Terminal::new()?;
+    for chunk in recording::chunks(BufReader::new(File::open(path)?)) {
+        terminal.advance(&chunk?.data);
+    }
+    let mut screen = vec![];
+    terminal.render_to(&mut screen, usize::MAX)?;
+    Ok(String::from_utf8_lossy(&screen).into_owned())
+}
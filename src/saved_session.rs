@@ -0,0 +1,84 @@
+use crate::window_state::state_dir;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+/** A tab as it was when the window closed */
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct SavedTab {
+    /** The profile it was opened with */
+    pub profile: Option<String>,
+    pub working_directory: Option<PathBuf>,
+    /** What was running in the foreground, as a shell command line */
+    pub command: Option<String>,
+}
+
+/** The tabs of the window that closed last, to reopen next time */
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct SavedSession {
+    pub tabs: Vec<SavedTab>,
+    /** Index of the tab that was showing */
+    pub active: usize,
+}
+
+fn path() -> Option<PathBuf> {
+    Some(state_dir()?.join("firn").join("session.json"))
+}
+
+impl SavedSession {
+    /** What was saved last time, if anything */
+    pub fn load() -> Option<Self> {
+        let file = File::open(path()?).ok()?;
+        serde_json::from_reader(file)
+            .ok()
+            .filter(|session: &Self| !session.tabs.is_empty())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = path() else {
+            bail!("Nowhere to save the session: HOME is not set");
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Unable to create {dir:?}"))?;
+        }
+        let file = File::create(&path).with_context(|| format!("Unable to create {path:?}"))?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /** Forget the saved session, e.g. once every shell was exited on purpose */
+    pub fn remove() {
+        if let Some(path) = path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /** A lone shell waiting at its prompt is no different from a fresh window */
+    pub fn is_worth_offering(&self) -> bool {
+        self.tabs.len() > 1 || self.tabs.iter().any(|tab| tab.command.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_worth_offering() {
+        let shell = SavedTab {
+            profile: None,
+            working_directory: Some("/tmp".into()),
+            command: None,
+        };
+        let mut session = SavedSession {
+            tabs: vec![shell.clone()],
+            active: 0,
+        };
+        assert!(!session.is_worth_offering());
+        session.tabs[0].command = Some("htop".into());
+        assert!(session.is_worth_offering());
+        session.tabs = vec![shell.clone(), shell];
+        assert!(session.is_worth_offering());
+    }
+}
@@ -0,0 +1,74 @@
+/** How wide the scrollbar is, in logical pixels */
+pub const WIDTH: f32 = 8.0;
+
+/** Keeps the thumb big enough to grab even with a long history */
+const MIN_THUMB_HEIGHT: f32 = 16.0;
+
+/**
+ * The part of the scrollbar standing for the rows in view, placed along a
+ * track that spans the whole grid
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thumb {
+    pub top: f32,
+    pub height: f32,
+}
+
+impl Thumb {
+    /** Where the thumb sits, or `None` if there is no history to scroll through */
+    pub fn new(track: f32, rows: usize, max_scrollback: usize, scrollback: usize) -> Option<Self> {
+        if max_scrollback == 0 {
+            return None;
+        }
+        let visible = rows as f32 / (rows + max_scrollback) as f32;
+        let height = (track * visible).max(MIN_THUMB_HEIGHT).min(track);
+        let scrolled = 1.0 - scrollback.min(max_scrollback) as f32 / max_scrollback as f32;
+        Some(Self {
+            top: (track - height) * scrolled,
+            height,
+        })
+    }
+
+    pub fn contains(&self, y: f32) -> bool {
+        (self.top..self.top + self.height).contains(&y)
+    }
+
+    /** How far back to scroll for the thumb's top to move to `top` */
+    pub fn scrollback_at(&self, top: f32, track: f32, max_scrollback: usize) -> usize {
+        let travel = track - self.height;
+        if travel <= 0.0 {
+            return 0;
+        }
+        let scrolled = (top / travel).clamp(0.0, 1.0);
+        ((1.0 - scrolled) * max_scrollback as f32).round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumb() {
+        assert_eq!(Thumb::new(100.0, 10, 0, 0), None);
+
+        let bottom = Thumb::new(100.0, 25, 75, 0).unwrap();
+        assert_eq!(
+            bottom,
+            Thumb {
+                top: 75.0,
+                height: 25.0
+            }
+        );
+        let top = Thumb::new(100.0, 25, 75, 75).unwrap();
+        assert_eq!(top.top, 0.0);
+        assert!(top.contains(10.0) && !top.contains(30.0));
+
+        assert_eq!(bottom.scrollback_at(0.0, 100.0, 75), 75);
+        assert_eq!(bottom.scrollback_at(37.5, 100.0, 75), 38);
+        assert_eq!(bottom.scrollback_at(90.0, 100.0, 75), 0);
+
+        let long = Thumb::new(100.0, 10, 10_000, 0).unwrap();
+        assert_eq!(long.height, MIN_THUMB_HEIGHT);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::data::DataComponent;
+pub use firn_core::search::SearchMatch;
+use iced::widget::text_input;
+use regex::Regex;
+use std::ops::Range;
+use std::time::Duration;
+
+/**
+ * Lines searched at a time, from the newest up, so that typing into the
+ * search bar stays responsive however long the history is
+ */
+const SCAN_LINES: usize = 5000;
+
+/** How often another batch of older lines is searched, until all of them have been */
+pub const SCAN_INTERVAL: Duration = Duration::from_millis(10);
+
+/** State of the search bar */
+pub struct Search {
+    pub query: String,
+    /** Treat the query as a regular expression rather than literal text */
+    pub regex: bool,
+    pub matches: Vec<SearchMatch>,
+    /** Index into `matches` of the one jumped to */
+    pub current: Option<usize>,
+    /** The lines searched so far, the ones above still to come */
+    scanned: Range<usize>,
+    /** Why the query could not be used, e.g. an invalid regex */
+    pub error: Option<String>,
+    pub input_id: text_input::Id,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            regex: false,
+            matches: vec![],
+            current: None,
+            scanned: 0..0,
+            error: None,
+            input_id: text_input::Id::unique(),
+        }
+    }
+
+    fn pattern(&self) -> Result<Regex, regex::Error> {
+        if self.regex {
+            Regex::new(&self.query)
+        } else {
+            Regex::new(&regex::escape(&self.query))
+        }
+    }
+
+    /**
+     * Search again, e.g. after the query changed or on another tab. Only the
+     * newest lines are searched straight away; `scan` goes through the rest.
+     */
+    pub fn update(&mut self, data: &DataComponent) {
+        self.error = None;
+        self.matches.clear();
+        self.current = None;
+        let line_count = data.line_count();
+        self.scanned = 0..line_count;
+        if self.query.is_empty() {
+            return;
+        }
+        if let Err(err) = self.pattern() {
+            // Parse errors point at the problem over several lines; the last one says what it is
+            let err = err.to_string();
+            self.error = err.lines().last().map(str::to_owned);
+            return;
+        }
+        self.scanned = line_count..line_count;
+        self.scan(data);
+    }
+
+    /**
+     * Search the lines from `row` down again, after output changed them, and
+     * any written since
+     */
+    pub fn refresh(&mut self, data: &DataComponent, row: usize) {
+        let Ok(pattern) = self.pattern() else {
+            return;
+        };
+        if self.query.is_empty() {
+            return;
+        }
+        // Lines not searched yet are left to `scan`
+        let row = row.max(self.scanned.start);
+        let kept = self.matches.partition_point(|found| found.row < row);
+        self.matches.truncate(kept);
+        self.matches
+            .extend(data.search(&pattern, row..data.line_count()));
+        self.scanned.end = data.line_count();
+        self.current = match self.current {
+            _ if self.matches.is_empty() => None,
+            Some(current) => Some(current.min(self.matches.len() - 1)),
+            // Start from the most recent output
+            None => Some(self.matches.len() - 1),
+        };
+    }
+
+    /** Renumber the matches after the oldest lines were dropped, forgetting those on them */
+    pub fn shift_up(&mut self, dropped: usize) {
+        let gone = self.matches.partition_point(|found| found.row < dropped);
+        self.matches.drain(..gone);
+        for found in &mut self.matches {
+            found.row -= dropped;
+        }
+        self.current = match self.current {
+            _ if self.matches.is_empty() => None,
+            Some(current) => Some(current.saturating_sub(gone).min(self.matches.len() - 1)),
+            None => None,
+        };
+        self.scanned =
+            self.scanned.start.saturating_sub(dropped)..self.scanned.end.saturating_sub(dropped);
+    }
+
+    /** Whether every line has been searched, so that the match count is final */
+    pub fn is_complete(&self) -> bool {
+        self.scanned.start == 0
+    }
+
+    /** Search the next batch of older lines, if any are left */
+    pub fn scan(&mut self, data: &DataComponent) {
+        if self.is_complete() {
+            return;
+        }
+        let end = self.scanned.start.min(data.line_count());
+        let start = end.saturating_sub(SCAN_LINES);
+        self.scanned.start = start;
+        let Ok(pattern) = self.pattern() else {
+            return;
+        };
+        let found = data.search(&pattern, start..end);
+        let added = found.len();
+        self.matches.splice(0..0, found);
+        self.current = match self.current {
+            Some(current) => Some(current + added),
+            // Start from the most recent output
+            None => self.matches.len().checked_sub(1),
+        };
+    }
+
+    /** Move to the next match further down, wrapping around */
+    pub fn next(&mut self) -> Option<&SearchMatch> {
+        let len = self.matches.len();
+        self.current = self.current.map(|current| (current + 1) % len);
+        self.current.map(|current| &self.matches[current])
+    }
+
+    /**
+     * Move to the previous match further up, searching older lines for it
+     * if need be. Wraps around once there are none.
+     */
+    pub fn previous(&mut self, data: &DataComponent) -> Option<&SearchMatch> {
+        let had_current = self.current.is_some();
+        while matches!(self.current, None | Some(0)) && !self.is_complete() {
+            self.scan(data);
+        }
+        // Without a current match the newest one found is the one wanted
+        if had_current {
+            let len = self.matches.len();
+            self.current = self.current.map(|current| (current + len - 1) % len);
+        }
+        self.current.map(|current| &self.matches[current])
+    }
+
+    /** How far through the matches the current one is, e.g. "3/12", or "3/12+" while searching on */
+    pub fn counter(&self) -> Option<String> {
+        let current = self.current?;
+        let more = if self.is_complete() { "" } else { "+" };
+        Some(format!("{}/{}{more}", current + 1, self.matches.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::Translator;
+
+    #[test]
+    fn test_search() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        translator.write("axb\r\nAb a.b\r\n".as_bytes(), &mut data);
+
+        let mut search = Search::new();
+        search.query = "a.b".into();
+        search.update(&data);
+        assert_eq!(
+            search.matches,
+            [SearchMatch {
+                row: 1,
+                columns: 3..6
+            }]
+        );
+
+        search.regex = true;
+        search.update(&data);
+        assert_eq!(search.matches.len(), 2);
+        assert_eq!(search.current, Some(1));
+        assert_eq!(search.counter().as_deref(), Some("2/2"));
+        assert_eq!(search.next().unwrap().row, 0);
+        assert_eq!(search.next().unwrap().row, 1);
+        assert_eq!(search.previous(&data).unwrap().row, 0);
+
+        search.query = "(".into();
+        search.update(&data);
+        assert!(search.error.is_some());
+        assert_eq!(search.current, None);
+    }
+    #[test]
+    fn test_scan_lazily() {
+        let mut data = DataComponent::new();
+        let mut translator = Translator::new().unwrap();
+        for _ in 0..SCAN_LINES {
+            translator.write(b"match\r\n", &mut data);
+        }
+        translator.write(b"match\r\nno\r\n", &mut data);
+
+        let mut search = Search::new();
+        search.query = "match".into();
+        search.update(&data);
+        assert!(!search.is_complete());
+        // The newest lines searched start three lines down
+        assert_eq!(search.matches.len(), SCAN_LINES - 2);
+        assert_eq!(search.counter(), Some(format!("{0}/{0}+", SCAN_LINES - 2)));
+
+        // Output changes the last line, and history is searched in the background
+        translator.write(b"match", &mut data);
+        search.refresh(&data, data.line_count() - 1);
+        assert_eq!(search.matches.len(), SCAN_LINES - 1);
+        search.scan(&data);
+        assert!(search.is_complete());
+        assert_eq!(search.matches.len(), SCAN_LINES + 2);
+        // The current match stays put as matches are found around it
+        assert_eq!(search.current, Some(SCAN_LINES));
+
+        search.shift_up(2);
+        assert_eq!(search.matches.len(), SCAN_LINES);
+        assert_eq!(search.matches[0].row, 0);
+
+        // Going back from the first match searched finds older ones first
+        let mut search = Search::new();
+        search.query = "match".into();
+        search.update(&data);
+        search.current = Some(0);
+        assert_eq!(search.previous(&data).unwrap().row, 2);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::cast::take_utf8;
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/** Where an escape sequence being left out of the log has got to */
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Escape {
+    None,
+    /** Just after ESC */
+    Start,
+    /** A CSI sequence, which ends at a byte from `@` to `~` */
+    Csi,
+    /** An OSC, DCS or similar string, which ends at BEL or ST */
+    String,
+    /** ESC inside a string, maybe the start of ST */
+    StringEsc,
+}
+
+/**
+ * Appends the text a session prints to a file, like `script(1)`, optionally
+ * without escape sequences and with the time each line started
+ */
+pub struct SessionLog {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    timestamps: bool,
+    strip_escapes: bool,
+    /** The end of a UTF-8 sequence split across reads */
+    pending: Vec<u8>,
+    escape: Escape,
+    at_line_start: bool,
+}
+
+impl SessionLog {
+    pub fn open(path: &Path, timestamps: bool, strip_escapes: bool) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Unable to create {dir:?}"))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Unable to open {path:?}"))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            path: path.to_owned(),
+            timestamps,
+            strip_escapes,
+            pending: vec![],
+            escape: Escape::None,
+            at_line_start: true,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(data);
+        let text = take_utf8(&mut self.pending);
+        let text = if self.strip_escapes {
+            self.strip(&text)
+        } else {
+            text
+        };
+        for line in text.split_inclusive('\n') {
+            if self.at_line_start && self.timestamps {
+                write!(self.writer, "[{}] ", utc_time(SystemTime::now()))?;
+            }
+            self.writer.write_all(line.as_bytes())?;
+            self.at_line_start = line.ends_with('\n');
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /** Leave out escape sequences and control characters other than newlines and tabs */
+    fn strip(&mut self, text: &str) -> String {
+        let mut stripped = String::with_capacity(text.len());
+        for ch in text.chars() {
+            self.escape = match (self.escape, ch) {
+                (Escape::None, '\x1b') => Escape::Start,
+                (Escape::None, '\n' | '\t') => {
+                    stripped.push(ch);
+                    Escape::None
+                }
+                (Escape::None, ch) if ch.is_control() => Escape::None,
+                (Escape::None, ch) => {
+                    stripped.push(ch);
+                    Escape::None
+                }
+                (Escape::Start, '[') => Escape::Csi,
+                (Escape::Start, ']' | 'P' | 'X' | '^' | '_') => Escape::String,
+                // Intermediate bytes, as in `ESC ( B`, come before the final one
+                (Escape::Start, ' '..='/') => Escape::Start,
+                (Escape::Start, _) => Escape::None,
+                (Escape::Csi, '@'..='~') => Escape::None,
+                (Escape::Csi, _) => Escape::Csi,
+                (Escape::String, '\x07') => Escape::None,
+                (Escape::String, '\x1b') => Escape::StringEsc,
+                (Escape::String, _) => Escape::String,
+                (Escape::StringEsc, '\\') => Escape::None,
+                (Escape::StringEsc, _) => Escape::String,
+            };
+        }
+        stripped
+    }
+}
+
+/** A fresh file in `dir` to log a session to, going by the time */
+pub fn path(dir: &Path, tab: usize) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    dir.join(format!("firn-{seconds}-{}-{tab}.log", std::process::id()))
+}
+
+/** `time` as `YYYY-MM-DD HH:MM:SS` in UTC */
+fn utc_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+    // Howard Hinnant's days-to-civil algorithm, for days since 1970-01-01
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_utc_time() {
+        assert_eq!(utc_time(UNIX_EPOCH), "1970-01-01 00:00:00");
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(utc_time(time), "2024-02-29 12:34:56");
+    }
+
+    #[test]
+    fn test_write() {
+        let path =
+            std::env::temp_dir().join(format!("firn-test-session-log-{}.log", std::process::id()));
+        let mut log = SessionLog::open(&path, false, true).unwrap();
+        log.write(b"\x1b[1;31mred\x1b[0m \x1b]0;title\x07text\r\n\xd0")
+            .unwrap();
+        log.write(b"\x96 \x1b]8;;http://x\x1b\\link\x1b(B\x08!\n")
+            .unwrap();
+        let mut log = SessionLog::open(&path, false, false).unwrap();
+        log.write(b"\x1b[1mraw\n").unwrap();
+        drop(log);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "red text\n\u{416} link!\n\x1b[1mraw\n");
+    }
+}
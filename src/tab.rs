@@ -0,0 +1,156 @@
+use crate::child::SessionId;
+use crate::colors::ColorScheme;
+use crate::data::DataComponent;
+use crate::marks::Marks;
+use crate::selection::Selection;
+use crate::session_log::SessionLog;
+use crate::translator::Translator;
+use crate::width::WidthPolicy;
+use iced::widget::canvas;
+use iced::Font;
+use std::ops::Range;
+use std::time::Instant;
+
+/** Longest title shown on a tab before it is cut short */
+const MAX_LABEL_CHARS: usize = 24;
+
+/** A shell and everything needed to show it, independent of the other tabs */
+pub struct Tab {
+    pub session: SessionId,
+    pub data: DataComponent,
+    pub translator: Translator,
+    pub grid_cache: canvas::Cache,
+    /** How many lines back into history the view is scrolled */
+    pub scrollback: usize,
+    /** Lines of output that arrived below the view since it was scrolled back */
+    pub new_lines: usize,
+    pub selection: Option<Selection>,
+    /** Lines the user named, which last as long as the shell does */
+    pub marks: Marks,
+    /** Output arrived while another tab was showing */
+    pub activity: bool,
+    /** The bell rang while another tab was showing */
+    pub bell: bool,
+    /** Output stopped for `monitor.silence_secs` while another tab was showing */
+    pub silent: bool,
+    /** When output last arrived, while the tab is still to be marked silent once it stops */
+    pub last_output: Option<Instant>,
+    /** The profile the tab was opened with */
+    pub profile: Option<String>,
+    /** Typed into the shell once it is up, e.g. the command a restored tab was running */
+    pub pending_input: Option<String>,
+    /** Colors of the profile the tab was opened with, in place of the window's */
+    pub colors: Option<ColorScheme>,
+    /** Font of the profile the tab was opened with, in place of the window's */
+    pub font: Option<Font>,
+    /** Where the output is being logged to, if anywhere */
+    pub log: Option<SessionLog>,
+}
+
+impl Tab {
+    pub fn new(session: SessionId, colors: &ColorScheme, width_policy: WidthPolicy) -> Self {
+        let mut data = DataComponent::new();
+        data.set_default_colors(colors.foreground, colors.background);
+        data.set_width_policy(width_policy);
+        Self {
+            session,
+            data,
+            translator: Translator::new().unwrap(),
+            grid_cache: canvas::Cache::new(),
+            scrollback: 0,
+            new_lines: 0,
+            selection: None,
+            marks: Marks::default(),
+            activity: false,
+            bell: false,
+            silent: false,
+            last_output: None,
+            profile: None,
+            pending_input: None,
+            colors: None,
+            font: None,
+            log: None,
+        }
+    }
+
+    /** Forget everything shown so far, e.g. before a restarted shell starts writing */
+    pub fn reset(&mut self, colors: &ColorScheme, width_policy: WidthPolicy) {
+        let colors = self.colors.as_ref().unwrap_or(colors);
+        let mut tab = Self::new(self.session, colors, width_policy);
+        tab.profile = self.profile.take();
+        tab.colors = self.colors.take();
+        tab.font = self.font;
+        tab.log = self.log.take();
+        *self = tab;
+    }
+
+    /** Clear the cached grid if any rows changed since it was drawn */
+    pub fn apply_damage(&mut self) {
+        if !self.data.take_damage().is_empty() {
+            self.grid_cache.clear();
+        }
+    }
+
+    /** The lines in view when `rows` of them fit into the window */
+    pub fn visible_rows(&self, rows: usize) -> Range<usize> {
+        let line_count = self.data.line_count();
+        let first_line = line_count.saturating_sub(rows + self.scrollback);
+        first_line..(first_line + rows).min(line_count)
+    }
+
+    /** How far back the view is scrolled, the way vim's ruler puts it */
+    pub fn scroll_position(&self, max_scrollback: usize) -> String {
+        match self.scrollback {
+            0 => "Bot".into(),
+            scrollback if scrollback >= max_scrollback => "Top".into(),
+            scrollback => format!("{}%", 100 - 100 * scrollback / max_scrollback),
+        }
+    }
+
+    /** What the tab bar shows: the title set by the shell, or `fallback` */
+    pub fn label(&self, title: &str) -> String {
+        let mut label: String = title.chars().take(MAX_LABEL_CHARS).collect();
+        if title.chars().count() > MAX_LABEL_CHARS {
+            label.push('…');
+        }
+        if self.bell {
+            label.insert_str(0, "♪ ");
+        } else if self.silent {
+            label.insert_str(0, "○ ");
+        } else if self.activity {
+            label.insert_str(0, "● ");
+        }
+        label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::WindowSize;
+    use crate::child::SessionManager;
+    use crate::config::Config;
+
+    #[test]
+    fn test_label() {
+        let session =
+            SessionManager::new().spawn(Config::default(), WindowSize { rows: 24, cols: 80 });
+        let colors = ColorScheme::built_in("dark").unwrap();
+        let mut tab = Tab::new(session, &colors, WidthPolicy::Unicode);
+        assert_eq!(tab.label("bash"), "bash");
+
+        let title = "a very long title set by the shell";
+        tab.activity = true;
+        assert_eq!(tab.label(title), "● a very long title set by…");
+        tab.silent = true;
+        assert_eq!(tab.label(title), "○ a very long title set by…");
+        tab.bell = true;
+        assert_eq!(tab.label(title), "♪ a very long title set by…");
+
+        assert_eq!(tab.scroll_position(40), "Bot");
+        tab.scrollback = 10;
+        assert_eq!(tab.scroll_position(40), "75%");
+        tab.scrollback = 40;
+        assert_eq!(tab.scroll_position(40), "Top");
+    }
+}
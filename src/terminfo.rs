@@ -0,0 +1,48 @@
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tracing::info;
+
+/** The terminal type of firn's own terminfo entry */
+pub const TERM: &str = "firn";
+
+/**
+ * The terminal type advertised to the shell. firn's own entry lacks cursor
+ * addressing and screen clearing, so it is only used when the config's `env`
+ * sets `TERM` to [`TERM`].
+ */
+pub const DEFAULT_TERM: &str = "xterm-256color";
+
+const TERMINFO_SOURCE: &str = include_str!("../extra/firn.terminfo");
+
+/** Compile and install firn's terminfo entry into `~/.terminfo` using `tic` */
+pub fn install() -> Result<()> {
+    let output_dir =
+        PathBuf::from(env::var_os("HOME").context("HOME is not set")?).join(".terminfo");
+    // The source goes through stdin, so no file others could tamper with is involved
+    let mut tic = Command::new("tic")
+        .arg("-x")
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| "Unable to run tic")?;
+    let written = tic
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(TERMINFO_SOURCE.as_bytes());
+    let status = tic.wait().with_context(|| "Unable to run tic")?;
+    written.with_context(|| "Unable to pass the terminfo entry to tic")?;
+    if !status.success() {
+        bail!("tic failed to compile the terminfo entry");
+    }
+    info!(
+        "Installed terminfo entry {TERM} to {}",
+        output_dir.display()
+    );
+    Ok(())
+}
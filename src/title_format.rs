@@ -0,0 +1,58 @@
+use std::path::Path;
+
+/**
+ * Fill in the placeholders of a `title_format`, e.g. `{title} in {cwd}`.
+ * Placeholders that `value` does not know are left as they are.
+ */
+pub fn format(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut title = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        title.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after
+            .find('}')
+            .and_then(|end| Some((value(&after[..end])?, end)))
+        {
+            Some((text, end)) => {
+                title.push_str(&text);
+                rest = &after[end + 1..];
+            }
+            None => {
+                title.push('{');
+                rest = after;
+            }
+        }
+    }
+    title.push_str(rest);
+    title
+}
+
+/** A path with the home directory shortened to `~` */
+pub fn tilde(path: &Path) -> String {
+    let home = std::env::var_os("HOME").filter(|home| !home.is_empty());
+    match home.and_then(|home| path.strip_prefix(home).ok().map(Path::to_owned)) {
+        Some(relative) if relative.as_os_str().is_empty() => "~".into(),
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let value = |name: &str| match name {
+            "title" => Some("vim".to_string()),
+            "columns" => Some("80".into()),
+            "rows" => Some("24".into()),
+            _ => None,
+        };
+        assert_eq!(format("{title}", value), "vim");
+        assert_eq!(format("{title} ({columns}x{rows})", value), "vim (80x24)");
+        assert_eq!(format("{unknown} {title", value), "{unknown} {title");
+        assert_eq!(format("{{title}}", value), "{vim}");
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+/** Where and how big the window was when firn last closed, in logical pixels */
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+pub struct WindowState {
+    pub width: u32,
+    pub height: u32,
+    /** Unknown until the window manager reports a move */
+    pub position: Option<(i32, i32)>,
+}
+
+/** The per-user directory for state that is not configuration, following the XDG spec */
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(dir.into());
+    }
+    if cfg!(windows) {
+        return std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    }
+    let home = std::env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".local").join("state"))
+}
+
+fn path() -> Option<PathBuf> {
+    Some(state_dir()?.join("firn").join("window.json"))
+}
+
+impl WindowState {
+    /** What was saved last time, if anything */
+    pub fn load() -> Option<Self> {
+        let file = File::open(path()?).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = path() else {
+            bail!("Nowhere to save the window state: HOME is not set");
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Unable to create {dir:?}"))?;
+        }
+        let file = File::create(&path).with_context(|| format!("Unable to create {path:?}"))?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+}